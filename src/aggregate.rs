@@ -0,0 +1,86 @@
+use nom::IResult;
+
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use parser;
+
+// Warrior_aggregate selects aggregates of the Warriors, leads_count counts the objects of the leads relation
+pub const AGGREGATE_SUFFIX: &'static str = "_aggregate";
+pub const COUNT_SUFFIX: &'static str = "_count";
+pub const AGGREGATE_FUNCTIONS: [&'static str; 5] = ["count", "sum", "avg", "min", "max"];
+pub const AGGREGATE_ARGUMENTS: [&'static str; 1] = ["groupBy"];
+
+pub fn is_aggregate(query: &QueryObject) -> bool {
+    query.name.len() > AGGREGATE_SUFFIX.len() && query.name.ends_with(AGGREGATE_SUFFIX)
+}
+
+// The type an aggregate query is made on, Warrior_aggregate gives Warrior
+pub fn get_aggregate_target(name: &str) -> &str {
+    &name[..name.len() - AGGREGATE_SUFFIX.len()]
+}
+
+// The relation a count field counts, leads_count gives leads
+pub fn get_count_relation(name: &str) -> Option<&str> {
+    if name.len() > COUNT_SUFFIX.len() && name.ends_with(COUNT_SUFFIX) {
+        Some(&name[..name.len() - COUNT_SUFFIX.len()])
+    }
+    else {
+        None
+    }
+}
+
+// Splits avg(strength) into its function and column, count alone counts the rows and has no column
+pub fn split_aggregate_field(name: &str) -> Option<(&str, Option<&str>)> {
+    if name == "count" {
+        return Some((name, None));
+    }
+    match (name.find('('), name.ends_with(')')) {
+        (Some(index), true) if AGGREGATE_FUNCTIONS.contains(&&name[..index]) => Some((&name[..index], Some(&name[index + 1..name.len() - 1]))),
+        _ => None
+    }
+}
+
+// The columns given in groupBy: [race] or groupBy: race
+pub fn get_group_by(params: &Vec<(String, String)>) -> Result<Vec<String>, GraphqlError> {
+    let mut group_by: Vec<String> = Vec::new();
+    for &(ref name, ref value) in params {
+        if name != "groupBy" {
+            continue;
+        }
+        if !value.starts_with("[") {
+            group_by.push(value.clone());
+            continue;
+        }
+        match parser::parse_list_items(value.as_bytes()) {
+            IResult::Done(remaining, items) if remaining.is_empty() => group_by.extend(items.iter().map(|item| item.to_string())),
+            _ => return Err(GraphqlError::Validation("Argument \"groupBy\" has invalid value ".to_string() + value + ", expected a list of fields"))
+        }
+    }
+    Ok(group_by)
+}
+
+// The arguments of an aggregate query without groupBy, they filter the aggregated objects
+pub fn get_filter_arguments(params: &Option<Vec<(String, String)>>) -> Option<Vec<(String, String)>> {
+    params.as_ref()
+        .map(|params| params.iter().filter(|&&(ref name, _)| !AGGREGATE_ARGUMENTS.contains(&name.as_str())).cloned().collect::<Vec<(String, String)>>())
+        .and_then(|params| if params.is_empty() {None} else {Some(params)})
+}
+
+#[test]
+fn test_aggregate_fields(){
+    assert_eq!(get_aggregate_target("Warrior_aggregate"), "Warrior");
+    assert_eq!(get_count_relation("leads_count"), Some("leads"));
+    assert_eq!(get_count_relation("count"), None);
+    assert_eq!(split_aggregate_field("count"), Some(("count", None)));
+    assert_eq!(split_aggregate_field("avg(strength)"), Some(("avg", Some("strength"))));
+    assert_eq!(split_aggregate_field("median(strength)"), None);
+    assert_eq!(split_aggregate_field("race"), None);
+
+    let params = vec![("groupBy".to_string(), "[race, name]".to_string()), ("strength_gt".to_string(), "10".to_string())];
+    assert_eq!(get_group_by(&params), Ok(vec!["race".to_string(), "name".to_string()]));
+    assert_eq!(get_group_by(&vec![("groupBy".to_string(), "race".to_string())]), Ok(vec!["race".to_string()]));
+    assert_eq!(get_filter_arguments(&Some(params)), Some(vec![("strength_gt".to_string(), "10".to_string())]));
+    assert_eq!(get_filter_arguments(&Some(vec![("groupBy".to_string(), "race".to_string())])), None);
+}
@@ -1,14 +1,21 @@
 use eventual::Complete;
-use mio::tcp::*;
 use mio::Token;
+use mysql;
 use std::str;
 
-use nom::IResult;
 use serialize::*;
 use deserialize::*;
 
-use def::TargetPool;
+use def::{TargetPool, SqlStatement, QueryObject, MutationObject, ResultSet, Operation, QueryOperation, OperationResult};
+use error::GraphqlError;
 use parser;
+use validator;
+use variables;
+use fragments;
+use aggregate;
+use introspection;
+use introspection::Introspector;
+use json::JsonValue;
 
 
 pub enum GraphqlMsg{
@@ -16,38 +23,57 @@ pub enum GraphqlMsg{
     Request{
         operation: String,
         body: String,
-        tx: Complete<String,()>,
+        variables: Option<JsonValue>,
+        // selects the operation of a query document with several operations
+        operation_name: Option<String>,
+        tx: Complete<OperationResult, GraphqlError>,
     },
-    Response{
+    // operations executed in order in a single transaction, the future gets the response of each one
+    Transaction{
+        operations: Vec<(String, String)>,
+        tx: Complete<Vec<String>, GraphqlError>,
+    },
+    // raw SQL, the future gets its rows
+    Query{
         body: String,
+        tx: Complete<ResultSet, GraphqlError>,
+    },
+    Response{
+        token: Token,
+        body: Vec<u8>,
     },
     Shutdown
 }
 
-pub struct Connection {
-    // handle to the accepted socket
-    pub socket: TcpStream,
-
-    // token used to register with the event loop
-    pub token: Token,
+// Borrows the MySQL connection of a worker, taking a new one from the pool if there is none yet
+fn get_conn<'a>(conn: &'a mut Option<mysql::PooledConn>, pool: &mysql::Pool) -> Result<&'a mut mysql::PooledConn, GraphqlError> {
+    if conn.is_none() {
+        *conn = Some(try!(pool.get_conn()));
+    }
+    Ok(conn.as_mut().unwrap())
+}
 
+// Executes GraphQL requests against the target database
+pub struct Connection {
     pub request_messages: Vec<GraphqlMsg>,
-    pub response_messages: Vec<GraphqlMsg>,
+    conn: Option<mysql::PooledConn>,
+    transaction_open: bool,
     target: TargetPool,
     serializer: Serializer,
     deserializer: Deserializer
 }
 
 impl Connection {
-    pub fn new(socket:TcpStream, token: Token, target_pool: TargetPool, serializer: Serializer) -> Connection{
+    pub fn new(target_pool: TargetPool, serializer: Serializer) -> Connection{
+        let mut deserializer = Deserializer::new();
+        deserializer.store_tables(&mut target_pool.database.clone());
         Connection {
-            socket: socket,
-            token: token,
             request_messages: Vec::new(),
-            response_messages: Vec::new(),
+            conn: None,
+            transaction_open: false,
             target: target_pool,
             serializer: serializer,
-            deserializer: Deserializer::new()
+            deserializer: deserializer
         }
     }
 
@@ -55,117 +81,350 @@ impl Connection {
         self.request_messages.push(msg);
     }
 
+    pub fn execute(&mut self, operation: &str, body: &str, variables: Option<&JsonValue>, operation_name: Option<&str>) -> Result<OperationResult, GraphqlError> {
+        if variables.is_some() && operation != "get" && operation != "document" {
+            return Err(GraphqlError::Validation("Variables are only supported in queries".to_string()));
+        }
+        if operation_name.is_some() && operation != "get" && operation != "document" {
+            return Err(GraphqlError::Validation("An operation name is only supported in documents".to_string()));
+        }
+        let result = match operation {
+            "add" => {
+                self.add(body)
+            },
+            "get" => {
+                self.get(body, variables, operation_name)
+            },
+            "document" => {
+                self.execute_document(body, variables, operation_name)
+            },
+            "update" => {
+                self.update(body)
+            },
+            "delete" => {
+                self.delete(body)
+            },
+            "query" => {
+                self.process_mysql_query(body).map(|result_set| OperationResult::Json(result_set.to_json().to_string()))
+            },
+            "destroy_db" => {
+                self.destroy_database()
+            },
+            _ => Err(GraphqlError::Validation("Wrong operation type: ".to_string() + operation))
+        };
+        if let Err(GraphqlError::Connection(_)) = result {
+            // the MySQL connection is broken, the next request takes a fresh one
+            self.conn = None;
+        }
+        result
+    }
+
+    // Executes several operations in one transaction, none of them is applied if one fails
+    pub fn execute_transaction(&mut self, operations: &Vec<(String, String)>) -> Result<Vec<String>, GraphqlError> {
+        for &(ref operation, _) in operations {
+            // raw SQL could commit the transaction itself, DDL statements do implicitly
+            if operation == "destroy_db" || operation == "query" {
+                return Err(GraphqlError::Validation(operation.to_string() + " can not be executed in a transaction"));
+            }
+        }
+        let result = self.in_transaction(|connection| {
+            let mut responses: Vec<String> = Vec::new();
+            for &(ref operation, ref body) in operations {
+                responses.push(try!(connection.execute(operation, body, None, None)).into_string());
+            }
+            Ok(responses)
+        });
+        if let Err(GraphqlError::Connection(_)) = result {
+            self.conn = None;
+        }
+        result
+    }
+
+    // Runs a mutation between START TRANSACTION and COMMIT, rolling it back when any statement fails.
+    // Inside a transaction that is already open the mutation is part of it.
+    fn in_transaction<T, F>(&mut self, mutation: F) -> Result<T, GraphqlError>
+        where F: FnOnce(&mut Connection) -> Result<T, GraphqlError> {
+        if self.transaction_open {
+            return mutation(self);
+        }
+        {
+            let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+            try!(conn.query("START TRANSACTION"));
+        }
+        self.transaction_open = true;
+        let result = mutation(self);
+        self.transaction_open = false;
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        match result {
+            Ok(response) => {
+                try!(conn.query("COMMIT"));
+                Ok(response)
+            },
+            Err(err) => {
+                conn.query("ROLLBACK").ok();
+                Err(err)
+            }
+        }
+    }
+
     pub fn process(&mut self){
         while !self.request_messages.is_empty(){
             let msg = self.request_messages.remove(0);
             match msg {
-                GraphqlMsg::Request{operation, body, tx} => {
-                    let response_body = match operation.as_str(){
-                        "add" => {
-                            self.add(&body)
-                        },
-                        "get" => {
-                            self.get(&body)
-                        },
-                        "update" => {
-                            self.update(&body)
-                        },
-                        "delete" => {
-                            self.delete(&body)
-                        },
-                        "query" => {
-                            self.process_mysql_query(&body)
-                        },
-                        "destroy_db" => {
-                            self.destroy_database()
-                        },
-                        _ => panic!("Error: Wrong operation type")
-                    };
-                    self.response_messages.push(GraphqlMsg::Response{body: response_body.clone()});
-                    tx.complete(response_body);
+                GraphqlMsg::Request{operation, body, variables, operation_name, tx} => {
+                    match self.execute(&operation, &body, variables.as_ref(), operation_name.as_ref().map(|name| name.as_str())) {
+                        Ok(response_body) => tx.complete(response_body),
+                        Err(err) => tx.fail(err)
+                    }
+                },
+                GraphqlMsg::Query{body, tx} => {
+                    let result = self.process_mysql_query(&body);
+                    if let Err(GraphqlError::Connection(_)) = result {
+                        self.conn = None;
+                    }
+                    match result {
+                        Ok(result_set) => tx.complete(result_set),
+                        Err(err) => tx.fail(err)
+                    }
+                },
+                GraphqlMsg::Transaction{operations, tx} => {
+                    match self.execute_transaction(&operations) {
+                        Ok(response_bodies) => tx.complete(response_bodies),
+                        Err(err) => tx.fail(err)
+                    }
                 },
-                _ => panic!("Error: Unexpected type of message")
+                // the event loop messages carry no future to fail, the worker logs them and goes on
+                GraphqlMsg::Connect | GraphqlMsg::Response{..} | GraphqlMsg::Shutdown => {
+                    error!("A worker received a message of the event loop, it is ignored")
+                }
             }
         }
     }
 
-    pub fn get_responses (&mut self) -> Vec<GraphqlMsg>{
-        let mut result: Vec<GraphqlMsg> = Vec::new();
-        while !self.response_messages.is_empty() {
-            result.push(self.response_messages.remove(0));
+    pub fn get (&mut self, query: &str, variables: Option<&JsonValue>, operation_name: Option<&str>) -> Result<OperationResult, GraphqlError> {
+        match try!(parser::parse_operation_named(query.as_bytes(), operation_name)) {
+            Operation::Query(operation) => self.perform_query(operation, variables).map(OperationResult::Json),
+            Operation::Mutation(_) => Err(GraphqlError::Validation("The operation is a mutation, mutations are sent with POST".to_string()))
+        }
+    }
+
+    // Executes the query or the mutation selected in a document, the mutation type decides between add, update and delete
+    pub fn execute_document (&mut self, document: &str, variables: Option<&JsonValue>, operation_name: Option<&str>) -> Result<OperationResult, GraphqlError> {
+        match try!(parser::parse_operation_named(document.as_bytes(), operation_name)) {
+            Operation::Query(operation) => self.perform_query(operation, variables).map(OperationResult::Json),
+            Operation::Mutation(operation) => {
+                if variables.is_some() {
+                    return Err(GraphqlError::Validation("Variables are only supported in queries".to_string()));
+                }
+                match operation.kind.as_str() {
+                    "add" => self.add_mutation(operation.mutation),
+                    "update" => self.update_mutation(operation.mutation),
+                    _ => self.delete_mutation(operation.mutation)
+                }
+            }
         }
-        result
     }
 
-    pub fn get (&mut self, query: &str) -> String {
-        let select_query_data = parser::parse_query(query.as_bytes());
-        match select_query_data{
-            IResult::Done(_, select_structure) => {
-                let mysql_select_origin_ids = self.serializer.perform_get_ids((&self.target.working_database_name).to_string(), &select_structure);
-                let origin_ids : Vec<i32> = self.deserializer.perform_get_ids(&self.target.pool, mysql_select_origin_ids);
+    fn perform_query (&mut self, operation: QueryOperation, variables: Option<&JsonValue>) -> Result<String, GraphqlError> {
+        let values = try!(variables::resolve_variables(&operation.variables, variables));
+        try!(validator::validate_query(&self.target, &operation, &values));
+        let expanded = try!(fragments::expand_fragments(operation.query, &operation.fragments, &self.target.database));
+        let select_structure = try!(variables::bind_variables(&operation.variables, expanded, &values));
+        if introspection::is_introspection(&select_structure) {
+            return Introspector::new(&self.target.database, &self.target.enums).perform_introspection(&select_structure);
+        }
 
-                let mysql_select: String = self.serializer.perform_get((&self.target.working_database_name).to_string(), &select_structure);
-                let mysql_select_rels: String = self.serializer.perform_get_rels((&self.target.working_database_name).to_string(), &select_structure, origin_ids);
+        let mysql_select: SqlStatement = try!(self.serializer.perform_get((&self.target.working_database_name).to_string(), &select_structure));
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
 
-                self.deserializer.perform_get(&self.target.pool, mysql_select, mysql_select_rels, &select_structure)
-            },
-            IResult::Error (cause) => panic!("Graph_QL_Pool::get::Error: {}", cause),
-            IResult::Incomplete (_) => unimplemented!()
+        if aggregate::is_aggregate(&select_structure) {
+            return self.deserializer.perform_get_aggregate(conn, mysql_select, &select_structure);
         }
+        self.deserializer.perform_get(conn, &self.serializer, &self.target.working_database_name, mysql_select, &select_structure)
+    }
+
+    // Reads back the objects affected by a mutation, in the same shape as get
+    fn get_affected(&mut self, selection: &QueryObject, ids: &Vec<i32>) -> Result<String, GraphqlError> {
+        let mysql_select: SqlStatement = try!(self.serializer.perform_get_by_ids((&self.target.working_database_name).to_string(), selection, ids));
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        self.deserializer.perform_get(conn, &self.serializer, &self.target.working_database_name, mysql_select, selection)
     }
 
-    pub fn add (&mut self, query: &str) -> String {
-        let insert_query_data = parser::parse_mutation_query(query.as_bytes());
-        match insert_query_data{
-            IResult::Done(_, insert_structure) => {
+    // Inserts an object and the objects it relates to, returns the id of the inserted object
+    fn insert_object(&mut self, insert_structure: &MutationObject) -> Result<i32, GraphqlError> {
+        let mysql_insert: SqlStatement = try!(self.serializer.perform_add_mutation((&self.target.working_database_name).to_string(), insert_structure));
+        let inserted_id = {
+            let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+            let insert_result = try!(conn.prep_exec(mysql_insert.query, mysql_insert.params));
+            insert_result.last_insert_id() as i32
+        };
 
-                let mut conn = self.target.pool.get_conn().unwrap();
-                self.serializer.perform_add_mutation(&mut conn, (&self.target.working_database_name).to_string(), &insert_structure);
-            },
-            IResult::Error (_) => unimplemented!(),
-            IResult::Incomplete (_) => unimplemented!()
+        for rels in try!(self.serializer.get_mutation_attrs(insert_structure)) {
+            if let Some(related_objects) = rels.attrs.as_ref() {
+                let target_ids = try!(self.get_related_ids(related_objects));
+                try!(self.add_rels(insert_structure, rels, inserted_id, &target_ids));
+            }
         }
-        "add response completed".to_string()
+        Ok(inserted_id)
     }
 
-    pub fn update (&mut self, query: &str) -> String {
-        let update_query_data = parser::parse_mutation_query(query.as_bytes());
-        match update_query_data{
-            IResult::Done(_, update_structure) => {
-                let mysql_update: String = self.serializer.perform_update_mutation((&self.target.working_database_name).to_string(), &update_structure);
+    // Creates the related objects given with fields and finds the ones given with a filter
+    fn get_related_ids(&mut self, related_objects: &Vec<MutationObject>) -> Result<Vec<i32>, GraphqlError> {
+        let mut target_ids: Vec<i32> = Vec::new();
+        for related_object in related_objects {
+            if related_object.attrs.is_some() {
+                target_ids.push(try!(self.insert_object(related_object)));
+            }
+            else {
+                target_ids.append(&mut try!(self.get_filtered_ids(related_object)));
+            }
+        }
+        Ok(target_ids)
+    }
 
-                let mut conn = self.target.pool.get_conn().unwrap();
-                conn.query(&mysql_update).unwrap();
-            },
-            IResult::Error (_) => unimplemented!(),
-            IResult::Incomplete (_) => unimplemented!()
+    fn add_rels(&mut self, origin: &MutationObject, rels: &MutationObject, origin_id: i32, target_ids: &Vec<i32>) -> Result<(), GraphqlError> {
+        if target_ids.is_empty() {
+            return Ok(());
         }
-        "update response completed".to_string()
+        let mysql_insert_rels = try!(self.serializer.perform_add_rels((&self.target.working_database_name).to_string(), origin, rels, origin_id, target_ids));
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        try!(conn.prep_exec(mysql_insert_rels.query, mysql_insert_rels.params));
+        Ok(())
     }
 
-    pub fn delete (&mut self, query: &str) -> String {
-        let delete_query_data = parser::parse_mutation_query(query.as_bytes());
-        match delete_query_data{
-            IResult::Done(_, delete_structure) => {
-                let mysql_delete: String = self.serializer.perform_delete_mutation((&self.target.working_database_name).to_string(), &delete_structure);
-                let mut conn = self.target.pool.get_conn().unwrap();
-                conn.query(&mysql_delete).unwrap();
-            },
-            IResult::Error (_) => unimplemented!(),
-            IResult::Incomplete (_) => unimplemented!()
+    fn delete_rels(&mut self, origin: &MutationObject, rels: &MutationObject, origin_ids: &Vec<i32>, target_ids: Option<&Vec<i32>>) -> Result<(), GraphqlError> {
+        if origin_ids.is_empty() || target_ids.map(|ids| ids.is_empty()).unwrap_or(false) {
+            return Ok(());
         }
-        "delete response completed".to_string()
+        let mysql_delete_rels = try!(self.serializer.perform_delete_rels((&self.target.working_database_name).to_string(), origin, rels, origin_ids, target_ids));
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        try!(conn.prep_exec(mysql_delete_rels.query, mysql_delete_rels.params));
+        Ok(())
+    }
+
+    // Finds the ids of the objects matching the filter of a mutation, Weapon (name: Bow)
+    fn get_filtered_ids(&mut self, mutation_structure: &MutationObject) -> Result<Vec<i32>, GraphqlError> {
+        let filter = QueryObject{
+            name: mutation_structure.name.clone(),
+            alias: None,
+            params: mutation_structure.params.clone(),
+            attrs: None,
+            position: mutation_structure.position,
+            param_positions: mutation_structure.param_positions.clone()
+        };
+        let mysql_select_ids = try!(self.serializer.perform_get_ids((&self.target.working_database_name).to_string(), &filter));
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        self.deserializer.perform_get_ids(conn, mysql_select_ids)
+    }
+
+    pub fn add (&mut self, query: &str) -> Result<OperationResult, GraphqlError> {
+        let insert_structure = try!(parser::parse_mutation(query.as_bytes()));
+        self.add_mutation(insert_structure)
+    }
+
+    fn add_mutation (&mut self, insert_structure: MutationObject) -> Result<OperationResult, GraphqlError> {
+        try!(validator::validate_mutation(&self.target, "add", &insert_structure));
+
+        self.in_transaction(|connection| {
+            let inserted_id = try!(connection.insert_object(&insert_structure));
+            match connection.serializer.get_mutation_selection(&insert_structure) {
+                Some(selection) => connection.get_affected(&selection, &vec![inserted_id]).map(OperationResult::Json),
+                None => Ok(OperationResult::Status("add response completed".to_string()))
+            }
+        })
+    }
+
+    pub fn update (&mut self, query: &str) -> Result<OperationResult, GraphqlError> {
+        let update_structure = try!(parser::parse_mutation(query.as_bytes()));
+        self.update_mutation(update_structure)
+    }
+
+    fn update_mutation (&mut self, update_structure: MutationObject) -> Result<OperationResult, GraphqlError> {
+        try!(validator::validate_mutation(&self.target, "update", &update_structure));
+
+        let mysql_update: Option<SqlStatement> = {
+            let columns = try!(self.serializer.get_mutation_attrs(&update_structure));
+            // an update may only change relations
+            if columns.iter().any(|col| col.attrs.is_some()) && !columns.iter().any(|col| col.value.is_some()) {
+                None
+            }
+            else {
+                Some(try!(self.serializer.perform_update_mutation((&self.target.working_database_name).to_string(), &update_structure)))
+            }
+        };
+
+        self.in_transaction(|connection| connection.update_objects(&update_structure, mysql_update))
+    }
+
+    fn update_objects (&mut self, update_structure: &MutationObject, mysql_update: Option<SqlStatement>) -> Result<OperationResult, GraphqlError> {
+        // the ids are taken before the update, which may change the columns the objects are filtered by
+        let updated_ids = try!(self.get_filtered_ids(update_structure));
+        if let Some(mysql_update) = mysql_update {
+            let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+            try!(conn.prep_exec(mysql_update.query, mysql_update.params));
+        }
+
+        for rels in try!(self.serializer.get_mutation_attrs(update_structure)) {
+            for operation in rels.attrs.as_ref().unwrap_or(&Vec::new()) {
+                let target_ids = try!(self.get_related_ids(operation.attrs.as_ref().unwrap_or(&Vec::new())));
+                match operation.name.as_str() {
+                    "set" => try!(self.delete_rels(update_structure, rels, &updated_ids, None)),
+                    // existing links are removed first so connecting twice does not duplicate them
+                    _ => try!(self.delete_rels(update_structure, rels, &updated_ids, Some(&target_ids)))
+                }
+                if operation.name != "disconnect" {
+                    for origin_id in &updated_ids {
+                        try!(self.add_rels(update_structure, rels, *origin_id, &target_ids));
+                    }
+                }
+            }
+        }
+
+        match self.serializer.get_mutation_selection(update_structure) {
+            Some(selection) => self.get_affected(&selection, &updated_ids).map(OperationResult::Json),
+            None => Ok(OperationResult::Status("update response completed".to_string()))
+        }
+    }
+
+    pub fn delete (&mut self, query: &str) -> Result<OperationResult, GraphqlError> {
+        let delete_structure = try!(parser::parse_mutation(query.as_bytes()));
+        self.delete_mutation(delete_structure)
+    }
+
+    fn delete_mutation (&mut self, delete_structure: MutationObject) -> Result<OperationResult, GraphqlError> {
+        try!(validator::validate_mutation(&self.target, "delete", &delete_structure));
+        let mysql_delete: SqlStatement = try!(self.serializer.perform_delete_mutation((&self.target.working_database_name).to_string(), &delete_structure));
+
+        self.in_transaction(|connection| {
+            let deleted_ids = try!(connection.get_filtered_ids(&delete_structure));
+            let conn = try!(get_conn(&mut connection.conn, &connection.target.pool));
+            // the deleted objects are read before they are gone
+            let response = match connection.serializer.get_mutation_selection(&delete_structure) {
+                Some(selection) => {
+                    let mysql_select: SqlStatement = try!(connection.serializer.perform_get((&connection.target.working_database_name).to_string(), &selection));
+                    OperationResult::Json(try!(connection.deserializer.perform_get(conn, &connection.serializer, &connection.target.working_database_name, mysql_select, &selection)))
+                },
+                None => OperationResult::Status("delete response completed".to_string())
+            };
+            // the links of the deleted objects are removed with them, in both directions
+            if !deleted_ids.is_empty() {
+                for mysql_delete_rels in connection.serializer.perform_delete_object_rels((&connection.target.working_database_name).to_string(), &delete_structure.name, &deleted_ids) {
+                    try!(conn.prep_exec(mysql_delete_rels.query, mysql_delete_rels.params));
+                }
+            }
+            try!(conn.prep_exec(mysql_delete.query, mysql_delete.params));
+            Ok(response)
+        })
     }
 
-    pub fn process_mysql_query (&mut self, query: &str) -> String {
-        let mut conn = self.target.pool.get_conn().unwrap();
-        conn.query(query).unwrap();
-        "query executed".to_string()
+    pub fn process_mysql_query (&mut self, query: &str) -> Result<ResultSet, GraphqlError> {
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        self.deserializer.perform_mysql_query(conn, query)
     }
 
-    pub fn destroy_database (&mut self) -> String {
-        let mut conn = self.target.pool.get_conn().unwrap();
-        conn.query("DROP DATABASE ".to_string() + (&self.target.working_database_name) + ";").unwrap();
-        "database dropped".to_string()
+    pub fn destroy_database (&mut self) -> Result<OperationResult, GraphqlError> {
+        let conn = try!(get_conn(&mut self.conn, &self.target.pool));
+        try!(conn.query("DROP DATABASE ".to_string() + (&self.target.working_database_name) + ";"));
+        Ok(OperationResult::Status("database dropped".to_string()))
     }
 }
\ No newline at end of file
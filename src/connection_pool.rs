@@ -1,89 +1,181 @@
-use eventual::{Async};
+use eventual::{Async, AsyncError, Future};
 
 use mio::*;
-use mio::tcp::{TcpStream, TcpListener};
+use mio::tcp::TcpListener;
 use mio::util::Slab;
 
 use std::net::SocketAddr;
 use std::thread;
-use std::vec::Vec;
 
 use connection::*;
-use def::TargetPool;
+use def::{TargetPool, OperationResult};
+use error::GraphqlError;
+use http;
+use http::HttpConnection;
 use serialize;
+use worker_pool::WorkerPool;
 
 
 pub const SERVER_TOKEN: Token = Token(0);
 
+pub const SERVER_ADDRESS: &'static str = "0.0.0.0:10000";
+
 pub const NUMBER_OF_CONNECTIONS: usize = 20;
 
 pub struct ConnectionPool {
     socket: TcpListener,
-    token_counter: usize,
-    connections: Slab<Connection>,
-    request_messages: Vec<GraphqlMsg>,
-    response_messages: Vec<GraphqlMsg>,
-    target: TargetPool,
-    serializer: serialize::Serializer
+    connections: Slab<HttpConnection>,
+    workers: WorkerPool
 }
 
 impl ConnectionPool {
 
-    pub fn new(target_pool: TargetPool, serializer: serialize::Serializer) -> Sender<GraphqlMsg> {
-        let addr: SocketAddr = "0.0.0.0:10000".parse::<SocketAddr>()
+    // Starts the event loop serving SERVER_ADDRESS in its own thread, failing when the server can not be started
+    pub fn new(target_pool: TargetPool, serializer: serialize::Serializer, number_of_connections: usize) -> Result<Sender<GraphqlMsg>, GraphqlError> {
+        let addr: SocketAddr = SERVER_ADDRESS.parse::<SocketAddr>()
             .ok().expect("Failed to parse host:port string");
-        let server_socket = TcpListener::bind(&addr).ok().expect("Failed to bind address");
+        let server_socket = try!(TcpListener::bind(&addr)
+            .map_err(|e| GraphqlError::Connection("Failed to bind ".to_string() + SERVER_ADDRESS + ": " + &e.to_string())));
 
-        let mut event_loop = EventLoop::<ConnectionPool>::new().ok().expect("Failed to create event loop");
-        event_loop.register(&server_socket,
-                            SERVER_TOKEN,
-                            EventSet::readable(),
-                            PollOpt::edge()).unwrap();
+        let mut event_loop = try!(EventLoop::<ConnectionPool>::new()
+            .map_err(|e| GraphqlError::Connection("Failed to create the event loop: ".to_string() + &e.to_string())));
+        try!(event_loop.register(&server_socket,
+                                 SERVER_TOKEN,
+                                 EventSet::readable(),
+                                 PollOpt::edge())
+            .map_err(|e| GraphqlError::Connection("Failed to register the server socket: ".to_string() + &e.to_string())));
 
         let mut pool = ConnectionPool {
             socket: server_socket,
             connections: Slab::new_starting_at(Token(1), 32768),
-            token_counter: 1,
-            request_messages: Vec::new(),
-            response_messages: Vec::new(),
-            target: target_pool,
-            serializer: serializer
+            workers: WorkerPool::new(number_of_connections, target_pool, serializer)
         };
-        pool.create_connections(addr);
 
         let sender = event_loop.channel();
 
-        thread::Builder::new().name("event_handler".to_string()).spawn(move || {
-            event_loop.run(&mut pool).ok().expect("Failed to start event loop");
-        });
+        try!(thread::Builder::new().name("event_handler".to_string()).spawn(move || {
+            if let Err(e) = event_loop.run(&mut pool) {
+                error!("The event loop stopped: {}", e);
+            }
+        }).map_err(|e| GraphqlError::Connection("Failed to start the event loop: ".to_string() + &e.to_string())));
 
-        sender
+        Ok(sender)
     }
 
-    fn create_connections(&mut self, addr: SocketAddr){
-        let client_socket = TcpStream::connect(&addr).ok().expect("Failed to unwrap the socket");
+    fn accept(&mut self, event_loop: &mut EventLoop<ConnectionPool>) {
+        loop {
+            let client_socket = match self.socket.accept() {
+                Err(e) => {
+                    error!("Accept error: {}", e);
+                    return;
+                },
+                Ok(None) => return,
+                Ok(Some((client_socket, _))) => client_socket
+            };
+            let token = match self.connections.insert_with(|token| HttpConnection::new(client_socket, token)) {
+                Some(token) => token,
+                None => {
+                    error!("Too many open connections, dropping the new one");
+                    return;
+                }
+            };
+            if let Err(e) = event_loop.register(&self.connections[token].socket, token,
+                                                EventSet::readable(), PollOpt::edge() | PollOpt::oneshot()) {
+                error!("Failed to register a connection: {}", e);
+                self.connections.remove(token);
+                continue;
+            }
+        }
+    }
+
+    fn close(&mut self, event_loop: &mut EventLoop<ConnectionPool>, token: Token) {
+        if let Some(connection) = self.connections.remove(token) {
+            event_loop.deregister(&connection.socket).ok();
+        }
+    }
+
+    // Hands the request to the workers, the response comes back to the event loop as a GraphqlMsg::Response
+    fn dispatch_request(&mut self, event_loop: &mut EventLoop<ConnectionPool>, token: Token, request: &http::HttpRequest) {
+        let graphql_request = match http::graphql_request(request) {
+            Ok(graphql_request) => graphql_request,
+            Err(err) => {
+                let response = http::response(http::error_status(&err), &http::error_body(&err, None));
+                return self.respond(event_loop, token, response);
+            }
+        };
+        let (tx, future) = Future::<OperationResult, GraphqlError>::pair();
+        self.workers.dispatch(GraphqlMsg::Request{
+            operation: graphql_request.operation.clone(),
+            body: graphql_request.query.clone(),
+            variables: graphql_request.variables.clone(),
+            operation_name: graphql_request.operation_name.clone(),
+            tx: tx
+        });
+
+        let sender = event_loop.channel();
+        future.receive(move |result| {
+            let result = match result {
+                Ok(body) => Ok(body),
+                Err(AsyncError::Failed(err)) => Err(err),
+                Err(AsyncError::Aborted) => Err(GraphqlError::Connection("The request was aborted".to_string()))
+            };
+            sender.send(GraphqlMsg::Response{
+                token: token,
+                body: http::graphql_response(&graphql_request, result)
+            }).ok();
+        });
     }
 
-/*
-    fn create_connection(&mut self, event_loop: &mut EventLoop<ConnectionPool>,address:&IpAddr) -> RCResult<Token>{
-        //println!("[ConnectionPool::create_connection]");
-        let mut conn = try_rc!(connect(SocketAddr::new(address.clone(),9042),
-                                None,
-                                event_loop,
-                                self.event_handler.clone()),"Failed connecting");
-        let token = try_rc!(self.add_connection(address.clone(),conn),"Failed adding a new connection");
-        Ok(token)
+    fn respond(&mut self, event_loop: &mut EventLoop<ConnectionPool>, token: Token, response: Vec<u8>) {
+        if self.connections.contains(token) {
+            self.connections[token].respond(response);
+            self.writable(event_loop, token);
+        }
     }
-*/
-    fn find_connection_by_token(&mut self, token: Token) -> Result<&mut Connection,&'static str>{
-        println!("[ConnectionPool::find_connection_by_token]");
-        if !self.connections.is_empty() {
-            let conn = Ok(self.connections.get_mut(token).unwrap());
-            return conn;
+
+    fn readable(&mut self, event_loop: &mut EventLoop<ConnectionPool>, token: Token, events: EventSet) {
+        let closed = match self.connections[token].read() {
+            Ok(closed) => closed,
+            Err(e) => {
+                error!("Read error: {}", e);
+                return self.close(event_loop, token);
+            }
+        };
+        match self.connections[token].request() {
+            Ok(Some(request)) => self.dispatch_request(event_loop, token, &request),
+            Ok(None) => {
+                if closed || events.is_hup() {
+                    return self.close(event_loop, token);
+                }
+                if let Err(e) = event_loop.reregister(&self.connections[token].socket, token, EventSet::readable(),
+                                                      PollOpt::edge() | PollOpt::oneshot()) {
+                    error!("Failed to reregister a connection: {}", e);
+                    self.close(event_loop, token);
+                }
+            },
+            Err(err) => {
+                let response = http::response(http::error_status(&err), &http::error_body(&err, None));
+                self.respond(event_loop, token, response);
+            }
         }
-        Err("There is no connection found")
     }
 
+    fn writable(&mut self, event_loop: &mut EventLoop<ConnectionPool>, token: Token) {
+        match self.connections[token].write() {
+            Ok(false) => {
+                if let Err(e) = event_loop.reregister(&self.connections[token].socket, token, EventSet::writable(),
+                                                      PollOpt::edge() | PollOpt::oneshot()) {
+                    error!("Failed to reregister a connection: {}", e);
+                    self.close(event_loop, token);
+                }
+            },
+            Ok(true) => self.close(event_loop, token),
+            Err(e) => {
+                error!("Write error: {}", e);
+                self.close(event_loop, token);
+            }
+        }
+    }
 }
 
 impl Handler for ConnectionPool {
@@ -91,7 +183,12 @@ impl Handler for ConnectionPool {
     type Message = GraphqlMsg;
 
     fn notify(&mut self, event_loop: &mut EventLoop<ConnectionPool>, msg: GraphqlMsg) {
-        self.request_messages.push(msg);
+        match msg {
+            GraphqlMsg::Request{..} | GraphqlMsg::Transaction{..} | GraphqlMsg::Query{..} => self.workers.dispatch(msg),
+            GraphqlMsg::Response{token, body} => self.respond(event_loop, token, body),
+            GraphqlMsg::Shutdown => event_loop.shutdown(),
+            GraphqlMsg::Connect => {}
+        }
     }
 
     fn ready(&mut self,
@@ -101,47 +198,25 @@ impl Handler for ConnectionPool {
             )
     {
         match token {
-            // A read event for our `Server` token means we are establishing a new connection.
-            SERVER_TOKEN => {
-                let client_socket = match self.socket.accept() {
-                    Err(e) => {
-                        println!("Accept error: {}", e);
-                        return;
-                    },
-                    Ok(None) => unreachable!("Accept has returned 'None'"),
-                    Ok(Some((client_socket, addr))) => client_socket
-                };
-                let new_token = Token(self.token_counter);
-                self.token_counter += 1;
-                let connection = Connection::new(client_socket, new_token, self.target.clone(), self.serializer.clone());
-
-                self.connections.insert(connection);
-                event_loop.register(&self.connections[new_token].socket, new_token,
-                                    EventSet::writable(), PollOpt::oneshot()
-                ).or_else(|e| {Err(e)});
-            }
+            // A read event for our `Server` token means we are establishing new connections.
+            SERVER_TOKEN => self.accept(event_loop),
             token => {
-                let mut connection = self.connections.get_mut(token).unwrap();
-                //we're getting response
-                if events.is_readable() {
-                    self.response_messages.append(&mut connection.get_responses());
-                    event_loop.reregister(&connection.socket, connection.token, EventSet::writable(),
-                                          PollOpt::edge() | PollOpt::oneshot()).unwrap();
+                if !self.connections.contains(token) {
+                    return;
                 }
-                if events.is_writable(){
-                    if !self.request_messages.is_empty(){
-                        //write request to the client and reregister it to readable.
-                        connection.push_request(self.request_messages.remove(0));
-                        connection.process();
-                        event_loop.reregister(&connection.socket, connection.token, EventSet::readable(),
-                                              PollOpt::edge() | PollOpt::oneshot()).unwrap();
-
-                    }
-                    else {
-                        //no requests, do nothing
-                    }
+                if events.is_error() {
+                    self.close(event_loop, token);
+                }
+                else if events.is_readable() {
+                    self.readable(event_loop, token, events);
+                }
+                else if events.is_writable() {
+                    self.writable(event_loop, token);
+                }
+                else if events.is_hup() {
+                    self.close(event_loop, token);
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
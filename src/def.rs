@@ -2,6 +2,8 @@ use std::vec::Vec;
 
 use mysql;
 
+use json::JsonValue;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DbColumn {
     pub name: String,
@@ -15,28 +17,229 @@ pub struct DbTable {
     pub columns: Vec<DbColumn>
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DbEnum {
+    pub name: String,
+    pub values: Vec<String>
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SchemaDefinition {
+    Type(String, Vec<(String, String, bool)>),
+    Enum(String, Vec<String>)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SqlStatement {
+    pub query: String,
+    pub params: Vec<mysql::Value>
+}
+
+// The rows of a raw SQL query with the names of their columns, the values are typed by the column types
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<JsonValue>>,
+    pub affected_rows: u64,
+    pub last_insert_id: u64
+}
+
+impl ResultSet {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("columns".to_string(), JsonValue::Array(self.columns.iter().map(|column| JsonValue::String(column.clone())).collect())),
+            ("rows".to_string(), JsonValue::Array(self.rows.iter().map(|row| JsonValue::Array(row.clone())).collect())),
+            ("affectedRows".to_string(), JsonValue::Int(self.affected_rows as i64)),
+            ("lastInsertId".to_string(), JsonValue::Int(self.last_insert_id as i64))
+        ])
+    }
+}
+
+// The result of an operation, the JSON response of a query or of a mutation selecting fields,
+// or the status message of an operation without a selection set
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OperationResult {
+    Json(String),
+    Status(String)
+}
+
+impl OperationResult {
+    // The response as the futures of GraphQLPool give it
+    pub fn into_string(self) -> String {
+        match self {
+            OperationResult::Json(body) => body,
+            OperationResult::Status(message) => message
+        }
+    }
+}
+
+// A column of the database as INFORMATION_SCHEMA describes it, column_type is its COLUMN_TYPE, like int(11)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExistingColumn {
+    pub table_name: String,
+    pub name: String,
+    pub column_type: String,
+    pub is_mandatory: bool
+}
+
+// The statements that bring the database to a version of the schema, none when it is up to date.
+// The destructive statements drop columns or modify them in ways that can lose their values.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Migration {
+    pub version: String,
+    pub statements: Vec<String>,
+    pub destructive_statements: Vec<String>
+}
+
+// The table the applied migrations are recorded in
+pub const HISTORY_TABLE: &'static str = "schema_migrations";
+
 #[derive(Clone)]
 pub struct TargetPool {
     pub pool: mysql::Pool,
     pub database: Vec<DbTable>,
+    pub enums: Vec<DbEnum>,
     pub working_database_name: String
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// position is the byte offset of the name in the query, param_positions the ones of the name and the value
+// of each argument, in the order of params. They are 0 for the fields made by the library.
+#[derive(Debug, Clone)]
 pub struct QueryObject {
     pub name: String,
+    pub alias: Option<String>,
     pub params: Option<Vec <(String, String)> >,
-    pub attrs: Option<Vec <QueryObject> >
+    pub attrs: Option<Vec <QueryObject> >,
+    pub position: usize,
+    pub param_positions: Vec<(usize, usize)>
+}
+
+// The same fields written at different places of a query are equal
+impl PartialEq for QueryObject {
+    fn eq(&self, other: &QueryObject) -> bool {
+        self.name == other.name && self.alias == other.alias && self.params == other.params && self.attrs == other.attrs
+    }
+}
+
+impl Eq for QueryObject {}
+
+// $id: Int! = 3 in the definition of a query operation
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VariableDefinition {
+    pub name: String,
+    pub var_type: String,
+    pub is_mandatory: bool,
+    pub default: Option<String>
+}
+
+// fragment Name on Type { ... }, position is the byte offset of its type condition in the query
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Fragment {
+    pub name: String,
+    pub on: String,
+    pub attrs: Vec<QueryObject>,
+    pub position: usize
 }
 
 #[derive(Debug, PartialEq, Eq)]
+pub struct QueryOperation {
+    pub name: Option<String>,
+    pub variables: Vec<VariableDefinition>,
+    pub query: QueryObject,
+    pub fragments: Vec<Fragment>
+}
+
+// mutation Name { add { Weapon { name: Bow } } }, kind is the mutation executed: add, update or delete
+#[derive(Debug, PartialEq, Eq)]
+pub struct MutationOperation {
+    pub name: Option<String>,
+    pub kind: String,
+    pub mutation: MutationObject
+}
+
+// The operation of a document selected for execution
+#[derive(Debug, PartialEq, Eq)]
+pub enum Operation {
+    Query(QueryOperation),
+    Mutation(MutationOperation)
+}
+
+// In a selection, a QueryObject named ...Name spreads the fragment Name
+// and one named ... on Type holds the fields of an inline fragment
+pub const FRAGMENT_SPREAD: &'static str = "...";
+pub const INLINE_FRAGMENT: &'static str = "... on ";
+// Selected on any object, it gives the name of its type
+pub const TYPENAME: &'static str = "__typename";
+
+impl QueryObject {
+    // The key the field is returned under, its alias when it has one
+    pub fn key(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.name)
+    }
+
+    // The id field is not selected as a column, it is the id every row starts with
+    pub fn is_id(&self) -> bool {
+        self.name == "id" && self.attrs.is_none()
+    }
+
+    pub fn fragment_spread(&self) -> Option<&str> {
+        if self.name.starts_with(FRAGMENT_SPREAD) && !self.name.starts_with(INLINE_FRAGMENT) {
+            Some(&self.name[FRAGMENT_SPREAD.len()..])
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn inline_fragment(&self) -> Option<&str> {
+        if self.name.starts_with(INLINE_FRAGMENT) {
+            Some(&self.name[INLINE_FRAGMENT.len()..])
+        }
+        else {
+            None
+        }
+    }
+}
+
+// The condition arguments put on the objects of a type, strength_gt: 40 is a condition with the gt operator,
+// plain arguments use eq
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Filter {
+    Condition{column: String, operator: String, values: Vec<String>},
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>)
+}
+
+// The orderBy, first and offset arguments of a query or a relation field,
+// order_by holds the ordered fields with their direction, ASC or DESC
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pagination {
+    pub order_by: Vec<(String, String)>,
+    pub first: Option<u64>,
+    pub offset: Option<u64>
+}
+
+// The positions are byte offsets in the query like the ones of a QueryObject, value_position is the one of the value
+#[derive(Debug, Clone)]
 pub struct MutationObject {
     pub name: String,
     pub value: Option <String>,
     pub params: Option<Vec <(String, String)> >,
-    pub attrs: Option<Vec <MutationObject> >
+    pub attrs: Option<Vec <MutationObject> >,
+    pub position: usize,
+    pub value_position: usize,
+    pub param_positions: Vec<(usize, usize)>
 }
 
+impl PartialEq for MutationObject {
+    fn eq(&self, other: &MutationObject) -> bool {
+        self.name == other.name && self.value == other.value && self.params == other.params && self.attrs == other.attrs
+    }
+}
+
+impl Eq for MutationObject {}
+
 #[derive(Clone)]
 pub struct Relation {
     pub name: String,
@@ -1,131 +1,360 @@
 use mysql;
+use std::collections::HashMap;
 use def;
+use serialize::Serializer;
+use error::GraphqlError;
+use json;
+use json::JsonValue;
+use relay;
+use aggregate;
+#[cfg(test)]
+use test_support::{column, field};
+
+// The objects related through a relation field to each of the objects they were loaded for, by origin id
+struct LoadedRelation {
+    target: String,
+    objects: HashMap<i32, Vec<(i32, Vec<mysql::Value>)>>,
+    relations: Vec<LoadedRelation>
+}
+
+// Turns the value of a column into JSON according to the type of the column in the schema
+pub fn value_to_json(column_type: &str, value: mysql::Value) -> JsonValue {
+    match value {
+        mysql::Value::NULL => JsonValue::Null,
+        mysql::Value::Int(number) => if column_type == "BOOLEAN" {JsonValue::Boolean(number != 0)} else {JsonValue::Int(number)},
+        mysql::Value::UInt(number) => if column_type == "BOOLEAN" {JsonValue::Boolean(number != 0)} else {JsonValue::Int(number as i64)},
+        mysql::Value::Float(number) => JsonValue::Float(number),
+        // values read as text, an INT or BOOLEAN column still gives a number or a boolean
+        mysql::Value::Bytes(bytes) => {
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            match column_type {
+                "INT" => text.parse::<i64>().map(JsonValue::Int).unwrap_or(JsonValue::String(text)),
+                "BOOLEAN" => JsonValue::Boolean(text != "0"),
+                // averages of INT columns
+                "DECIMAL" => text.parse::<f64>().map(JsonValue::Float).unwrap_or(JsonValue::String(text)),
+                _ => JsonValue::String(text)
+            }
+        },
+        // dates and times are given as strings, without the quotes of their SQL literal
+        other => JsonValue::String(other.into_str().trim_matches('\'').to_string())
+    }
+}
+
+// Takes the id at the start of a row
+fn take_id(values: &mut Vec<mysql::Value>) -> Result<i32, GraphqlError> {
+    if values.is_empty() {
+        return Err(GraphqlError::Sql("Missing id column in result".to_string()));
+    }
+    match values.remove(0) {
+        mysql::Value::Int(id) => Ok(id as i32),
+        mysql::Value::UInt(id) => Ok(id as i32),
+        mysql::Value::Bytes(bytes) => String::from_utf8_lossy(&bytes).parse::<i32>().map_err(|_| GraphqlError::Sql("Invalid id in result".to_string())),
+        _ => Err(GraphqlError::Sql("Invalid id in result".to_string()))
+    }
+}
+
+fn get_selection(query: &def::QueryObject) -> Result<&Vec<def::QueryObject>, GraphqlError> {
+    query.attrs.as_ref().ok_or(GraphqlError::Validation("Field \"".to_string() + &query.name + "\" must have a selection of subfields"))
+}
+
+// The type a value of a raw SQL query is read as, by the type of its column in the result
+fn get_result_column_type(column_type: mysql::consts::ColumnType) -> &'static str {
+    use mysql::consts::ColumnType::*;
+    match column_type {
+        MYSQL_TYPE_TINY | MYSQL_TYPE_SHORT | MYSQL_TYPE_LONG | MYSQL_TYPE_INT24 | MYSQL_TYPE_LONGLONG | MYSQL_TYPE_YEAR => "INT",
+        MYSQL_TYPE_DECIMAL | MYSQL_TYPE_NEWDECIMAL | MYSQL_TYPE_FLOAT | MYSQL_TYPE_DOUBLE => "DECIMAL",
+        _ => ""
+    }
+}
 
 pub struct Deserializer{
-    tabs: i8
+    tables: Vec<def::DbTable>
 }
 
 impl Deserializer {
     pub fn new() -> Deserializer{
         Deserializer{
-            tabs: 0
+            tables: Vec::new()
         }
     }
 
-    fn get_tabulation(&self) -> String{
-        let mut tabbings = "".to_string();
-        let mut n = 0;
-        while n < self.tabs {
-            tabbings = tabbings + "\t";
-            n = n+1;
-        }
-        tabbings
-    }
-
-    fn endline(&self) -> String {
-        "\n".to_string()
+    pub fn store_tables(&mut self, tables: &mut Vec<def::DbTable>){
+        self.tables.append(tables);
     }
 
-    fn add_tabbing(&mut self){
-        self.tabs = self.tabs+1;
+    fn get_column_type(&self, type_name: &str, column_name: &str) -> String{
+        if column_name == "id" {
+            return "INT".to_string();
+        }
+        for table in &self.tables{
+            if table.name == type_name{
+                for column in &table.columns{
+                    if column.name == column_name{
+                        return column.db_type.clone();
+                    }
+                }
+            }
+        }
+        // leads_count counts the objects of the leads relation
+        if let Some(relation) = aggregate::get_count_relation(column_name) {
+            if self.get_column_type(type_name, relation).starts_with("[") {
+                return "INT".to_string();
+            }
+        }
+        "".to_string()
     }
 
-    fn remove_tabbing(&mut self){
-        if self.tabs > 0 {self.tabs = self.tabs-1};
+    // The type of an aggregate field, counts and sums are numbers, averages are decimals and minimums and maximums
+    // have the type of their column, like the grouped columns
+    fn get_aggregate_type(&self, type_name: &str, field_name: &str) -> String{
+        match aggregate::split_aggregate_field(field_name) {
+            Some(("count", _)) | Some(("sum", _)) => "INT".to_string(),
+            Some(("avg", _)) => "DECIMAL".to_string(),
+            Some((_, Some(column))) => self.get_column_type(type_name, column),
+            _ => self.get_column_type(type_name, field_name)
+        }
     }
 
-    pub fn perform_get_ids(&mut self, pool: &mysql::Pool, query: String) -> Vec<i32> {
+    pub fn perform_get_ids(&mut self, conn: &mut mysql::PooledConn, query: def::SqlStatement) -> Result<Vec<i32>, GraphqlError> {
         let mut ids : Vec<i32> = Vec::new();
 
-        let mut query_result = pool.prep_exec(query, ()).unwrap();
+        let mut query_result = try!(conn.prep_exec(query.query, query.params));
 
         for result in query_result.by_ref() {
-            let mut row = result.unwrap();
-            ids.push(row.take("id").unwrap());
+            let mut row = try!(result);
+            let id: mysql::Value = try!(row.take("id").ok_or(GraphqlError::Sql("Missing id column in result".to_string())));
+            ids.push(try!(take_id(&mut vec![id])));
         };
-        ids
+        Ok(ids)
     }
 
-    fn perform_get_relations(&mut self, pool: &mysql::Pool, query_relations: String, select_structure : &def::QueryObject ) -> String{
-        let mut json = "".to_string();
-
-        let mut query_result = pool.prep_exec(query_relations, ()).unwrap();
-
-        for col in select_structure.attrs.as_ref().unwrap(){
-            if col.attrs.as_ref() != None {
-
-                json = json + &(self.get_tabulation()) + "\"" + col.name.as_str() + "\": [  " + &(self.endline());
-                self.add_tabbing();
-
-                for result in query_result.by_ref() {
-                    json = json + &(self.get_tabulation()) + "{" + &(self.endline());
-                    self.add_tabbing();
-
-                    let mut related_col = 0;
-                    let mut row: Vec<mysql::Value> = result.unwrap().unwrap();
-                    for value in row{
-                        json = json + &(self.get_tabulation()) + "\"" + col.attrs.as_ref().unwrap()[related_col].name.as_str() + "\": " + &value.into_str() + &(self.endline());
-                        related_col = related_col + 1;
-                    }
+    // Loads the objects related to all the given objects through a relation field with one query,
+    // then the relations selected on them the same way
+    fn load_relation(&mut self, conn: &mut mysql::PooledConn, serializer: &Serializer, db_name: &str, owner: &str, relation : &def::QueryObject, origin_ids: &Vec<i32>) -> Result<LoadedRelation, GraphqlError>{
+        let node;
+        let relation = if relay::is_connection(relation) {
+            node = try!(relay::node_query(relation));
+            &node
+        }
+        else {
+            relation
+        };
+        let target = try!(serializer.get_target_by_relation(owner.to_string(), relation.name.clone()));
+        let mut loaded = LoadedRelation{target: target, objects: HashMap::new(), relations: Vec::new()};
+        let mut target_ids: Vec<i32> = Vec::new();
 
-                    self.remove_tabbing();
-                    json = json + &(self.get_tabulation()) + "}," + &(self.endline());
+        if !origin_ids.is_empty() {
+            let query_relation = try!(serializer.perform_get_rel(db_name.to_string(), owner, relation, origin_ids));
+            let query_result = try!(conn.prep_exec(query_relation.query, query_relation.params));
+            for result in query_result {
+                let mut values: Vec<mysql::Value> = try!(result).unwrap();
+                let origin_id = try!(take_id(&mut values));
+                let target_id = try!(take_id(&mut values));
+                if !target_ids.contains(&target_id) {
+                    target_ids.push(target_id);
                 }
-                json.pop();
-                json.pop();
-                json = json + &(self.endline());
-
-                self.remove_tabbing();
-                json = json + &(self.get_tabulation()) + "]" + &(self.endline());
+                loaded.objects.entry(origin_id).or_insert(Vec::new()).push((target_id, values));
             }
         }
 
-        json
+        for col in try!(get_selection(relation)) {
+            if col.attrs.as_ref() != None {
+                let nested = try!(self.load_relation(conn, serializer, db_name, &loaded.target, col, &target_ids));
+                loaded.relations.push(nested);
+            }
+        }
+        Ok(loaded)
     }
 
-    pub fn perform_get(&mut self, pool: &mysql::Pool, query_objects: String, query_relations: String, select_structure : &def::QueryObject ) -> String {
-        let mut json = "".to_string();
+    // Builds an object from the values of its selected columns, in the order of the selection,
+    // relations holds the loaded relation fields of the selection in the same order
+    fn perform_get_fields(&self, type_name: &str, id: i32, selection: &Vec<def::QueryObject>, values: Vec<mysql::Value>, relations: &Vec<LoadedRelation>) -> Result<JsonValue, GraphqlError>{
+        let mut fields: Vec<(String, JsonValue)> = Vec::new();
+        let mut values = values.into_iter();
+        let mut relations = relations.iter();
 
-        json = json + &(self.get_tabulation()) + "{" + &(self.endline());
-        self.add_tabbing();
+        for col in selection {
+            if col.name == def::TYPENAME {
+                fields.push((col.key().to_string(), JsonValue::String(type_name.to_string())));
+            }
+            else if col.is_id() {
+                fields.push((col.key().to_string(), JsonValue::Int(id as i64)));
+            }
+            else if col.attrs.as_ref() == None {
+                let data : mysql::Value = try!(values.next().ok_or(GraphqlError::Sql("Missing column ".to_string() + col.key() + " in result")));
+                fields.push((col.key().to_string(), value_to_json(&self.get_column_type(type_name, &col.name), data)));
+            }
+            else {
+                let relation = try!(relations.next().ok_or(GraphqlError::Sql("Relation ".to_string() + &col.name + " was not loaded")));
+                fields.push((col.key().to_string(), try!(self.perform_get_relation(col, id, relation))));
+            }
+        }
 
-        json = json + &(self.get_tabulation()) + "\"data\": {" + &(self.endline());
-        self.add_tabbing();
+        Ok(JsonValue::Object(fields))
+    }
 
-        let mut query_result = pool.prep_exec(query_objects, ()).unwrap();
+    // The related objects of a connection field are the nodes of its edges
+    fn perform_get_relation(&self, relation : &def::QueryObject, origin_id: i32, loaded: &LoadedRelation) -> Result<JsonValue, GraphqlError>{
+        let node;
+        let selection = if relay::is_connection(relation) {
+            node = try!(relay::node_query(relation));
+            try!(get_selection(&node))
+        }
+        else {
+            try!(get_selection(relation))
+        };
+        let mut related_objects: Vec<(i32, JsonValue)> = Vec::new();
+        for &(related_id, ref values) in loaded.objects.get(&origin_id).unwrap_or(&Vec::new()) {
+            related_objects.push((related_id, try!(self.perform_get_fields(&loaded.target, related_id, selection, values.clone(), &loaded.relations))));
+        }
+        if relay::is_connection(relation) {
+            relay::connection_json(relation, &loaded.target, related_objects)
+        }
+        else {
+            Ok(JsonValue::Array(related_objects.into_iter().map(|(_, object)| object).collect()))
+        }
+    }
 
+    // Executes raw SQL, the values of the rows are typed by the types of their columns
+    pub fn perform_mysql_query(&self, conn: &mut mysql::PooledConn, query: &str) -> Result<def::ResultSet, GraphqlError> {
+        let mut query_result = try!(conn.query(query));
+        let columns: Vec<String> = query_result.columns_ref().iter().map(|column| column.name_str().into_owned()).collect();
+        let column_types: Vec<&str> = query_result.columns_ref().iter().map(|column| get_result_column_type(column.column_type())).collect();
+        let mut rows: Vec<Vec<JsonValue>> = Vec::new();
         for result in query_result.by_ref() {
-            let mut row = result.unwrap();
-            let mut resulting_object : String = "".to_string();
-
-            resulting_object = resulting_object + &(self.get_tabulation()) + "\"" + select_structure.name.as_str() + "\": {" + &(self.endline());
-            self.add_tabbing();
+            let values: Vec<mysql::Value> = try!(result).unwrap();
+            rows.push(values.into_iter().zip(column_types.iter()).map(|(value, column_type)| value_to_json(column_type, value)).collect());
+        }
+        Ok(def::ResultSet{
+            columns: columns,
+            rows: rows,
+            affected_rows: query_result.affected_rows(),
+            last_insert_id: query_result.last_insert_id()
+        })
+    }
 
-            for col in select_structure.attrs.as_ref().unwrap(){
-                if col.attrs.as_ref() == None {
-                    let data : mysql::Value = row.take(col.name.as_str()).unwrap();
-                    resulting_object = resulting_object + &(self.get_tabulation()) + "\"" + &col.name + "\": " + &(data.into_str()) + if col != select_structure.attrs.as_ref().unwrap().last().unwrap() {","} else {""} + &(self.endline())
+    // A list of rows of aggregates, one for each group when they are grouped and the one of all the objects otherwise
+    pub fn perform_get_aggregate(&self, conn: &mut mysql::PooledConn, query_aggregates: def::SqlStatement, aggregate_structure : &def::QueryObject) -> Result<String, GraphqlError> {
+        let type_name = aggregate::get_aggregate_target(&aggregate_structure.name);
+        let selection = aggregate_structure.attrs.clone().unwrap_or(Vec::new());
+        let mut rows: Vec<JsonValue> = Vec::new();
+        for result in try!(conn.prep_exec(query_aggregates.query, query_aggregates.params)) {
+            let mut values = try!(result).unwrap().into_iter();
+            let mut fields: Vec<(String, JsonValue)> = Vec::new();
+            for field in &selection {
+                let value = if field.name == def::TYPENAME {
+                    JsonValue::String(aggregate_structure.name.clone())
                 }
                 else {
-                    resulting_object = resulting_object + self.perform_get_relations(pool, query_relations.clone(), select_structure).as_str();
-                }
+                    let data: mysql::Value = try!(values.next().ok_or(GraphqlError::Sql("Missing column ".to_string() + field.key() + " in result")));
+                    value_to_json(&self.get_aggregate_type(type_name, &field.name), data)
+                };
+                fields.push((field.key().to_string(), value));
             }
+            rows.push(JsonValue::Object(fields));
+        }
+        let data = JsonValue::Object(vec![(aggregate_structure.key().to_string(), JsonValue::Array(rows))]);
+        Ok(json::response_body(Some(data), Vec::new()).to_string())
+    }
 
-            self.remove_tabbing();
-            resulting_object = resulting_object + &(self.get_tabulation()) + "}," + &(self.endline());
-            json = json + resulting_object.as_str();
-
+    // Each selected row starts with its id, the related objects are attached to the row with their origin id.
+    // A connection is returned for a connection query and a list otherwise, also when the objects are asked for by id,
+    // so every root field has the list type introspection gives it.
+    pub fn perform_get(&mut self, conn: &mut mysql::PooledConn, serializer: &Serializer, db_name: &str, query_objects: def::SqlStatement, query : &def::QueryObject) -> Result<String, GraphqlError> {
+        let node;
+        let select_structure = if relay::is_connection(query) {
+            node = try!(relay::node_query(query));
+            &node
+        }
+        else {
+            query
         };
-        json.pop();
-        json.pop();
-        json = json + &(self.endline());
 
-        self.remove_tabbing();
-        json = json + &(self.get_tabulation()) + "}" + &(self.endline());
+        // the rows are read before the relations are queried on the same connection
+        let mut objects: Vec<(i32, Vec<mysql::Value>)> = Vec::new();
+        for result in try!(conn.prep_exec(query_objects.query, query_objects.params)) {
+            let mut values: Vec<mysql::Value> = try!(result).unwrap();
+            let id = try!(take_id(&mut values));
+            objects.push((id, values));
+        }
+        let origin_ids: Vec<i32> = objects.iter().map(|&(id, _)| id).collect();
 
-        self.remove_tabbing();
-        json = json + &(self.get_tabulation()) + "}" + &(self.endline());
+        // every relation is loaded for all the rows at once, before any of them is written
+        let mut relations: Vec<LoadedRelation> = Vec::new();
+        let selection = try!(get_selection(select_structure));
+        for col in selection {
+            if col.attrs.as_ref() != None {
+                let loaded = try!(self.load_relation(conn, serializer, db_name, &select_structure.name, col, &origin_ids));
+                relations.push(loaded);
+            }
+        }
+
+        let mut results: Vec<(i32, JsonValue)> = Vec::new();
+        for (id, values) in objects {
+            results.push((id, try!(self.perform_get_fields(&select_structure.name, id, selection, values, &relations))));
+        }
+        let result = if relay::is_connection(query) {
+            try!(relay::connection_json(query, &select_structure.name, results))
+        }
+        else {
+            JsonValue::Array(results.into_iter().map(|(_, object)| object).collect())
+        };
 
-        json
+        let data = JsonValue::Object(vec![(query.key().to_string(), result)]);
+        Ok(json::response_body(Some(data), Vec::new()).to_string())
     }
 }
+
+#[test]
+fn test_value_to_json(){
+    assert_eq!(value_to_json("INT", mysql::Value::Int(50)), JsonValue::Int(50));
+    assert_eq!(value_to_json("INT", mysql::Value::Bytes(b"50".to_vec())), JsonValue::Int(50));
+    assert_eq!(value_to_json("BOOLEAN", mysql::Value::Int(1)), JsonValue::Boolean(true));
+    assert_eq!(value_to_json("BOOLEAN", mysql::Value::Bytes(b"0".to_vec())), JsonValue::Boolean(false));
+    assert_eq!(value_to_json("TEXT(2048)", mysql::Value::Bytes(b"Bow of \"Lorien\"".to_vec())), JsonValue::String("Bow of \"Lorien\"".to_string()));
+    assert_eq!(value_to_json("TEXT(2048)", mysql::Value::NULL), JsonValue::Null);
+    assert_eq!(
+        json::response_body(Some(JsonValue::Object(vec![("Weapon".to_string(), JsonValue::Array(vec![
+            JsonValue::Object(vec![("name".to_string(), value_to_json("TEXT(2048)", mysql::Value::Bytes(b"Bow of \"Lorien\"\n".to_vec())))])
+        ]))])), Vec::new()).to_string(),
+        "{\"data\":{\"Weapon\":[{\"name\":\"Bow of \\\"Lorien\\\"\\n\"}]}}"
+    );
+
+    assert_eq!(value_to_json(get_result_column_type(mysql::consts::ColumnType::MYSQL_TYPE_LONGLONG), mysql::Value::Bytes(b"10".to_vec())), JsonValue::Int(10));
+    assert_eq!(value_to_json(get_result_column_type(mysql::consts::ColumnType::MYSQL_TYPE_NEWDECIMAL), mysql::Value::Bytes(b"50.5000".to_vec())), JsonValue::Float(50.5));
+    let result_set = def::ResultSet{
+        columns: vec!["name".to_string(), "warriors".to_string()],
+        rows: vec![vec![JsonValue::String("Galadriel".to_string()), JsonValue::Int(10)]],
+        affected_rows: 0,
+        last_insert_id: 0
+    };
+    assert_eq!(
+        result_set.to_json().to_string(),
+        "{\"columns\":[\"name\",\"warriors\"],\"rows\":[[\"Galadriel\",10]],\"affectedRows\":0,\"lastInsertId\":0}"
+    );
+}
+
+#[test]
+fn test_typename(){
+    let mut deserializer = Deserializer::new();
+    deserializer.store_tables(&mut vec![def::DbTable{name: "Warrior".to_string(), columns: vec![
+        column("name", "TEXT(2048)", true)
+    ]}]);
+    assert_eq!(
+        deserializer.perform_get_fields("Warrior", 1, &vec![field("__typename"), field("name")], vec![mysql::Value::from("legolas")], &vec![]).unwrap().to_string(),
+        "{\"__typename\":\"Warrior\",\"name\":\"legolas\"}"
+    );
+}
+
+#[test]
+fn test_id_field(){
+    let mut deserializer = Deserializer::new();
+    deserializer.store_tables(&mut vec![def::DbTable{name: "Warrior".to_string(), columns: vec![
+        column("name", "TEXT(2048)", true)
+    ]}]);
+    // the id is not selected twice, the one the row starts with is used
+    assert_eq!(
+        deserializer.perform_get_fields("Warrior", 7, &vec![field("name"), field("id")], vec![mysql::Value::from("legolas")], &vec![]).unwrap().to_string(),
+        "{\"name\":\"legolas\",\"id\":7}"
+    );
+}
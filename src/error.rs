@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+
+use mysql;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ValidationError {
+    // byte offset in the query
+    pub position: usize,
+    pub message: String
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GraphqlError {
+    // the query or schema could not be parsed, position is the byte offset in the source
+    Parse{
+        position: usize,
+        message: String
+    },
+    // the request does not match the loaded schema
+    Validation(String),
+    // the query does not follow the schema, with the position of every error in the query
+    Invalid(Vec<ValidationError>),
+    // MySQL rejected the generated statement
+    Sql(String),
+    // the database could not be reached or the pool is gone
+    Connection(String)
+}
+
+impl fmt::Display for GraphqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GraphqlError::Parse{position, ref message} => write!(f, "Parse error at {}: {}", position, message),
+            GraphqlError::Validation(ref message) => write!(f, "Validation error: {}", message),
+            GraphqlError::Invalid(ref errors) => {
+                let messages: Vec<String> = errors.iter().map(|error| format!("at {}: {}", error.position, error.message)).collect();
+                write!(f, "Validation errors: {}", messages.join("; "))
+            },
+            GraphqlError::Sql(ref message) => write!(f, "SQL error: {}", message),
+            GraphqlError::Connection(ref message) => write!(f, "Connection error: {}", message)
+        }
+    }
+}
+
+impl Error for GraphqlError {
+    fn description(&self) -> &str {
+        match *self {
+            GraphqlError::Parse{..} => "GraphQL parse error",
+            GraphqlError::Validation(_) | GraphqlError::Invalid(_) => "GraphQL validation error",
+            GraphqlError::Sql(_) => "MySQL error",
+            GraphqlError::Connection(_) => "connection error"
+        }
+    }
+}
+
+impl From<mysql::Error> for GraphqlError {
+    fn from(err: mysql::Error) -> GraphqlError {
+        match err {
+            mysql::Error::MySqlError(_) => GraphqlError::Sql(err.to_string()),
+            _ => GraphqlError::Connection(err.to_string())
+        }
+    }
+}
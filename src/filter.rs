@@ -0,0 +1,179 @@
+use nom::IResult;
+
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use parser;
+#[cfg(test)]
+use test_support::column;
+
+pub const FILTER_OPERATORS: [&'static str; 9] = ["gt", "gte", "lt", "lte", "ne", "in", "contains", "startsWith", "isNull"];
+pub const PAGINATION_ARGUMENTS: [&'static str; 3] = ["orderBy", "first", "offset"];
+
+// Splits an argument name into the column it filters and its operator, strength_gt gives strength and gt.
+// A name that is a column of the filtered type is that column, max_gt is not split when there is a max_gt column.
+pub fn split_argument<'a>(name: &'a str, columns: &[DbColumn]) -> (&'a str, &'a str) {
+    if columns.iter().any(|column| column.name == name) {
+        return (name, "eq");
+    }
+    if let Some(index) = name.rfind('_') {
+        let operator = &name[index + 1..];
+        if FILTER_OPERATORS.contains(&operator) {
+            return (&name[..index], operator);
+        }
+    }
+    (name, "eq")
+}
+
+fn invalid_argument(name: &str, value: &str, expected: &str) -> GraphqlError {
+    GraphqlError::Validation("Argument \"".to_string() + name + "\" has invalid value " + value + ", expected " + expected)
+}
+
+fn get_list_items(name: &str, value: &str) -> Result<Vec<String>, GraphqlError> {
+    match parser::parse_list_items(value.as_bytes()) {
+        IResult::Done(remaining, items) if remaining.is_empty() => Ok(items.iter().map(|item| item.to_string()).collect()),
+        _ => Err(invalid_argument(name, value, "a list"))
+    }
+}
+
+fn get_object_filter(name: &str, value: &str, columns: &[DbColumn]) -> Result<Filter, GraphqlError> {
+    match parser::parse_object_fields(value.as_bytes()) {
+        IResult::Done(remaining, fields) if remaining.is_empty() => get_filter(&fields, columns),
+        _ => Err(invalid_argument(name, value, "an object of filters"))
+    }
+}
+
+// Turns the arguments of a query or a mutation into the filter they put on the objects, all of them have to hold.
+// columns are the ones of the filtered type.
+pub fn get_filter(params: &Vec<(String, String)>, columns: &[DbColumn]) -> Result<Filter, GraphqlError> {
+    let mut filters: Vec<Filter> = Vec::new();
+    for &(ref name, ref value) in params {
+        if PAGINATION_ARGUMENTS.contains(&name.as_str()) {
+            continue;
+        }
+        let filter = match name.as_str() {
+            "AND" | "OR" => {
+                let mut combined: Vec<Filter> = Vec::new();
+                for item in try!(get_list_items(name, value)) {
+                    combined.push(try!(get_object_filter(name, &item, columns)));
+                }
+                if name == "AND" {Filter::And(combined)} else {Filter::Or(combined)}
+            },
+            "NOT" => Filter::Not(Box::new(try!(get_object_filter(name, value, columns)))),
+            _ => {
+                let (column, operator) = split_argument(name, columns);
+                let values = match operator {
+                    "in" => try!(get_list_items(name, value)),
+                    "isNull" if value != "true" && value != "false" => return Err(invalid_argument(name, value, "Boolean")),
+                    _ => vec![value.clone()]
+                };
+                Filter::Condition{column: column.to_string(), operator: operator.to_string(), values: values}
+            }
+        };
+        filters.push(filter);
+    }
+    Ok(Filter::And(filters))
+}
+
+fn get_count(name: &str, value: &str) -> Result<u64, GraphqlError> {
+    value.parse::<u64>().map_err(|_| invalid_argument(name, value, "a non-negative Int"))
+}
+
+// Reads the orderBy, first and offset arguments, orderBy: {strength: DESC name: ASC} orders by strength first
+pub fn get_pagination(params: &Vec<(String, String)>) -> Result<Pagination, GraphqlError> {
+    let mut pagination = Pagination{order_by: Vec::new(), first: None, offset: None};
+    for &(ref name, ref value) in params {
+        match name.as_str() {
+            "orderBy" => {
+                let fields = match parser::parse_object_fields(value.as_bytes()) {
+                    IResult::Done(remaining, fields) if remaining.is_empty() => fields,
+                    _ => return Err(invalid_argument(name, value, "an object of fields"))
+                };
+                for (field, direction) in fields {
+                    if direction != "ASC" && direction != "DESC" {
+                        return Err(invalid_argument(name, value, "ASC or DESC"));
+                    }
+                    pagination.order_by.push((field, direction));
+                }
+            },
+            "first" => pagination.first = Some(try!(get_count(name, value))),
+            "offset" => pagination.offset = Some(try!(get_count(name, value))),
+            _ => {}
+        }
+    }
+    Ok(pagination)
+}
+
+#[test]
+fn test_get_filter(){
+    let condition = |column: &str, operator: &str, values: Vec<&str>| Filter::Condition{
+        column: column.to_string(),
+        operator: operator.to_string(),
+        values: values.iter().map(|value| value.to_string()).collect()
+    };
+    let params = vec![
+        ("race".to_string(), "Elf".to_string()),
+        ("strength_gte".to_string(), "40".to_string()),
+        ("name_in".to_string(), "[elf1, elf2]".to_string()),
+        ("OR".to_string(), "[{wisdom_isNull: true} {NOT: {name_startsWith: orc}}]".to_string())
+    ];
+    assert_eq!(
+        get_filter(&params, &[]),
+        Ok(Filter::And(vec![
+            condition("race", "eq", vec!["Elf"]),
+            condition("strength", "gte", vec!["40"]),
+            condition("name", "in", vec!["elf1", "elf2"]),
+            Filter::Or(vec![
+                Filter::And(vec![condition("wisdom", "isNull", vec!["true"])]),
+                Filter::And(vec![Filter::Not(Box::new(Filter::And(vec![condition("name", "startsWith", vec!["orc"])])))])
+            ])
+        ]))
+    );
+    assert_eq!(split_argument("first_name", &[]), ("first_name", "eq"));
+    let columns = vec![column("max_gt", "INT", false), column("is_in", "Boolean", false)];
+    assert_eq!(split_argument("max_gt", &columns), ("max_gt", "eq"));
+    assert_eq!(split_argument("max_gt_gt", &columns), ("max_gt", "gt"));
+    assert_eq!(
+        get_filter(&vec![("OR".to_string(), "[{is_in: true} {is_in_ne: true}]".to_string())], &columns),
+        Ok(Filter::And(vec![Filter::Or(vec![
+            Filter::And(vec![condition("is_in", "eq", vec!["true"])]),
+            Filter::And(vec![condition("is_in", "ne", vec!["true"])])
+        ])]))
+    );
+    assert_eq!(
+        get_filter(&vec![("wisdom_isNull".to_string(), "maybe".to_string())], &[]),
+        Err(GraphqlError::Validation("Argument \"wisdom_isNull\" has invalid value maybe, expected Boolean".to_string()))
+    );
+    assert_eq!(
+        get_filter(&vec![("OR".to_string(), "Elf".to_string())], &[]),
+        Err(GraphqlError::Validation("Argument \"OR\" has invalid value Elf, expected a list".to_string()))
+    );
+}
+
+#[test]
+fn test_get_pagination(){
+    let params = vec![
+        ("race".to_string(), "Elf".to_string()),
+        ("orderBy".to_string(), "{strength: DESC name: ASC}".to_string()),
+        ("first".to_string(), "10".to_string()),
+        ("offset".to_string(), "20".to_string())
+    ];
+    assert_eq!(
+        get_pagination(&params),
+        Ok(Pagination{
+            order_by: vec![("strength".to_string(), "DESC".to_string()), ("name".to_string(), "ASC".to_string())],
+            first: Some(10),
+            offset: Some(20)
+        })
+    );
+    assert_eq!(get_filter(&params, &[]), Ok(Filter::And(vec![Filter::Condition{column: "race".to_string(), operator: "eq".to_string(), values: vec!["Elf".to_string()]}])));
+    assert_eq!(
+        get_pagination(&vec![("first".to_string(), "-1".to_string())]),
+        Err(GraphqlError::Validation("Argument \"first\" has invalid value -1, expected a non-negative Int".to_string()))
+    );
+    assert_eq!(
+        get_pagination(&vec![("orderBy".to_string(), "{name: UP}".to_string())]),
+        Err(GraphqlError::Validation("Argument \"orderBy\" has invalid value {name: UP}, expected ASC or DESC".to_string()))
+    );
+}
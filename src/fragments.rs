@@ -0,0 +1,228 @@
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use introspection;
+use parser;
+#[cfg(test)]
+use test_support::{column, field, object};
+
+// The type of the objects a relation field points to, Leader.leads gives Warrior
+fn get_relation_target(database: &Vec<DbTable>, type_name: &str, field_name: &str) -> Option<String> {
+    for table in database {
+        if table.name == type_name {
+            for column in &table.columns {
+                if column.name == field_name && column.db_type.starts_with("[") && column.db_type.ends_with("]") {
+                    return Some(column.db_type[1..column.db_type.len() - 1].to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+// Adds a field to a selection, a field selected twice under the same key is merged into the first one
+fn merge_field(selection: &mut Vec<QueryObject>, field: QueryObject) -> Result<(), GraphqlError> {
+    let position = match selection.iter().position(|selected| selected.key() == field.key()) {
+        Some(position) => position,
+        None => {
+            selection.push(field);
+            return Ok(());
+        }
+    };
+    if selection[position].name != field.name {
+        return Err(GraphqlError::Validation(
+            "Fields \"".to_string() + field.key() + "\" conflict because " + &selection[position].name + " and " + &field.name + " are different fields"
+        ));
+    }
+    if selection[position].params != field.params {
+        return Err(GraphqlError::Validation("Fields \"".to_string() + field.key() + "\" conflict because they have differing arguments"));
+    }
+    if let Some(subfields) = field.attrs {
+        if let Some(ref mut selected_subfields) = selection[position].attrs {
+            for subfield in subfields {
+                try!(merge_field(selected_subfields, subfield));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Fragments spread several times can expand exponentially, larger selections are rejected while being expanded
+const MAX_SELECTED_FIELDS: usize = 10000;
+
+struct FragmentExpander<'a> {
+    fragments: &'a Vec<Fragment>,
+    database: &'a Vec<DbTable>,
+    used: Vec<String>,
+    // number of fields, spreads and inline fragments expanded so far
+    selected: usize
+}
+
+impl<'a> FragmentExpander<'a> {
+    fn check_type_condition(&self, type_name: Option<&str>, fragment_name: &str, on: &str) -> Result<(), GraphqlError> {
+        match type_name {
+            Some(type_name) if type_name != on => Err(GraphqlError::Validation(
+                "Fragment ".to_string() + fragment_name + "cannot be spread here as objects of type \"" + type_name + "\" can never be of type \"" + on + "\""
+            )),
+            _ => Ok(())
+        }
+    }
+
+    // type_name is the type of the selected objects, None when it is not known
+    // spreading holds the fragments being expanded, to detect the ones that spread themselves
+    // depth counts the selections and fragments being expanded, it is limited like the nesting of a parsed query
+    fn expand_selection(&mut self, type_name: Option<&str>, selection: Vec<QueryObject>, spreading: &mut Vec<String>, depth: usize) -> Result<Vec<QueryObject>, GraphqlError> {
+        if depth > parser::MAX_DEPTH {
+            return Err(GraphqlError::Validation("The selection is nested more than ".to_string() + &parser::MAX_DEPTH.to_string() + " levels deep"));
+        }
+        let mut expanded: Vec<QueryObject> = Vec::new();
+        for field in selection {
+            self.selected += 1;
+            if self.selected > MAX_SELECTED_FIELDS {
+                return Err(GraphqlError::Validation("The selection expands to more than ".to_string() + &MAX_SELECTED_FIELDS.to_string() + " fields"));
+            }
+            if let Some(fragment_name) = field.fragment_spread() {
+                if spreading.iter().any(|spread| spread == fragment_name) {
+                    return Err(GraphqlError::Validation(
+                        "Cannot spread fragment \"".to_string() + fragment_name + "\" within itself via " + &spreading.join(", ")
+                    ));
+                }
+                let fragment = try!(self.fragments.iter().find(|fragment| fragment.name == fragment_name).ok_or(
+                    GraphqlError::Validation("Unknown fragment \"".to_string() + fragment_name + "\"")
+                ));
+                try!(self.check_type_condition(type_name, &("\"".to_string() + fragment_name + "\" "), &fragment.on));
+                self.used.push(fragment_name.to_string());
+
+                spreading.push(fragment_name.to_string());
+                let fragment_fields = try!(self.expand_selection(Some(&fragment.on), fragment.attrs.clone(), spreading, depth + 1));
+                spreading.pop();
+                for fragment_field in fragment_fields {
+                    try!(merge_field(&mut expanded, fragment_field));
+                }
+                continue;
+            }
+            if let Some(on) = field.inline_fragment() {
+                try!(self.check_type_condition(type_name, "", on));
+                let fragment_fields = try!(self.expand_selection(Some(on), field.attrs.clone().unwrap_or(Vec::new()), spreading, depth + 1));
+                for fragment_field in fragment_fields {
+                    try!(merge_field(&mut expanded, fragment_field));
+                }
+                continue;
+            }
+
+            let field_type = type_name.and_then(|type_name| get_relation_target(self.database, type_name, &field.name));
+            let attrs = match field.attrs {
+                Some(subfields) => Some(try!(self.expand_selection(field_type.as_ref().map(|field_type| field_type.as_str()), subfields, spreading, depth + 1))),
+                None => None
+            };
+            try!(merge_field(&mut expanded, QueryObject{name: field.name, alias: field.alias, params: field.params, attrs: attrs, position: field.position, param_positions: field.param_positions}));
+        }
+        Ok(expanded)
+    }
+}
+
+// Replaces the fragment spreads and inline fragments of a query by their fields
+pub fn expand_fragments(query: QueryObject, fragments: &Vec<Fragment>, database: &Vec<DbTable>) -> Result<QueryObject, GraphqlError> {
+    for (i, fragment) in fragments.iter().enumerate() {
+        if fragments[..i].iter().any(|previous| previous.name == fragment.name) {
+            return Err(GraphqlError::Validation("There can be only one fragment named \"".to_string() + &fragment.name + "\""));
+        }
+    }
+
+    let mut expander = FragmentExpander{fragments: fragments, database: database, used: Vec::new(), selected: 0};
+    // the types of the introspection fields are not tables, their type conditions are not checked
+    let attrs = {
+        let type_name = if introspection::is_introspection(&query) {None} else {Some(query.name.as_str())};
+        match query.attrs {
+            Some(selection) => Some(try!(expander.expand_selection(type_name, selection, &mut Vec::new(), 1))),
+            None => None
+        }
+    };
+    for fragment in fragments {
+        if !expander.used.contains(&fragment.name) {
+            return Err(GraphqlError::Validation("Fragment \"".to_string() + &fragment.name + "\" is never used"));
+        }
+    }
+    Ok(QueryObject{name: query.name, alias: query.alias, params: query.params, attrs: attrs, position: query.position, param_positions: query.param_positions})
+}
+
+#[test]
+fn test_expand_fragments(){
+    let database = vec![
+        DbTable{name: "Leader".to_string(), columns: vec![
+            column("name", "TEXT(2048)", true),
+            column("leads", "[Warrior]", false)
+        ]},
+        DbTable{name: "Warrior".to_string(), columns: vec![
+            column("name", "TEXT(2048)", true)
+        ]}
+    ];
+    let fragments = vec![
+        Fragment{name: "LeaderFields".to_string(), on: "Leader".to_string(), attrs: vec![
+            field("name"),
+            object("leads", vec![field("...WarriorFields")])
+        ], position: 0},
+        Fragment{name: "WarriorFields".to_string(), on: "Warrior".to_string(), attrs: vec![field("name")], position: 0}
+    ];
+    let query = object("Leader", vec![
+        field("name"),
+        field("...LeaderFields"),
+        object("... on Leader", vec![field("id")])
+    ]);
+    assert_eq!(
+        expand_fragments(query, &fragments, &database),
+        Ok(object("Leader", vec![
+            field("name"),
+            object("leads", vec![field("name")]),
+            field("id")
+        ]))
+    );
+
+    let query = object("Warrior", vec![field("...LeaderFields")]);
+    assert_eq!(
+        expand_fragments(query, &fragments[..1].to_vec(), &database),
+        Err(GraphqlError::Validation("Fragment \"LeaderFields\" cannot be spread here as objects of type \"Warrior\" can never be of type \"Leader\"".to_string()))
+    );
+
+    let cyclic_fragments = vec![
+        Fragment{name: "LeaderFields".to_string(), on: "Leader".to_string(), attrs: vec![field("...MoreLeaderFields")], position: 0},
+        Fragment{name: "MoreLeaderFields".to_string(), on: "Leader".to_string(), attrs: vec![field("...LeaderFields")], position: 0}
+    ];
+    let query = object("Leader", vec![field("...LeaderFields")]);
+    assert_eq!(
+        expand_fragments(query, &cyclic_fragments, &database),
+        Err(GraphqlError::Validation("Cannot spread fragment \"LeaderFields\" within itself via LeaderFields, MoreLeaderFields".to_string()))
+    );
+
+    let query = object("Leader", vec![field("name")]);
+    assert_eq!(
+        expand_fragments(query, &fragments[1..].to_vec(), &database),
+        Err(GraphqlError::Validation("Fragment \"WarriorFields\" is never used".to_string()))
+    );
+    let chained_fragments: Vec<Fragment> = (0..100).map(|i| Fragment{
+        name: "LeaderFields".to_string() + &i.to_string(),
+        on: "Leader".to_string(),
+        attrs: vec![if i == 99 {field("name")} else {field(&("...LeaderFields".to_string() + &(i + 1).to_string()))}],
+        position: 0
+    }).collect();
+    let query = object("Leader", vec![field("...LeaderFields0")]);
+    assert_eq!(
+        expand_fragments(query, &chained_fragments, &database),
+        Err(GraphqlError::Validation("The selection is nested more than 64 levels deep".to_string()))
+    );
+    let doubling_fragments: Vec<Fragment> = (0..20).map(|i| Fragment{
+        name: "LeaderFields".to_string() + &i.to_string(),
+        on: "Leader".to_string(),
+        attrs: if i == 19 {vec![field("name")]} else {
+            let spread = "...LeaderFields".to_string() + &(i + 1).to_string();
+            vec![field(&spread), field(&spread)]
+        },
+        position: 0
+    }).collect();
+    let query = object("Leader", vec![field("...LeaderFields0")]);
+    assert_eq!(
+        expand_fragments(query, &doubling_fragments, &database),
+        Err(GraphqlError::Validation("The selection expands to more than 10000 fields".to_string()))
+    );
+}
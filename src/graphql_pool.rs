@@ -3,13 +3,16 @@ use std::str;
 
 use mysql;
 use mio::{Sender};
-use eventual::Future;
+use eventual::{Future, Async};
 
 use reader;
+use migration;
 use serialize;
 use connection_pool::*;
 use connection::*;
 use def::*;
+use error::GraphqlError;
+use json::JsonValue;
 
 #[derive(Clone)]
 pub struct GraphQLPool {
@@ -17,96 +20,140 @@ pub struct GraphQLPool {
 }
 
 impl GraphQLPool {
-    pub fn new (db_conn: &str, db_name: &str, path_name: &str) -> GraphQLPool{
-
-        let db = reader::extract_database_from_file(path_name);
+    pub fn new (db_conn: &str, db_name: &str, path_name: &str) -> Result<GraphQLPool, GraphqlError>{
+        GraphQLPool::with_connections(db_conn, db_name, path_name, NUMBER_OF_CONNECTIONS)
+    }
 
-        let pool = mysql::Pool::new(db_conn).unwrap();
-        let mut conn = pool.get_conn().unwrap();
-        let mut serializer = serialize::Serializer::new();
+    // number_of_connections is the number of worker threads executing requests concurrently.
+    // The database is migrated to the schema file first, without the destructive statements of the migration,
+    // see GraphQLPool::plan_migration and GraphQLPool::apply_migration.
+    pub fn with_connections (db_conn: &str, db_name: &str, path_name: &str, number_of_connections: usize) -> Result<GraphQLPool, GraphqlError>{
 
-        conn.query(serializer.create_database(db_name.to_string())).unwrap();
-        conn.query(serializer.use_database(db_name.to_string())).unwrap();
+        let (db, enums) = try!(reader::extract_database_from_file(path_name));
 
-        let mut relations : Vec<Relation> = Vec::new();
-        for table in & db {
-            let (query, mut rels) = serializer.create_table(db_name.to_string(), &table);
-            relations.append(&mut rels);
-            conn.query(query).unwrap();
-        }
+        let pool = try!(mysql::Pool::new(db_conn));
+        let mut conn = try!(pool.get_conn());
+        let serializer = load_serializer(db_name, &db, &enums);
 
-        serializer.store_relations(&mut relations);
-        for rel in &serializer.relations {
-            let query = serializer.create_relation_table(db_name.to_string(), &rel);
-            conn.query(query).unwrap();
-        }
+        try!(conn.query(serializer.create_database(db_name.to_string())));
+        try!(conn.query(serializer.use_database(db_name.to_string())));
+        try!(migration::migrate(&mut conn, &serializer, db_name, &db, &enums, false));
 
         let target_pool = TargetPool{
             pool: pool.clone(),
             database: db.clone(),
+            enums: enums.clone(),
             working_database_name: db_name.to_string(),
         };
 
-        GraphQLPool{
-            sender: ConnectionPool::new(target_pool.clone(), serializer),
-        }
+        Ok(GraphQLPool{
+            sender: try!(ConnectionPool::new(target_pool.clone(), serializer, number_of_connections)),
+        })
     }
 
-    pub fn get (&self, query: &str) -> Future<String, ()> {
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "get".to_string(),
-            body: query.to_string(),
-            tx: tx
-        }).unwrap();
-        future
+    // The statements that would migrate the database to the schema file (a dry run), nothing is executed.
+    // Tables and columns are created, modified or dropped as the types of the file changed.
+    pub fn plan_migration (db_conn: &str, db_name: &str, path_name: &str) -> Result<Migration, GraphqlError> {
+        let (db, enums) = try!(reader::extract_database_from_file(path_name));
+        let pool = try!(mysql::Pool::new(db_conn));
+        let mut conn = try!(pool.get_conn());
+        let serializer = load_serializer(db_name, &db, &enums);
+        migration::plan(&mut conn, &serializer, db_name, &db, &enums)
     }
 
+    // Migrates the database to the schema file including the destructive statements, which drop the columns
+    // of the fields removed from the file and can lose values of the modified columns
+    pub fn apply_migration (db_conn: &str, db_name: &str, path_name: &str) -> Result<Migration, GraphqlError> {
+        let (db, enums) = try!(reader::extract_database_from_file(path_name));
+        let pool = try!(mysql::Pool::new(db_conn));
+        let mut conn = try!(pool.get_conn());
+        let serializer = load_serializer(db_name, &db, &enums);
+        try!(conn.query(serializer.create_database(db_name.to_string())));
+        migration::migrate(&mut conn, &serializer, db_name, &db, &enums, true)
+    }
 
-    pub fn add (&mut self, query: &str) -> Future<String, ()> {
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "add".to_string(),
-            body: query.to_string(),
+    fn request (&self, operation: &str, body: &str, variables: Option<JsonValue>, operation_name: Option<&str>) -> Future<String, GraphqlError> {
+        let (tx, future) = Future::<OperationResult, GraphqlError>::pair();
+        let sent = self.sender.send(GraphqlMsg::Request{
+            operation: operation.to_string(),
+            body: body.to_string(),
+            variables: variables,
+            operation_name: operation_name.map(|name| name.to_string()),
             tx: tx
-        }).unwrap();
-        future
+        });
+        match sent {
+            Ok(_) => future.map(|result| result.into_string()),
+            Err(_) => Future::error(GraphqlError::Connection("The connection pool is not running".to_string()))
+        }
     }
 
-    pub fn update (&mut self, query: &str) -> Future<String, ()> {
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "update".to_string(),
-            body: query.to_string(),
-            tx: tx
-        }).unwrap();
-        future
+    pub fn get (&self, query: &str) -> Future<String, GraphqlError> {
+        self.request("get", query, None, None)
     }
 
-    pub fn delete (&mut self, query: &str) {
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "delete".to_string(),
-            body: query.to_string(),
-            tx: tx
-        }).unwrap();
+    // Executes the operation named operation_name of a document with several operations,
+    // query Warriors { Warrior { name } } query Weapons { Weapon { name } } with "Weapons"
+    pub fn get_operation (&self, query: &str, operation_name: &str) -> Future<String, GraphqlError> {
+        self.request("get", query, None, Some(operation_name))
+    }
+
+    // Executes a query with variables, query Warrior($id: Int!) { Warrior (id: $id) { name } } with [("id", JsonValue::Int(8))]
+    pub fn get_with_variables (&self, query: &str, variables: &[(&str, JsonValue)]) -> Future<String, GraphqlError> {
+        let variables = JsonValue::Object(variables.iter().map(|&(name, ref value)| (name.to_string(), value.clone())).collect());
+        self.request("get", query, Some(variables), None)
+    }
+
+    pub fn add (&mut self, query: &str) -> Future<String, GraphqlError> {
+        self.request("add", query, None, None)
     }
 
-    pub fn mysql_query (&mut self, query: &str) {
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "query".to_string(),
-            body: query.to_string(),
+    pub fn update (&mut self, query: &str) -> Future<String, GraphqlError> {
+        self.request("update", query, None, None)
+    }
+
+    pub fn delete (&mut self, query: &str) -> Future<String, GraphqlError> {
+        self.request("delete", query, None, None)
+    }
+
+    // Executes the (operation, query) pairs in order in a single transaction, e.g. ("add", "{ Weapon { name: Bow } }").
+    // The future gets the response of every operation, or the first error after everything is rolled back.
+    pub fn transaction (&mut self, operations: &[(&str, &str)]) -> Future<Vec<String>, GraphqlError> {
+        let (tx, future) = Future::<Vec<String>, GraphqlError>::pair();
+        let sent = self.sender.send(GraphqlMsg::Transaction{
+            operations: operations.iter().map(|&(operation, body)| (operation.to_string(), body.to_string())).collect(),
             tx: tx
-        }).unwrap();
+        });
+        match sent {
+            Ok(_) => future,
+            Err(_) => Future::error(GraphqlError::Connection("The connection pool is not running".to_string()))
+        }
+    }
+
+    // Executes raw SQL, the future gets the rows it returns, the number of affected rows and the last inserted id
+    pub fn mysql_query (&mut self, query: &str) -> Future<ResultSet, GraphqlError> {
+        let (tx, future) = Future::<ResultSet, GraphqlError>::pair();
+        match self.sender.send(GraphqlMsg::Query{body: query.to_string(), tx: tx}) {
+            Ok(_) => future,
+            Err(_) => Future::error(GraphqlError::Connection("The connection pool is not running".to_string()))
+        }
     }
 
     pub fn destroy_database (&mut self){
-        let (tx, future) = Future::<String, ()>::pair();
-        self.sender.send(GraphqlMsg::Request{
-            operation: "destroy_db".to_string(),
-            body: "".to_string(),
-            tx: tx
-        }).unwrap();
+        self.request("destroy_db", "", None, None);
     }
-}
\ No newline at end of file
+}
+
+// A serializer knowing the enums and the relations of the schema
+fn load_serializer (db_name: &str, db: &Vec<DbTable>, enums: &Vec<DbEnum>) -> serialize::Serializer {
+    let mut serializer = serialize::Serializer::new();
+    serializer.store_enums(&mut enums.clone());
+
+    let mut relations : Vec<Relation> = Vec::new();
+    for table in db {
+        let (_, mut rels) = serializer.create_table(db_name.to_string(), &table);
+        relations.append(&mut rels);
+    }
+    serializer.store_relations(&mut relations);
+    serializer.store_tables(&mut db.clone());
+    serializer
+}
@@ -0,0 +1,356 @@
+use mio::tcp::TcpStream;
+use mio::{Token, TryRead, TryWrite};
+
+use std::io;
+use std::str;
+use std::vec::Vec;
+
+use def::OperationResult;
+use error::GraphqlError;
+#[cfg(test)]
+use error::ValidationError;
+use json;
+use json::JsonValue;
+
+pub const MAX_REQUEST_SIZE: usize = 1048576;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query_string: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        for header in &self.headers {
+            if header.0.to_lowercase() == name.to_lowercase() {
+                return Some(header.1.as_str());
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GraphqlRequest {
+    // the worker operation: get for a GET request, which only executes queries,
+    // document for a POST request, where a mutation operation decides between add, update and delete
+    pub operation: String,
+    pub query: String,
+    pub variables: Option<JsonValue>,
+    pub operation_name: Option<String>
+}
+
+// A client socket accepted by the ConnectionPool, serving one request per connection
+pub struct HttpConnection {
+    pub socket: TcpStream,
+    pub token: Token,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    written: usize
+}
+
+impl HttpConnection {
+    pub fn new(socket: TcpStream, token: Token) -> HttpConnection {
+        HttpConnection {
+            socket: socket,
+            token: token,
+            request: Vec::new(),
+            response: Vec::new(),
+            written: 0
+        }
+    }
+
+    // Reads everything available on the socket, returns true when the peer has closed its side.
+    // Reading stops past MAX_REQUEST_SIZE, request() then rejects the request.
+    pub fn read(&mut self) -> io::Result<bool> {
+        let mut buf = [0u8; 4096];
+        while self.request.len() <= MAX_REQUEST_SIZE {
+            match try!(self.socket.try_read(&mut buf)) {
+                Some(0) => return Ok(true),
+                Some(n) => self.request.extend_from_slice(&buf[..n]),
+                None => return Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn request(&self) -> Result<Option<HttpRequest>, GraphqlError> {
+        if self.request.len() > MAX_REQUEST_SIZE {
+            return Err(GraphqlError::Validation("Request is too large".to_string()));
+        }
+        parse_request(&self.request)
+    }
+
+    pub fn respond(&mut self, response: Vec<u8>) {
+        self.response = response;
+        self.written = 0;
+    }
+
+    // Writes as much of the response as the socket accepts, returns true once all of it is sent
+    pub fn write(&mut self) -> io::Result<bool> {
+        while self.written < self.response.len() {
+            match try!(self.socket.try_write(&self.response[self.written..])) {
+                Some(n) => self.written += n,
+                None => return Ok(false)
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// Returns None while the request is not fully received yet
+pub fn parse_request(buf: &[u8]) -> Result<Option<HttpRequest>, GraphqlError> {
+    let head_end = match find_subsequence(buf, b"\r\n\r\n") {
+        Some(position) => position,
+        None => return Ok(None)
+    };
+    let head = try!(str::from_utf8(&buf[..head_end]).map_err(|_| GraphqlError::Parse{position: 0, message: "Request head is not valid utf-8".to_string()}));
+    let mut lines = head.split("\r\n");
+
+    let request_line: Vec<&str> = lines.next().unwrap_or("").split(' ').collect();
+    if request_line.len() != 3 || !request_line[2].starts_with("HTTP/") {
+        return Err(GraphqlError::Parse{position: 0, message: "Malformed request line".to_string()});
+    }
+    let (path, query_string) = match request_line[1].find('?') {
+        Some(i) => (request_line[1][..i].to_string(), Some(request_line[1][i + 1..].to_string())),
+        None => (request_line[1].to_string(), None)
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        match line.find(':') {
+            Some(i) => headers.push((line[..i].trim().to_string(), line[i + 1..].trim().to_string())),
+            None => return Err(GraphqlError::Parse{position: 0, message: "Malformed header: ".to_string() + line})
+        }
+    }
+
+    let mut request = HttpRequest {
+        method: request_line[0].to_string(),
+        path: path,
+        query_string: query_string,
+        headers: headers,
+        body: Vec::new()
+    };
+
+    let content_length = match request.header("Content-Length") {
+        Some(length) => try!(length.parse::<usize>().map_err(|_| GraphqlError::Parse{position: 0, message: "Invalid Content-Length".to_string()})),
+        None => 0
+    };
+    let body_start = head_end + 4;
+    if buf.len() < body_start + content_length {
+        return Ok(None);
+    }
+    request.body = buf[body_start..body_start + content_length].to_vec();
+    Ok(Some(request))
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'...b'9' => Some(c - b'0'),
+        b'a'...b'f' => Some(c - b'a' + 10),
+        b'A'...b'F' => Some(c - b'A' + 10),
+        _ => None
+    }
+}
+
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                decoded.push(high * 16 + low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(if bytes[i] == b'+' {b' '} else {bytes[i]});
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn graphql_request(request: &HttpRequest) -> Result<GraphqlRequest, GraphqlError> {
+    match request.path.trim_right_matches('/') {
+        "" | "/graphql" => {},
+        _ => return Err(GraphqlError::Validation("Unknown path: ".to_string() + &request.path))
+    }
+
+    let (operation, query, variables, operation_name) = match request.method.as_str() {
+        "GET" => {
+            let mut query: Option<String> = None;
+            let mut variables: Option<JsonValue> = None;
+            let mut operation_name: Option<String> = None;
+            for pair in request.query_string.as_ref().map(|q| q.as_str()).unwrap_or("").split('&') {
+                let (key, value) = match pair.find('=') {
+                    Some(i) => (percent_decode(&pair[..i]), percent_decode(&pair[i + 1..])),
+                    None => (percent_decode(pair), "".to_string())
+                };
+                match key.as_str() {
+                    "query" => query = Some(value),
+                    "variables" => if !value.is_empty() {variables = Some(try!(json::parse(value.as_bytes())))},
+                    "operationName" => if !value.is_empty() {operation_name = Some(value)},
+                    _ => {}
+                }
+            }
+            ("get", query, variables, operation_name)
+        },
+        "POST" => {
+            let body = try!(json::parse(&request.body));
+            let query = body.get("query").and_then(|q| q.as_str()).map(|q| q.to_string());
+            let variables = match body.get("variables") {
+                Some(&JsonValue::Null) | None => None,
+                Some(variables) => Some(variables.clone())
+            };
+            let operation_name = body.get("operationName").and_then(|name| name.as_str()).map(|name| name.to_string());
+            ("document", query, variables, operation_name)
+        },
+        _ => return Err(GraphqlError::Validation("Unsupported method: ".to_string() + &request.method))
+    };
+
+    Ok(GraphqlRequest {
+        operation: operation.to_string(),
+        query: try!(query.ok_or(GraphqlError::Validation("Missing query".to_string()))),
+        variables: variables,
+        operation_name: operation_name
+    })
+}
+
+// Converts a byte offset into the 1-based line and column GraphQL uses for error locations,
+// the column counts the characters before the offset on its line
+fn location(query: &str, position: usize) -> (usize, usize) {
+    let mut offset = if position < query.len() {position} else {query.len()};
+    while !query.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &query[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+fn located_error(message: String, position: Option<usize>, query: Option<&str>) -> JsonValue {
+    let mut error_fields = vec![("message".to_string(), JsonValue::String(message))];
+    if let (Some(position), Some(query)) = (position, query) {
+        let (line, column) = location(query, position);
+        error_fields.push(("locations".to_string(), JsonValue::Array(vec![JsonValue::Object(vec![
+            ("line".to_string(), JsonValue::Int(line as i64)),
+            ("column".to_string(), JsonValue::Int(column as i64))
+        ])])));
+    }
+    JsonValue::Object(error_fields)
+}
+
+pub fn error_body(err: &GraphqlError, query: Option<&str>) -> String {
+    let errors = match *err {
+        GraphqlError::Parse{position, ..} => vec![located_error(err.to_string(), Some(position), query)],
+        // one entry per validation error, as GraphQL servers report them
+        GraphqlError::Invalid(ref errors) => errors.iter().map(|error| located_error(error.message.clone(), Some(error.position), query)).collect(),
+        _ => vec![located_error(err.to_string(), None, query)]
+    };
+    json::response_body(None, errors).to_string()
+}
+
+pub fn error_status(err: &GraphqlError) -> u16 {
+    match *err {
+        GraphqlError::Parse{..} | GraphqlError::Validation(_) | GraphqlError::Invalid(_) => 400,
+        GraphqlError::Sql(_) | GraphqlError::Connection(_) => 500
+    }
+}
+
+// Builds the HTTP response for the result of a GraphQL request
+pub fn graphql_response(request: &GraphqlRequest, result: Result<OperationResult, GraphqlError>) -> Vec<u8> {
+    match result {
+        Ok(OperationResult::Json(body)) => response(200, &body),
+        // mutations without a selection set only answer with a status message
+        Ok(OperationResult::Status(message)) => {
+            let data = JsonValue::Object(vec![("status".to_string(), JsonValue::String(message))]);
+            response(200, &json::response_body(Some(data), Vec::new()).to_string())
+        },
+        Err(err) => response(error_status(&err), &error_body(&err, Some(&request.query)))
+    }
+}
+
+pub fn response(status: u16, body: &str) -> Vec<u8> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        500 => "Internal Server Error",
+        _ => ""
+    };
+    let mut response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, reason, body.len()).into_bytes();
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+#[test]
+fn test_http_request_parsing(){
+    let post = b"POST /graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 46\r\n\r\n{\"query\": \"{ Weapon { name } }\", \"variables\"";
+    assert_eq!(parse_request(&post[..]), Ok(None));
+
+    let post = b"POST /graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 32\r\n\r\n{\"query\": \"{ Weapon { name } }\"}";
+    let request = parse_request(&post[..]).unwrap().unwrap();
+    assert_eq!(request.header("content-length"), Some("32"));
+    assert_eq!(
+        graphql_request(&request),
+        Ok(GraphqlRequest {
+            operation: "document".to_string(),
+            query: "{ Weapon { name } }".to_string(),
+            variables: None,
+            operation_name: None
+        })
+    );
+
+    let get = b"GET /graphql?query=%7B+Weapon+%7B+name+%7D+%7D&operationName=Weapons HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let request = parse_request(&get[..]).unwrap().unwrap();
+    assert_eq!(
+        graphql_request(&request),
+        Ok(GraphqlRequest {
+            operation: "get".to_string(),
+            query: "{ Weapon { name } }".to_string(),
+            variables: None,
+            operation_name: Some("Weapons".to_string())
+        })
+    );
+
+    let get = b"GET /graphql/add?query=%7B+Weapon+%7B+name%3A+Bow+%7D+%7D HTTP/1.1\r\n\r\n";
+    let request = parse_request(&get[..]).unwrap().unwrap();
+    assert_eq!(graphql_request(&request), Err(GraphqlError::Validation("Unknown path: /graphql/add".to_string())));
+
+    assert_eq!(
+        error_body(&GraphqlError::Parse{position: 12, message: "unexpected end of input".to_string()}, Some("{\n  Weapon {")),
+        "{\"errors\":[{\"message\":\"Parse error at 12: unexpected end of input\",\"locations\":[{\"line\":2,\"column\":11}]}]}"
+    );
+    assert_eq!(
+        error_body(&GraphqlError::Invalid(vec![
+            ValidationError{position: 2, message: "Unknown type \"Orc\"".to_string()},
+            ValidationError{position: 17, message: "Cannot query field \"age\" on type \"Leader\"".to_string()}
+        ]), Some("{ Orc { name }\n{ age }")),
+        "{\"errors\":[{\"message\":\"Unknown type \\\"Orc\\\"\",\"locations\":[{\"line\":1,\"column\":3}]},{\"message\":\"Cannot query field \\\"age\\\" on type \\\"Leader\\\"\",\"locations\":[{\"line\":2,\"column\":3}]}]}"
+    );
+    // É takes two bytes
+    assert_eq!(location("{ Warrior (name: \"Éowyn\") { heigh } }", 29), (1, 29));
+    assert_eq!(location("{ Weapon }", 40), (1, 11));
+}
+
+#[test]
+fn test_graphql_response(){
+    let request = GraphqlRequest{operation: "document".to_string(), query: "mutation { add { Weapon { name: Axe } } }".to_string(), variables: None, operation_name: None};
+    let body = |response: Vec<u8>| String::from_utf8(response).unwrap().split("\r\n\r\n").nth(1).unwrap().to_string();
+    assert_eq!(
+        body(graphql_response(&request, Ok(OperationResult::Status("add response completed".to_string())))),
+        "{\"data\":{\"status\":\"add response completed\"}}"
+    );
+    assert_eq!(
+        body(graphql_response(&request, Ok(OperationResult::Json("{\"data\":{\"Weapon\":[]}}".to_string())))),
+        "{\"data\":{\"Weapon\":[]}}"
+    );
+}
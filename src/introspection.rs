@@ -0,0 +1,532 @@
+use std::vec::Vec;
+
+use aggregate;
+use def::*;
+use error::GraphqlError;
+use filter;
+use json;
+use json::JsonValue;
+use reader;
+use relay;
+#[cfg(test)]
+use test_support::column;
+
+// The root type of the queries, it has a list, a connection and an aggregate field for each type of the schema
+pub const QUERY_TYPE: &'static str = "Query";
+pub const SCALAR_TYPES: [&'static str; 5] = ["ID", "String", "Int", "Float", "Boolean"];
+pub const PAGE_INFO_TYPE: &'static str = "PageInfo";
+pub const ORDER_DIRECTION_TYPE: &'static str = "OrderDirection";
+// WarriorEdge holds a node of a WarriorConnection, Warrior_filter is an object of the AND, OR and NOT filters
+// and Warrior_orderBy the object given to orderBy
+pub const EDGE_SUFFIX: &'static str = "Edge";
+pub const FILTER_SUFFIX: &'static str = "_filter";
+pub const ORDER_BY_SUFFIX: &'static str = "_orderBy";
+
+// __schema, __type and __typename are answered from the schema, without querying the database
+pub fn is_introspection(query: &QueryObject) -> bool {
+    query.name.starts_with("__")
+}
+
+// A type as it is used by a field or an argument, wrapped in lists and non null types
+enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>)
+}
+
+fn named(name: &str) -> TypeRef {
+    TypeRef::Named(name.to_string())
+}
+
+fn list_of(of_type: TypeRef) -> TypeRef {
+    TypeRef::List(Box::new(of_type))
+}
+
+fn non_null(of_type: TypeRef) -> TypeRef {
+    TypeRef::NonNull(Box::new(of_type))
+}
+
+struct FieldDefinition {
+    name: String,
+    args: Vec<(String, TypeRef)>,
+    field_type: TypeRef
+}
+
+// [Warrior] gives Warrior
+fn get_relation_target(column: &DbColumn) -> Option<&str> {
+    if column.db_type.starts_with("[") && column.db_type.ends_with("]") {
+        Some(&column.db_type[1..column.db_type.len() - 1])
+    }
+    else {
+        None
+    }
+}
+
+fn invalid_field(field_name: &str, type_name: &str) -> GraphqlError {
+    GraphqlError::Validation("Cannot query field \"".to_string() + field_name + "\" on type \"" + type_name + "\"")
+}
+
+fn get_subfields(field: &QueryObject) -> Result<&Vec<QueryObject>, GraphqlError> {
+    field.attrs.as_ref().ok_or(GraphqlError::Validation("Field \"".to_string() + &field.name + "\" must have a selection of subfields"))
+}
+
+// Builds an object of an introspection type from the fields selected on it, resolve_field gives None for unknown fields
+fn resolve_object<F>(type_name: &str, selection: &Vec<QueryObject>, mut resolve_field: F) -> Result<JsonValue, GraphqlError>
+    where F: FnMut(&QueryObject) -> Result<Option<JsonValue>, GraphqlError> {
+    let mut fields: Vec<(String, JsonValue)> = Vec::new();
+    for field in selection {
+        let value = if field.name == TYPENAME {
+            JsonValue::String(type_name.to_string())
+        }
+        else {
+            try!(try!(resolve_field(field)).ok_or(invalid_field(&field.name, type_name)))
+        };
+        fields.push((field.key().to_string(), value));
+    }
+    Ok(JsonValue::Object(fields))
+}
+
+// Answers the introspection queries from the types and enums read from the schema file
+pub struct Introspector<'a> {
+    database: &'a Vec<DbTable>,
+    enums: &'a Vec<DbEnum>
+}
+
+impl<'a> Introspector<'a> {
+    pub fn new(database: &'a Vec<DbTable>, enums: &'a Vec<DbEnum>) -> Introspector<'a> {
+        Introspector{database: database, enums: enums}
+    }
+
+    fn find_table(&self, name: &str) -> Option<&'a DbTable> {
+        self.database.iter().find(|table| table.name == name)
+    }
+
+    // The table a type is generated for, WarriorConnection with the suffix Connection gives Warrior
+    fn find_generated_table(&self, type_name: &str, suffix: &str) -> Option<&'a DbTable> {
+        if type_name.len() > suffix.len() && type_name.ends_with(suffix) {
+            self.find_table(&type_name[..type_name.len() - suffix.len()])
+        }
+        else {
+            None
+        }
+    }
+
+    fn get_kind(&self, type_name: &str) -> Option<&'static str> {
+        let is_generated = |suffixes: &[&str]| suffixes.iter().any(|suffix| self.find_generated_table(type_name, suffix).is_some());
+        if type_name == QUERY_TYPE || type_name == PAGE_INFO_TYPE || self.find_table(type_name).is_some()
+            || is_generated(&[relay::CONNECTION_SUFFIX, EDGE_SUFFIX, aggregate::AGGREGATE_SUFFIX]) {
+            Some("OBJECT")
+        }
+        else if is_generated(&[FILTER_SUFFIX, ORDER_BY_SUFFIX]) {
+            Some("INPUT_OBJECT")
+        }
+        else if self.get_enum_values(type_name).is_some() {
+            Some("ENUM")
+        }
+        else if SCALAR_TYPES.contains(&type_name) {
+            Some("SCALAR")
+        }
+        else {
+            None
+        }
+    }
+
+    fn get_type_names(&self) -> Vec<String> {
+        let mut type_names: Vec<String> = vec![QUERY_TYPE.to_string()];
+        for table in self.database {
+            for suffix in &["", relay::CONNECTION_SUFFIX, EDGE_SUFFIX, aggregate::AGGREGATE_SUFFIX, FILTER_SUFFIX, ORDER_BY_SUFFIX] {
+                type_names.push(table.name.clone() + suffix);
+            }
+        }
+        type_names.push(PAGE_INFO_TYPE.to_string());
+        type_names.extend(self.enums.iter().map(|enumeration| enumeration.name.clone()));
+        type_names.push(ORDER_DIRECTION_TYPE.to_string());
+        type_names.extend(SCALAR_TYPES.iter().map(|scalar| scalar.to_string()));
+        type_names
+    }
+
+    fn get_enum_values(&self, type_name: &str) -> Option<Vec<String>> {
+        if type_name == ORDER_DIRECTION_TYPE {
+            return Some(vec!["ASC".to_string(), "DESC".to_string()]);
+        }
+        self.enums.iter().find(|enumeration| enumeration.name == type_name).map(|enumeration| enumeration.values.clone())
+    }
+
+    // Relations are lists of their target type, mandatory columns are non null
+    fn get_column_type(&self, column: &DbColumn) -> TypeRef {
+        let graphql_type = reader::mysql_to_graphql_type(&column.db_type);
+        let column_type = match get_relation_target(column) {
+            Some(target) => list_of(non_null(named(target))),
+            None => TypeRef::Named(graphql_type)
+        };
+        if column.is_mandatory {non_null(column_type)} else {column_type}
+    }
+
+    // The id and the scalar fields of a table with their GraphQL type, the fields objects are filtered and ordered by
+    fn get_scalar_fields(&self, table: &DbTable) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = vec![("id".to_string(), "ID".to_string())];
+        for column in &table.columns {
+            if get_relation_target(column).is_none() {
+                fields.push((column.name.clone(), reader::mysql_to_graphql_type(&column.db_type)));
+            }
+        }
+        fields
+    }
+
+    // A filter for each scalar field and operator of the filter module, strength_gt: Int, name_in: [String!],
+    // and the AND, OR and NOT combinations of filters
+    fn get_filter_arguments(&self, table: &DbTable) -> Vec<(String, TypeRef)> {
+        let mut args: Vec<(String, TypeRef)> = Vec::new();
+        for (name, field_type) in self.get_scalar_fields(table) {
+            args.push((name.clone(), named(&field_type)));
+            for operator in &filter::FILTER_OPERATORS {
+                let arg_type = match *operator {
+                    "in" => list_of(non_null(named(&field_type))),
+                    "isNull" => named("Boolean"),
+                    // contains and startsWith match text
+                    "contains" | "startsWith" if field_type != "String" => continue,
+                    _ => named(&field_type)
+                };
+                args.push((name.clone() + "_" + operator, arg_type));
+            }
+        }
+        let filter_type = table.name.clone() + FILTER_SUFFIX;
+        args.push(("AND".to_string(), list_of(non_null(named(&filter_type)))));
+        args.push(("OR".to_string(), list_of(non_null(named(&filter_type)))));
+        args.push(("NOT".to_string(), named(&filter_type)));
+        args
+    }
+
+    // The arguments of a list of objects, its filters, orderBy and the pagination
+    fn get_list_arguments(&self, table: &DbTable) -> Vec<(String, TypeRef)> {
+        let mut args = self.get_filter_arguments(table);
+        args.push(("orderBy".to_string(), named(&(table.name.clone() + ORDER_BY_SUFFIX))));
+        args.push(("first".to_string(), named("Int")));
+        args.push(("offset".to_string(), named("Int")));
+        args
+    }
+
+    // The pages of a connection are taken in the order of the ids, from the cursor given in after
+    fn get_connection_arguments(&self, table: &DbTable) -> Vec<(String, TypeRef)> {
+        let mut args = self.get_filter_arguments(table);
+        args.push(("first".to_string(), named("Int")));
+        args.push(("after".to_string(), named("String")));
+        args
+    }
+
+    fn get_aggregate_arguments(&self, table: &DbTable) -> Vec<(String, TypeRef)> {
+        let mut args = self.get_filter_arguments(table);
+        args.push(("groupBy".to_string(), list_of(non_null(named("String")))));
+        args
+    }
+
+    fn get_fields(&self, type_name: &str) -> Option<Vec<FieldDefinition>> {
+        let field = |name: String, args: Vec<(String, TypeRef)>, field_type: TypeRef| FieldDefinition{name: name, args: args, field_type: field_type};
+        if type_name == QUERY_TYPE {
+            let mut fields: Vec<FieldDefinition> = Vec::new();
+            for table in self.database {
+                fields.push(field(table.name.clone(), self.get_list_arguments(table), list_of(non_null(named(&table.name)))));
+                let connection_name = table.name.clone() + relay::CONNECTION_SUFFIX;
+                fields.push(field(connection_name.clone(), self.get_connection_arguments(table), non_null(named(&connection_name))));
+                let aggregate_name = table.name.clone() + aggregate::AGGREGATE_SUFFIX;
+                fields.push(field(aggregate_name.clone(), self.get_aggregate_arguments(table), list_of(non_null(named(&aggregate_name)))));
+            }
+            return Some(fields);
+        }
+        if type_name == PAGE_INFO_TYPE {
+            return Some(vec![
+                field("hasNextPage".to_string(), Vec::new(), non_null(named("Boolean"))),
+                field("hasPreviousPage".to_string(), Vec::new(), non_null(named("Boolean"))),
+                field("startCursor".to_string(), Vec::new(), named("String")),
+                field("endCursor".to_string(), Vec::new(), named("String"))
+            ]);
+        }
+        if let Some(table) = self.find_generated_table(type_name, relay::CONNECTION_SUFFIX) {
+            return Some(vec![
+                field("edges".to_string(), Vec::new(), non_null(list_of(non_null(named(&(table.name.clone() + EDGE_SUFFIX)))))),
+                field("pageInfo".to_string(), Vec::new(), non_null(named(PAGE_INFO_TYPE)))
+            ]);
+        }
+        if let Some(table) = self.find_generated_table(type_name, EDGE_SUFFIX) {
+            return Some(vec![
+                field("cursor".to_string(), Vec::new(), non_null(named("String"))),
+                field("node".to_string(), Vec::new(), non_null(named(&table.name)))
+            ]);
+        }
+        // count, the fields given in groupBy and the functions of the fields, avg(strength)
+        if let Some(table) = self.find_generated_table(type_name, aggregate::AGGREGATE_SUFFIX) {
+            let mut fields = vec![field("count".to_string(), Vec::new(), non_null(named("Int")))];
+            for column in &table.columns {
+                if get_relation_target(column).is_some() {
+                    continue;
+                }
+                let column_type = reader::mysql_to_graphql_type(&column.db_type);
+                fields.push(field(column.name.clone(), Vec::new(), named(&column_type)));
+                for function in &aggregate::AGGREGATE_FUNCTIONS[1..] {
+                    let function_type = match *function {
+                        "sum" | "avg" if column.db_type != "INT" => continue,
+                        "sum" => "Int",
+                        "avg" => "Float",
+                        _ => column_type.as_str()
+                    };
+                    fields.push(field(function.to_string() + "(" + &column.name + ")", Vec::new(), named(function_type)));
+                }
+            }
+            return Some(fields);
+        }
+        self.find_table(type_name).map(|table| {
+            let mut fields = vec![field("id".to_string(), Vec::new(), non_null(named("ID")))];
+            for column in &table.columns {
+                match get_relation_target(column).and_then(|target| self.find_table(target)) {
+                    Some(target) => {
+                        fields.push(field(column.name.clone(), self.get_list_arguments(target), self.get_column_type(column)));
+                        let connection_type = target.name.clone() + relay::CONNECTION_SUFFIX;
+                        fields.push(field(column.name.clone() + relay::CONNECTION_SUFFIX, self.get_connection_arguments(target), non_null(named(&connection_type))));
+                        fields.push(field(column.name.clone() + aggregate::COUNT_SUFFIX, self.get_filter_arguments(target), non_null(named("Int"))));
+                    },
+                    None => fields.push(field(column.name.clone(), Vec::new(), self.get_column_type(column)))
+                }
+            }
+            fields
+        })
+    }
+
+    // The fields of the input objects, the filters of Warrior_filter and the scalar fields of Warrior_orderBy
+    fn get_input_fields(&self, type_name: &str) -> Option<Vec<(String, TypeRef)>> {
+        if let Some(table) = self.find_generated_table(type_name, FILTER_SUFFIX) {
+            return Some(self.get_filter_arguments(table));
+        }
+        self.find_generated_table(type_name, ORDER_BY_SUFFIX).map(|table| {
+            self.get_scalar_fields(table).into_iter().map(|(name, _)| (name, named(ORDER_DIRECTION_TYPE))).collect()
+        })
+    }
+
+    fn resolve_schema(&self, selection: &Vec<QueryObject>) -> Result<JsonValue, GraphqlError> {
+        resolve_object("__Schema", selection, |field| Ok(match field.name.as_str() {
+            "types" => {
+                let subfields = try!(get_subfields(field));
+                let mut types: Vec<JsonValue> = Vec::new();
+                for type_name in self.get_type_names() {
+                    types.push(try!(self.resolve_type(&TypeRef::Named(type_name), subfields)));
+                }
+                Some(JsonValue::Array(types))
+            },
+            "queryType" => Some(try!(self.resolve_type(&TypeRef::Named(QUERY_TYPE.to_string()), try!(get_subfields(field))))),
+            "mutationType" | "subscriptionType" | "description" => Some(JsonValue::Null),
+            "directives" => Some(JsonValue::Array(Vec::new())),
+            _ => None
+        }))
+    }
+
+    fn resolve_type(&self, type_ref: &TypeRef, selection: &Vec<QueryObject>) -> Result<JsonValue, GraphqlError> {
+        let (kind, name, of_type) = match *type_ref {
+            TypeRef::Named(ref name) => (self.get_kind(name).unwrap_or("SCALAR"), Some(name.as_str()), None),
+            TypeRef::List(ref of_type) => ("LIST", None, Some(of_type)),
+            TypeRef::NonNull(ref of_type) => ("NON_NULL", None, Some(of_type))
+        };
+        resolve_object("__Type", selection, |field| Ok(match field.name.as_str() {
+            "kind" => Some(JsonValue::String(kind.to_string())),
+            "name" => Some(name.map_or(JsonValue::Null, |name| JsonValue::String(name.to_string()))),
+            "ofType" => match of_type {
+                Some(of_type) => Some(try!(self.resolve_type(of_type, try!(get_subfields(field))))),
+                None => Some(JsonValue::Null)
+            },
+            "fields" => match name.and_then(|name| self.get_fields(name)) {
+                Some(field_definitions) => {
+                    let subfields = try!(get_subfields(field));
+                    let mut fields: Vec<JsonValue> = Vec::new();
+                    for field_definition in &field_definitions {
+                        fields.push(try!(self.resolve_field(field_definition, subfields)));
+                    }
+                    Some(JsonValue::Array(fields))
+                },
+                None => Some(JsonValue::Null)
+            },
+            "interfaces" => Some(if kind == "OBJECT" {JsonValue::Array(Vec::new())} else {JsonValue::Null}),
+            "enumValues" => match name.and_then(|name| self.get_enum_values(name)) {
+                Some(enum_values) => {
+                    let subfields = try!(get_subfields(field));
+                    let mut values: Vec<JsonValue> = Vec::new();
+                    for value in &enum_values {
+                        values.push(try!(self.resolve_enum_value(value, subfields)));
+                    }
+                    Some(JsonValue::Array(values))
+                },
+                None => Some(JsonValue::Null)
+            },
+            "inputFields" => match name.and_then(|name| self.get_input_fields(name)) {
+                Some(input_fields) => {
+                    let subfields = try!(get_subfields(field));
+                    let mut values: Vec<JsonValue> = Vec::new();
+                    for &(ref input_name, ref input_type) in &input_fields {
+                        values.push(try!(self.resolve_input_value(input_name, input_type, subfields)));
+                    }
+                    Some(JsonValue::Array(values))
+                },
+                None => Some(JsonValue::Null)
+            },
+            "description" | "possibleTypes" | "specifiedByURL" => Some(JsonValue::Null),
+            _ => None
+        }))
+    }
+
+    fn resolve_field(&self, field_definition: &FieldDefinition, selection: &Vec<QueryObject>) -> Result<JsonValue, GraphqlError> {
+        resolve_object("__Field", selection, |field| Ok(match field.name.as_str() {
+            "name" => Some(JsonValue::String(field_definition.name.clone())),
+            "args" => {
+                let subfields = try!(get_subfields(field));
+                let mut args: Vec<JsonValue> = Vec::new();
+                for &(ref name, ref arg_type) in &field_definition.args {
+                    args.push(try!(self.resolve_input_value(name, arg_type, subfields)));
+                }
+                Some(JsonValue::Array(args))
+            },
+            "type" => Some(try!(self.resolve_type(&field_definition.field_type, try!(get_subfields(field))))),
+            "isDeprecated" => Some(JsonValue::Boolean(false)),
+            "description" | "deprecationReason" => Some(JsonValue::Null),
+            _ => None
+        }))
+    }
+
+    fn resolve_input_value(&self, name: &str, input_type: &TypeRef, selection: &Vec<QueryObject>) -> Result<JsonValue, GraphqlError> {
+        resolve_object("__InputValue", selection, |field| Ok(match field.name.as_str() {
+            "name" => Some(JsonValue::String(name.to_string())),
+            "type" => Some(try!(self.resolve_type(input_type, try!(get_subfields(field))))),
+            "description" | "defaultValue" => Some(JsonValue::Null),
+            _ => None
+        }))
+    }
+
+    fn resolve_enum_value(&self, name: &str, selection: &Vec<QueryObject>) -> Result<JsonValue, GraphqlError> {
+        resolve_object("__EnumValue", selection, |field| Ok(match field.name.as_str() {
+            "name" => Some(JsonValue::String(name.to_string())),
+            "isDeprecated" => Some(JsonValue::Boolean(false)),
+            "description" | "deprecationReason" => Some(JsonValue::Null),
+            _ => None
+        }))
+    }
+
+    // { __schema { types { name } } }, { __type (name: Warrior) { fields { name } } } or { __typename }
+    pub fn perform_introspection(&self, query: &QueryObject) -> Result<String, GraphqlError> {
+        let result = match query.name.as_str() {
+            "__schema" => try!(self.resolve_schema(try!(get_subfields(query)))),
+            "__type" => {
+                let type_name = try!(query.params.as_ref().and_then(|params| params.iter().find(|&&(ref name, _)| name == "name")).ok_or(
+                    GraphqlError::Validation("Field \"__type\" argument \"name\" of type \"String!\" is required".to_string())
+                )).1.clone();
+                match self.get_kind(&type_name) {
+                    Some(_) => try!(self.resolve_type(&TypeRef::Named(type_name), try!(get_subfields(query)))),
+                    None => JsonValue::Null
+                }
+            },
+            TYPENAME => JsonValue::String(QUERY_TYPE.to_string()),
+            _ => return Err(invalid_field(&query.name, QUERY_TYPE))
+        };
+        let data = JsonValue::Object(vec![(query.key().to_string(), result)]);
+        Ok(json::response_body(Some(data), Vec::new()).to_string())
+    }
+}
+
+#[test]
+fn test_introspection(){
+    use parser;
+
+    let database = vec![
+        DbTable{name: "Leader".to_string(), columns: vec![column("name", "TEXT(2048)", true), column("leads", "[Warrior]", false)]},
+        DbTable{name: "Warrior".to_string(), columns: vec![column("name", "TEXT(2048)", true), column("race", "Race", false), column("strength", "INT", false)]}
+    ];
+    let enums = vec![DbEnum{name: "Race".to_string(), values: vec!["Elf".to_string(), "Orc".to_string()]}];
+    let introspector = Introspector::new(&database, &enums);
+    let introspect = |query: &str| {
+        let query = parser::into_result(query.as_bytes(), parser::parse_query(query.as_bytes())).unwrap();
+        introspector.perform_introspection(&query)
+    };
+    assert_eq!(
+        introspect("{ __schema { queryType { name } types { name kind } } }"),
+        Ok("{\"data\":{\"__schema\":{\"queryType\":{\"name\":\"Query\"},\"types\":[{\"name\":\"Query\",\"kind\":\"OBJECT\"},\
+            {\"name\":\"Leader\",\"kind\":\"OBJECT\"},{\"name\":\"LeaderConnection\",\"kind\":\"OBJECT\"},{\"name\":\"LeaderEdge\",\"kind\":\"OBJECT\"},\
+            {\"name\":\"Leader_aggregate\",\"kind\":\"OBJECT\"},{\"name\":\"Leader_filter\",\"kind\":\"INPUT_OBJECT\"},{\"name\":\"Leader_orderBy\",\"kind\":\"INPUT_OBJECT\"},\
+            {\"name\":\"Warrior\",\"kind\":\"OBJECT\"},{\"name\":\"WarriorConnection\",\"kind\":\"OBJECT\"},{\"name\":\"WarriorEdge\",\"kind\":\"OBJECT\"},\
+            {\"name\":\"Warrior_aggregate\",\"kind\":\"OBJECT\"},{\"name\":\"Warrior_filter\",\"kind\":\"INPUT_OBJECT\"},{\"name\":\"Warrior_orderBy\",\"kind\":\"INPUT_OBJECT\"},\
+            {\"name\":\"PageInfo\",\"kind\":\"OBJECT\"},{\"name\":\"Race\",\"kind\":\"ENUM\"},{\"name\":\"OrderDirection\",\"kind\":\"ENUM\"},\
+            {\"name\":\"ID\",\"kind\":\"SCALAR\"},{\"name\":\"String\",\"kind\":\"SCALAR\"},{\"name\":\"Int\",\"kind\":\"SCALAR\"},{\"name\":\"Float\",\"kind\":\"SCALAR\"},{\"name\":\"Boolean\",\"kind\":\"SCALAR\"}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: Query) { fields { name type { kind name ofType { kind name ofType { name } } } } } }"),
+        Ok("{\"data\":{\"__type\":{\"fields\":[\
+            {\"name\":\"Leader\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Leader\"}}}},\
+            {\"name\":\"LeaderConnection\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"OBJECT\",\"name\":\"LeaderConnection\",\"ofType\":null}}},\
+            {\"name\":\"Leader_aggregate\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Leader_aggregate\"}}}},\
+            {\"name\":\"Warrior\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Warrior\"}}}},\
+            {\"name\":\"WarriorConnection\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"OBJECT\",\"name\":\"WarriorConnection\",\"ofType\":null}}},\
+            {\"name\":\"Warrior_aggregate\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Warrior_aggregate\"}}}}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: \"Leader\") { __typename name fields { name type { kind name ofType { kind name ofType { name } } } } } }"),
+        Ok("{\"data\":{\"__type\":{\"__typename\":\"__Type\",\"name\":\"Leader\",\"fields\":[\
+            {\"name\":\"id\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"SCALAR\",\"name\":\"ID\",\"ofType\":null}}},\
+            {\"name\":\"name\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"SCALAR\",\"name\":\"String\",\"ofType\":null}}},\
+            {\"name\":\"leads\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Warrior\"}}}},\
+            {\"name\":\"leadsConnection\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"OBJECT\",\"name\":\"WarriorConnection\",\"ofType\":null}}},\
+            {\"name\":\"leads_count\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"SCALAR\",\"name\":\"Int\",\"ofType\":null}}}]}}}".to_string())
+    );
+    let argument_names = |args: Vec<(String, TypeRef)>| args.into_iter().map(|(name, _)| name).collect::<Vec<String>>().join(" ");
+    assert_eq!(
+        argument_names(introspector.get_filter_arguments(&database[1])),
+        "id id_gt id_gte id_lt id_lte id_ne id_in id_isNull \
+         name name_gt name_gte name_lt name_lte name_ne name_in name_contains name_startsWith name_isNull \
+         race race_gt race_gte race_lt race_lte race_ne race_in race_isNull \
+         strength strength_gt strength_gte strength_lt strength_lte strength_ne strength_in strength_isNull AND OR NOT"
+    );
+    assert!(argument_names(introspector.get_list_arguments(&database[1])).ends_with("AND OR NOT orderBy first offset"));
+    assert!(argument_names(introspector.get_connection_arguments(&database[1])).ends_with("AND OR NOT first after"));
+    assert!(argument_names(introspector.get_aggregate_arguments(&database[1])).ends_with("AND OR NOT groupBy"));
+    assert_eq!(
+        introspect("{ __type (name: \"Warrior_filter\") { kind fields { name } inputFields { name type { kind name ofType { kind name ofType { name } } } } } }")
+            .map(|response| response.contains("{\"name\":\"OR\",\"type\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Warrior_filter\"}}}}")),
+        Ok(true)
+    );
+    assert_eq!(
+        introspect("{ __type (name: \"Warrior_orderBy\") { kind inputFields { name type { name } } } }"),
+        Ok("{\"data\":{\"__type\":{\"kind\":\"INPUT_OBJECT\",\"inputFields\":[{\"name\":\"id\",\"type\":{\"name\":\"OrderDirection\"}},\
+            {\"name\":\"name\",\"type\":{\"name\":\"OrderDirection\"}},{\"name\":\"race\",\"type\":{\"name\":\"OrderDirection\"}},\
+            {\"name\":\"strength\",\"type\":{\"name\":\"OrderDirection\"}}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: WarriorConnection) { kind fields { name type { kind name ofType { kind name ofType { kind name ofType { name } } } } } } }"),
+        Ok("{\"data\":{\"__type\":{\"kind\":\"OBJECT\",\"fields\":[\
+            {\"name\":\"edges\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"LIST\",\"name\":null,\"ofType\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"WarriorEdge\"}}}}},\
+            {\"name\":\"pageInfo\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"kind\":\"OBJECT\",\"name\":\"PageInfo\",\"ofType\":null}}}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: WarriorEdge) { fields { name type { kind name ofType { name } } } } }"),
+        Ok("{\"data\":{\"__type\":{\"fields\":[{\"name\":\"cursor\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"String\"}}},\
+            {\"name\":\"node\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"Warrior\"}}}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: PageInfo) { fields { name } } }"),
+        Ok("{\"data\":{\"__type\":{\"fields\":[{\"name\":\"hasNextPage\"},{\"name\":\"hasPreviousPage\"},{\"name\":\"startCursor\"},{\"name\":\"endCursor\"}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: \"Warrior_aggregate\") { fields { name type { name } } } }"),
+        Ok("{\"data\":{\"__type\":{\"fields\":[{\"name\":\"count\",\"type\":{\"name\":null}},\
+            {\"name\":\"name\",\"type\":{\"name\":\"String\"}},{\"name\":\"min(name)\",\"type\":{\"name\":\"String\"}},{\"name\":\"max(name)\",\"type\":{\"name\":\"String\"}},\
+            {\"name\":\"race\",\"type\":{\"name\":\"Race\"}},{\"name\":\"min(race)\",\"type\":{\"name\":\"Race\"}},{\"name\":\"max(race)\",\"type\":{\"name\":\"Race\"}},\
+            {\"name\":\"strength\",\"type\":{\"name\":\"Int\"}},{\"name\":\"sum(strength)\",\"type\":{\"name\":\"Int\"}},{\"name\":\"avg(strength)\",\"type\":{\"name\":\"Float\"}},\
+            {\"name\":\"min(strength)\",\"type\":{\"name\":\"Int\"}},{\"name\":\"max(strength)\",\"type\":{\"name\":\"Int\"}}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: Race) { kind enumValues { name isDeprecated } } }"),
+        Ok("{\"data\":{\"__type\":{\"kind\":\"ENUM\",\"enumValues\":[{\"name\":\"Elf\",\"isDeprecated\":false},{\"name\":\"Orc\",\"isDeprecated\":false}]}}}".to_string())
+    );
+    assert_eq!(
+        introspect("{ __type (name: OrderDirection) { kind enumValues { name } } }"),
+        Ok("{\"data\":{\"__type\":{\"kind\":\"ENUM\",\"enumValues\":[{\"name\":\"ASC\"},{\"name\":\"DESC\"}]}}}".to_string())
+    );
+    assert_eq!(introspect("{ __type (name: Dwarf) { name } }"), Ok("{\"data\":{\"__type\":null}}".to_string()));
+    assert_eq!(introspect("{ __typename }"), Ok("{\"data\":{\"__typename\":\"Query\"}}".to_string()));
+    assert_eq!(
+        introspect("{ __schema { types { name height } } }"),
+        Err(GraphqlError::Validation("Cannot query field \"height\" on type \"__Type\"".to_string()))
+    );
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::str;
+use std::vec::Vec;
+
+use error::GraphqlError;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonValue {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    // keeps the order of the keys, GraphQL responses follow the order of the selection set
+    Object(Vec<(String, JsonValue)>)
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match *self {
+            JsonValue::Object(ref fields) => {
+                for field in fields {
+                    if field.0 == key {
+                        return Some(&field.1);
+                    }
+                }
+                None
+            },
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            JsonValue::String(ref value) => Some(value.as_str()),
+            _ => None
+        }
+    }
+}
+
+// A GraphQL response, the data key is left out when the request failed and the errors key when it did not
+pub fn response_body(data: Option<JsonValue>, errors: Vec<JsonValue>) -> JsonValue {
+    let mut fields: Vec<(String, JsonValue)> = Vec::new();
+    if let Some(data) = data {
+        fields.push(("data".to_string(), data));
+    }
+    if !errors.is_empty() {
+        fields.push(("errors".to_string(), JsonValue::Array(errors)));
+    }
+    JsonValue::Object(fields)
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = "\"".to_string();
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Boolean(value) => write!(f, "{}", value),
+            JsonValue::Int(value) => write!(f, "{}", value),
+            JsonValue::Float(value) => {
+                if value.is_finite() {write!(f, "{:?}", value)} else {write!(f, "null")}
+            },
+            JsonValue::String(ref value) => write!(f, "{}", escape_string(value)),
+            JsonValue::Array(ref values) => {
+                try!(write!(f, "["));
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {try!(write!(f, ","));}
+                    try!(write!(f, "{}", value));
+                }
+                write!(f, "]")
+            },
+            JsonValue::Object(ref fields) => {
+                try!(write!(f, "{{"));
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {try!(write!(f, ","));}
+                    try!(write!(f, "{}:{}", escape_string(&field.0), field.1));
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// Objects and arrays are parsed recursively, deeper nesting is rejected before it can overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, message: &str) -> GraphqlError {
+        GraphqlError::Parse{position: self.position, message: message.to_string()}
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.input.len() && (self.input[self.position] as char).is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), GraphqlError> {
+        if self.input[self.position..].starts_with(token.as_bytes()) {
+            self.position += token.len();
+            Ok(())
+        }
+        else {
+            Err(self.error(&("expected ".to_string() + token)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, GraphqlError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == b'{' || c == b'[' => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("too deeply nested"));
+                }
+                self.depth += 1;
+                let value = if c == b'{' {self.parse_object()} else {self.parse_array()};
+                self.depth -= 1;
+                value
+            },
+            Some(b'"') => Ok(JsonValue::String(try!(self.parse_string()))),
+            Some(b't') => {try!(self.expect("true")); Ok(JsonValue::Boolean(true))},
+            Some(b'f') => {try!(self.expect("false")); Ok(JsonValue::Boolean(false))},
+            Some(b'n') => {try!(self.expect("null")); Ok(JsonValue::Null)},
+            Some(c) if c == b'-' || (c as char).is_digit(10) => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input"))
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, GraphqlError> {
+        let mut fields: Vec<(String, JsonValue)> = Vec::new();
+        try!(self.expect("{"));
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = try!(self.parse_string());
+            self.skip_whitespace();
+            try!(self.expect(":"));
+            let value = try!(self.parse_value());
+            fields.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {self.position += 1; return Ok(JsonValue::Object(fields));},
+                _ => return Err(self.error("expected , or }"))
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, GraphqlError> {
+        let mut values: Vec<JsonValue> = Vec::new();
+        try!(self.expect("["));
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(try!(self.parse_value()));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {self.position += 1; return Ok(JsonValue::Array(values));},
+                _ => return Err(self.error("expected , or ]"))
+            }
+        }
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, GraphqlError> {
+        if self.position + 4 > self.input.len() {
+            return Err(self.error("unexpected end of input"));
+        }
+        let digits = try!(str::from_utf8(&self.input[self.position..self.position + 4]).map_err(|_| self.error("invalid unicode escape")));
+        let code = try!(u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape")));
+        self.position += 4;
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, GraphqlError> {
+        try!(self.expect("\""));
+        let mut bytes: Vec<u8> = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                },
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = match self.peek() {
+                        Some(c) => c,
+                        None => return Err(self.error("unterminated string"))
+                    };
+                    self.position += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = try!(self.parse_hex());
+                            if code >= 0xD800 && code < 0xDC00 {
+                                try!(self.expect("\\u"));
+                                let low = try!(self.parse_hex());
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            try!(::std::char::from_u32(code).ok_or(self.error("invalid unicode escape")))
+                        },
+                        _ => return Err(self.error("invalid escape sequence"))
+                    };
+                    let mut encoded = String::new();
+                    encoded.push(c);
+                    bytes.extend_from_slice(encoded.as_bytes());
+                },
+                Some(c) => {
+                    self.position += 1;
+                    bytes.push(c);
+                }
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid utf-8 in string"))
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, GraphqlError> {
+        let start = self.position;
+        let mut is_float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                b'0'...b'9' | b'-' | b'+' => {},
+                b'.' | b'e' | b'E' => is_float = true,
+                _ => break
+            }
+            self.position += 1;
+        }
+        let number = str::from_utf8(&self.input[start..self.position]).unwrap();
+        if is_float {
+            number.parse::<f64>().map(JsonValue::Float).map_err(|_| self.error("invalid number"))
+        }
+        else {
+            match number.parse::<i64>() {
+                Ok(value) => Ok(JsonValue::Int(value)),
+                Err(_) => number.parse::<f64>().map(JsonValue::Float).map_err(|_| self.error("invalid number"))
+            }
+        }
+    }
+}
+
+pub fn parse(input: &[u8]) -> Result<JsonValue, GraphqlError> {
+    let mut parser = JsonParser{input: input, position: 0, depth: 0};
+    let value = try!(parser.parse_value());
+    parser.skip_whitespace();
+    if parser.position != input.len() {
+        return Err(parser.error("unexpected input after the JSON value"));
+    }
+    Ok(value)
+}
+
+#[test]
+fn test_json_parse_and_print(){
+    let value = parse(&b"{\"query\": \"{ Warrior (name: \\\"elf\\\\1\\\") { name } }\", \"variables\": {\"id\": 8, \"ratio\": 0.5, \"tags\": [true, null]}, \"operationName\": null}"[..]).unwrap();
+    assert_eq!(
+        value,
+        JsonValue::Object(vec![
+            ("query".to_string(), JsonValue::String("{ Warrior (name: \"elf\\1\") { name } }".to_string())),
+            ("variables".to_string(), JsonValue::Object(vec![
+                ("id".to_string(), JsonValue::Int(8)),
+                ("ratio".to_string(), JsonValue::Float(0.5)),
+                ("tags".to_string(), JsonValue::Array(vec![JsonValue::Boolean(true), JsonValue::Null]))
+            ])),
+            ("operationName".to_string(), JsonValue::Null)
+        ])
+    );
+    assert_eq!(
+        value.to_string(),
+        "{\"query\":\"{ Warrior (name: \\\"elf\\\\1\\\") { name } }\",\"variables\":{\"id\":8,\"ratio\":0.5,\"tags\":[true,null]},\"operationName\":null}"
+    );
+    assert_eq!(parse(&b"\"caf\\u00e9\\n\""[..]), Ok(JsonValue::String("caf\u{e9}\n".to_string())));
+    assert_eq!(parse(&b"{\"query\": }"[..]), Err(GraphqlError::Parse{position: 10, message: "unexpected character".to_string()}));
+
+    let nested = (0..MAX_DEPTH).map(|_| "[").collect::<String>() + &(0..MAX_DEPTH).map(|_| "]").collect::<String>();
+    assert!(parse(nested.as_bytes()).is_ok());
+    let too_nested: String = (0..100000).map(|_| "[").collect();
+    assert_eq!(parse(too_nested.as_bytes()), Err(GraphqlError::Parse{position: MAX_DEPTH, message: "too deeply nested".to_string()}));
+}
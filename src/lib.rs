@@ -18,7 +18,23 @@ pub mod reader;
 pub mod serialize;
 pub mod deserialize;
 pub mod def;
+pub mod error;
 pub mod parser;
+pub mod validator;
+pub mod variables;
+pub mod fragments;
+pub mod filter;
+pub mod relay;
+pub mod aggregate;
+pub mod introspection;
+pub mod printer;
+pub mod migration;
+pub mod json;
+pub mod http;
 pub mod connection;
 pub mod connection_pool;
-pub mod graphql_pool;
\ No newline at end of file
+pub mod worker_pool;
+pub mod graphql_pool;
+
+#[cfg(test)]
+mod test_support;
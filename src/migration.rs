@@ -0,0 +1,307 @@
+use mysql;
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use printer;
+use reader;
+use serialize::Serializer;
+#[cfg(test)]
+use test_support::{column, existing_column};
+
+// FNV-1a, the version of a schema must not change from a build to another
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// The canonical SDL of a schema, its types then its enums
+fn canonical_schema(database: &Vec<DbTable>, enums: &Vec<DbEnum>) -> String {
+    printer::print_schema(&reader::into_definitions(database, enums))
+}
+
+// The version of a schema is the hash of its canonical SDL, the formatting of the schema file and the order
+// of its enums among its types do not change it
+pub fn schema_version(database: &Vec<DbTable>, enums: &Vec<DbEnum>) -> String {
+    format!("{:016x}", hash(canonical_schema(database, enums).as_bytes()))
+}
+
+// MySQL reports the types it stores, INT as int(11), TEXT(2048) as text and BOOLEAN as tinyint(1)
+fn normalize_column_type(column_type: &str) -> String {
+    let lowercase = column_type.to_lowercase();
+    if lowercase.starts_with("enum(") {
+        // the values of an enum keep their case
+        return "enum".to_string() + &column_type[4..];
+    }
+    match lowercase.as_str() {
+        "boolean" | "bool" => "tinyint(1)".to_string(),
+        _ if lowercase.starts_with("int") => "int".to_string(),
+        _ if lowercase.starts_with("text") => "text".to_string(),
+        _ => lowercase
+    }
+}
+
+// Table names are compared without case, MySQL lowercases them on some systems
+fn is_table(column: &ExistingColumn, table_name: &str) -> bool {
+    column.table_name.to_lowercase() == table_name.to_lowercase()
+}
+
+// The values of an ENUM column type, enum('Elf','Orc') gives 'Elf' and 'Orc'
+fn get_enum_values(column_type: &str) -> Option<Vec<&str>> {
+    if column_type.starts_with("enum(") && column_type.ends_with(")") {
+        Some(column_type[5..column_type.len() - 1].split(',').collect())
+    }
+    else {
+        None
+    }
+}
+
+// A modification keeps every stored value when it only makes a column nullable or adds values to an enum
+fn is_widening(existing_column: &ExistingColumn, column_type: &str, is_mandatory: bool) -> bool {
+    let existing_type = normalize_column_type(&existing_column.column_type);
+    let column_type = normalize_column_type(column_type);
+    let keeps_values = existing_type == column_type || match (get_enum_values(&existing_type), get_enum_values(&column_type)) {
+        (Some(existing_values), Some(values)) => existing_values.iter().all(|value| values.contains(value)),
+        _ => false
+    };
+    keeps_values && (existing_column.is_mandatory || !is_mandatory)
+}
+
+// The migration that brings the database described by existing to the schema: the missing tables, relation tables
+// and history table are created, and the columns of the existing tables are added, modified or dropped.
+// Dropping a column or modifying it in a way that can lose its values is a destructive statement.
+// The tables of the types and relations removed from the schema are kept.
+pub fn plan_migration(serializer: &Serializer, db_name: &str, database: &Vec<DbTable>, enums: &Vec<DbEnum>, existing: &Vec<ExistingColumn>) -> Migration {
+    let mut statements: Vec<String> = Vec::new();
+    let mut destructive_statements: Vec<String> = Vec::new();
+    let table_exists = |table_name: &str| existing.iter().any(|column| is_table(column, table_name));
+    if !table_exists(HISTORY_TABLE) {
+        statements.push(serializer.create_history_table(db_name.to_string()));
+    }
+
+    for table in database {
+        if !table_exists(&table.name) {
+            statements.push(serializer.create_table(db_name.to_string(), table).0);
+            continue;
+        }
+        let existing_columns: Vec<&ExistingColumn> = existing.iter().filter(|column| is_table(column, &table.name)).collect();
+        let columns: Vec<&DbColumn> = table.columns.iter().filter(|column| !column.db_type.starts_with("[")).collect();
+        for column in &columns {
+            match existing_columns.iter().find(|existing_column| existing_column.name == column.name) {
+                None => statements.push(serializer.add_column(db_name.to_string(), &table.name, column)),
+                Some(existing_column) => {
+                    let column_type = serializer.get_sql_type(&column.db_type);
+                    if normalize_column_type(&existing_column.column_type) == normalize_column_type(&column_type) && existing_column.is_mandatory == column.is_mandatory {
+                        continue;
+                    }
+                    let statement = serializer.modify_column(db_name.to_string(), &table.name, column);
+                    if is_widening(existing_column, &column_type, column.is_mandatory) {
+                        statements.push(statement);
+                    }
+                    else {
+                        destructive_statements.push(statement);
+                    }
+                }
+            }
+        }
+        for existing_column in &existing_columns {
+            if existing_column.name != "id" && !columns.iter().any(|column| column.name == existing_column.name) {
+                destructive_statements.push(serializer.drop_column(db_name.to_string(), &table.name, &existing_column.name));
+            }
+        }
+    }
+
+    for relation in &serializer.relations {
+        if !table_exists(&(relation.owner.clone() + "_" + &relation.name + "_" + &relation.target)) {
+            statements.push(serializer.create_relation_table(db_name.to_string(), relation));
+        }
+    }
+    Migration{version: schema_version(database, enums), statements: statements, destructive_statements: destructive_statements}
+}
+
+// The columns of every table of the database, none when it does not exist yet
+pub fn read_existing_columns(conn: &mut mysql::PooledConn, db_name: &str) -> Result<Vec<ExistingColumn>, GraphqlError> {
+    let query = "SELECT TABLE_NAME, COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE FROM INFORMATION_SCHEMA.COLUMNS \
+                 WHERE TABLE_SCHEMA = ? ORDER BY TABLE_NAME, ORDINAL_POSITION";
+    let mut columns: Vec<ExistingColumn> = Vec::new();
+    for result in try!(conn.prep_exec(query, vec![mysql::Value::from(db_name)])) {
+        let (table_name, name, column_type, is_nullable): (String, String, String, String) = mysql::from_row(try!(result));
+        columns.push(ExistingColumn{table_name: table_name, name: name, column_type: column_type, is_mandatory: is_nullable == "NO"});
+    }
+    Ok(columns)
+}
+
+// Seconds a process waits for another one to finish migrating the same database
+const MIGRATION_LOCK_TIMEOUT: u32 = 60;
+
+// The version of the last migration that was applied completely
+fn read_applied_version(conn: &mut mysql::PooledConn, db_name: &str) -> Result<Option<String>, GraphqlError> {
+    let query = "SELECT version FROM ".to_string() + db_name + "." + HISTORY_TABLE + " WHERE status = 'applied' ORDER BY id DESC LIMIT 1";
+    for result in try!(conn.query(query)) {
+        let mut values: Vec<mysql::Value> = try!(result).unwrap();
+        return Ok(Some(mysql::from_value(values.remove(0))));
+    }
+    Ok(None)
+}
+
+// The migration of the database to the schema, nothing is executed
+pub fn plan(conn: &mut mysql::PooledConn, serializer: &Serializer, db_name: &str, database: &Vec<DbTable>, enums: &Vec<DbEnum>) -> Result<Migration, GraphqlError> {
+    let existing = try!(read_existing_columns(conn, db_name));
+    Ok(plan_migration(serializer, db_name, database, enums, &existing))
+}
+
+// Executes the migration of the database to the schema and records it in the history, along with the version of
+// a schema that needed no statements but was never recorded. The destructive statements are only executed
+// when allow_destructive is set, otherwise they are left out of the migration and logged.
+// The migration is planned and applied holding a named lock, so concurrent processes migrate one after another.
+pub fn migrate(conn: &mut mysql::PooledConn, serializer: &Serializer, db_name: &str, database: &Vec<DbTable>, enums: &Vec<DbEnum>, allow_destructive: bool) -> Result<Migration, GraphqlError> {
+    let lock_name = db_name.to_string() + "." + HISTORY_TABLE;
+    let mut locked: Option<i64> = None;
+    for result in try!(conn.prep_exec("SELECT GET_LOCK(?, ?)", vec![mysql::Value::from(lock_name.as_str()), mysql::Value::from(MIGRATION_LOCK_TIMEOUT as i64)])) {
+        let mut values: Vec<mysql::Value> = try!(result).unwrap();
+        locked = mysql::from_value(values.remove(0));
+    }
+    if locked != Some(1) {
+        return Err(GraphqlError::Sql("Timed out waiting for another migration of ".to_string() + db_name));
+    }
+
+    let migration = migrate_locked(conn, serializer, db_name, database, enums, allow_destructive);
+    try!(conn.prep_exec("SELECT RELEASE_LOCK(?)", vec![mysql::Value::from(lock_name.as_str())]));
+    migration
+}
+
+// The statements are recorded in the history before they are executed, with the number of them applied so far.
+// A migration failing halfway is left as failed with its error, the statements applied before are not undone.
+fn migrate_locked(conn: &mut mysql::PooledConn, serializer: &Serializer, db_name: &str, database: &Vec<DbTable>, enums: &Vec<DbEnum>, allow_destructive: bool) -> Result<Migration, GraphqlError> {
+    try!(conn.query(serializer.create_history_table(db_name.to_string())));
+    let mut migration = try!(plan(conn, serializer, db_name, database, enums));
+    if !allow_destructive {
+        for statement in &migration.destructive_statements {
+            warn!("Skipped destructive migration statement: {}", statement);
+        }
+        migration.destructive_statements.clear();
+    }
+
+    let mut applied: Vec<String> = migration.statements.clone();
+    applied.extend(migration.destructive_statements.iter().cloned());
+    let applied_version = try!(read_applied_version(conn, db_name));
+    if applied.is_empty() && applied_version.as_ref() == Some(&migration.version) {
+        return Ok(migration);
+    }
+
+    let history = db_name.to_string() + "." + HISTORY_TABLE;
+    let record = "INSERT INTO ".to_string() + &history + " (version, schema_definition, statements, status) VALUES (?, ?, ?, 'running')";
+    let id = try!(conn.prep_exec(record, vec![
+        mysql::Value::from(migration.version.as_str()),
+        mysql::Value::from(canonical_schema(database, enums)),
+        mysql::Value::from(applied.join("\n"))
+    ])).last_insert_id();
+    for (i, statement) in applied.iter().enumerate() {
+        if let Err(err) = conn.query(statement.as_str()) {
+            let err = GraphqlError::from(err);
+            try!(conn.prep_exec(
+                "UPDATE ".to_string() + &history + " SET status = 'failed', error = ? WHERE id = ?",
+                vec![mysql::Value::from(err.to_string()), mysql::Value::from(id)]
+            ));
+            return Err(GraphqlError::Sql(
+                "Migration to version ".to_string() + &migration.version + " failed after " + &i.to_string() + " of "
+                    + &applied.len().to_string() + " statements, at " + statement + ": " + &err.to_string()
+            ));
+        }
+        try!(conn.prep_exec(
+            "UPDATE ".to_string() + &history + " SET applied_statements = ? WHERE id = ?",
+            vec![mysql::Value::from((i + 1) as i64), mysql::Value::from(id)]
+        ));
+    }
+    try!(conn.prep_exec("UPDATE ".to_string() + &history + " SET status = 'applied' WHERE id = ?", vec![mysql::Value::from(id)]));
+    Ok(migration)
+}
+
+#[test]
+fn test_plan_migration(){
+    let database = vec![
+        DbTable{name: "Warrior".to_string(), columns: vec![
+            column("name", "TEXT(2048)", true), column("race", "Race", true), column("strength", "INT", false), column("wears", "[Weapon]", false)
+        ]},
+        DbTable{name: "Weapon".to_string(), columns: vec![column("name", "TEXT(2048)", true)]}
+    ];
+    let enums = vec![DbEnum{name: "Race".to_string(), values: vec!["Elf".to_string(), "Orc".to_string()]}];
+    let mut serializer = Serializer::new();
+    serializer.store_enums(&mut enums.clone());
+    serializer.store_relations(&mut vec![Relation{name: "wears".to_string(), owner: "Warrior".to_string(), target: "Weapon".to_string()}]);
+
+    let up_to_date = vec![
+        existing_column("schema_migrations", "id", "int(11)", true),
+        existing_column("Warrior", "id", "int(11)", true),
+        existing_column("Warrior", "name", "text", true),
+        existing_column("Warrior", "race", "enum('Elf','Orc')", true),
+        existing_column("Warrior", "strength", "int(11)", false),
+        existing_column("Warrior_wears_Weapon", "origin_id", "int(11)", false),
+        existing_column("Weapon", "id", "int", true),
+        existing_column("Weapon", "name", "text", true)
+    ];
+    let migration = plan_migration(&serializer, "lotr_db", &database, &enums, &up_to_date);
+    assert_eq!(migration.statements, Vec::<String>::new());
+    assert_eq!(migration.destructive_statements, Vec::<String>::new());
+
+    let changed = vec![
+        existing_column("schema_migrations", "id", "int(11)", true),
+        existing_column("Warrior", "id", "int(11)", true),
+        existing_column("Warrior", "name", "text", false),
+        existing_column("Warrior", "race", "enum('Elf')", true),
+        existing_column("Warrior", "height", "int(11)", false),
+        existing_column("Weapon", "id", "int(11)", true),
+        existing_column("Weapon", "name", "text", true)
+    ];
+    let migration = plan_migration(&serializer, "lotr_db", &database, &enums, &changed);
+    assert_eq!(
+        migration.statements,
+        vec![
+            "ALTER TABLE lotr_db.Warrior MODIFY COLUMN race ENUM('Elf','Orc') NOT NULL;".to_string(),
+            "ALTER TABLE lotr_db.Warrior ADD COLUMN strength INT;".to_string(),
+            serializer.create_relation_table("lotr_db".to_string(), &serializer.relations[0])
+        ]
+    );
+    assert_eq!(
+        migration.destructive_statements,
+        vec![
+            "ALTER TABLE lotr_db.Warrior MODIFY COLUMN name TEXT(2048) NOT NULL;".to_string(),
+            "ALTER TABLE lotr_db.Warrior DROP COLUMN height;".to_string()
+        ]
+    );
+
+    let narrowed = vec![
+        existing_column("schema_migrations", "id", "int(11)", true),
+        existing_column("Warrior", "name", "text", true),
+        existing_column("Warrior", "race", "enum('Elf','Orc','Uruk')", true),
+        existing_column("Warrior", "strength", "text", false),
+        existing_column("Warrior_wears_Weapon", "origin_id", "int(11)", false),
+        existing_column("Weapon", "name", "text", true)
+    ];
+    let migration = plan_migration(&serializer, "lotr_db", &database, &enums, &narrowed);
+    assert_eq!(migration.statements, Vec::<String>::new());
+    assert_eq!(
+        migration.destructive_statements,
+        vec![
+            "ALTER TABLE lotr_db.Warrior MODIFY COLUMN race ENUM('Elf','Orc') NOT NULL;".to_string(),
+            "ALTER TABLE lotr_db.Warrior MODIFY COLUMN strength INT;".to_string()
+        ]
+    );
+
+    assert_eq!(
+        plan_migration(&serializer, "lotr_db", &database, &enums, &vec![]).statements,
+        vec![
+            serializer.create_history_table("lotr_db".to_string()),
+            serializer.create_table("lotr_db".to_string(), &database[0]).0,
+            serializer.create_table("lotr_db".to_string(), &database[1]).0,
+            serializer.create_relation_table("lotr_db".to_string(), &serializer.relations[0])
+        ]
+    );
+
+    assert_eq!(schema_version(&database, &enums).len(), 16);
+    assert_eq!(schema_version(&database, &enums), schema_version(&database.clone(), &enums.clone()));
+    assert!(schema_version(&database, &enums) != schema_version(&database[..1].to_vec(), &enums));
+}
@@ -1,16 +1,124 @@
 use nom::{space, alphanumeric, multispace};
-use nom::IResult;
+use nom::{IResult, Err};
 
 use std::str;
 use std::vec::Vec;
 
 use def::*;
+use error::GraphqlError;
+#[cfg(test)]
+use test_support::{field, object};
 
-named!(parse_param <&[u8],(String,String)>,
+fn is_argument_name_char(c: u8) -> bool {
+    (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || (c >= b'0' && c <= b'9') || c == b'_'
+}
+
+// The parsers record the positions as the length of the input left at a token, which does not depend on what comes
+// before it. The public parsers turn them into offsets from the start of their input with the set_positions functions.
+fn remaining_length(input: &[u8]) -> IResult<&[u8], usize> {
+    IResult::Done(input, input.len())
+}
+
+fn set_param_positions(param_positions: &mut Vec<(usize, usize)>, length: usize) {
+    for position in param_positions.iter_mut() {
+        *position = (length - position.0, length - position.1);
+    }
+}
+
+fn set_query_positions(query: &mut QueryObject, length: usize) {
+    query.position = length - query.position;
+    set_param_positions(&mut query.param_positions, length);
+    for field in query.attrs.iter_mut().flat_map(|attrs| attrs.iter_mut()) {
+        set_query_positions(field, length);
+    }
+}
+
+fn set_mutation_positions(mutation: &mut MutationObject, length: usize) {
+    mutation.position = length - mutation.position;
+    if mutation.value.is_some() {
+        mutation.value_position = length - mutation.value_position;
+    }
+    set_param_positions(&mut mutation.param_positions, length);
+    for field in mutation.attrs.iter_mut().flat_map(|attrs| attrs.iter_mut()) {
+        set_mutation_positions(field, length);
+    }
+}
+
+fn set_fragment_positions(fragment: &mut Fragment, length: usize) {
+    fragment.position = length - fragment.position;
+    for field in fragment.attrs.iter_mut() {
+        set_query_positions(field, length);
+    }
+}
+
+// An argument value, a variable reference keeps its $ so it can be bound later.
+// Lists and objects of filters are kept as they are written, see the filter module.
+named!(parse_value <&[u8], &str>,
+    map_res!(
+        alt!(
+            recognize!(chain!(char!('$') ~ alphanumeric, ||{()})) |
+            parse_list_value |
+            parse_object_value |
+            alphanumeric |
+            // quoted values hold any character but the quote, like the cursors of connections
+            delimited!(
+                char!('\"'),
+                is_not!("\""),
+                char!('\"')
+            )
+        ),
+        str::from_utf8
+    )
+);
+
+named!(parse_list_value <&[u8], &[u8]>,
+    recognize!(parse_list_items)
+);
+
+named!(parse_object_value <&[u8], &[u8]>,
+    recognize!(parse_object_fields)
+);
+
+// [Elf, Orc] or [{race: Elf} {strength_gt: 60}], the commas are optional
+named!(pub parse_list_items <&[u8], Vec<&str> >,
+    delimited!(
+        char!('['),
+        many0!(chain!(
+            multispace?                  ~
+            item: parse_value            ~
+            multispace?                  ~
+            char!(',')?                  ~
+            multispace?,
+            ||{item}
+        )),
+        char!(']')
+    )
+);
+
+// {race: Elf strength_gt: 60}
+named!(pub parse_object_fields <&[u8], Vec<(String, String)> >,
+    delimited!(
+        char!('{'),
+        many0!(chain!(
+            multispace?                  ~
+            field: parse_param,
+            ||{field}
+        )),
+        chain!(
+            multispace?                  ~
+            char!('}'),
+            ||{()}
+        )
+    )
+);
+
+// An argument with the length of the input left at its name and at its value, see set_positions
+named!(parse_argument <&[u8],(String, String, usize, usize)>,
   chain!(
+    key_remaining: remaining_length        ~
     key: map_res!(
             alt!(
-                alphanumeric |
+                take_while1!(is_argument_name_char) |
                 delimited!(
                     char!('\"'),
                     alphanumeric,
@@ -22,22 +130,37 @@ named!(parse_param <&[u8],(String,String)>,
          space?                            ~
          tag!(":")                         ~
          space?                            ~
-    val: map_res!(
-            alt!(
-                alphanumeric |
-                delimited!(
-                    char!('\"'),
-                    alphanumeric,
-                    char!('\"')
-                )
-            ),
-            str::from_utf8
-         )                                 ~
+    value_remaining: remaining_length      ~
+    val: parse_value                       ~
          multispace?                       ,
-    ||{(key.to_string(), val.to_string())}
+    ||{(key.to_string(), val.to_string(), key_remaining, value_remaining)}
   )
 );
 
+named!(parse_param <&[u8],(String,String)>,
+    map!(parse_argument, |(key, value, _, _)| (key, value))
+);
+
+// The arguments of a field in parentheses, with their positions apart
+named!(parse_arguments <&[u8], (Vec<(String, String)>, Vec<(usize, usize)>)>,
+    map!(
+        delimited!(
+            char!('('),
+            many0!(chain!(
+                multispace?              ~
+                argument: parse_argument ~
+                multispace?,
+                ||{argument}
+            )),
+            char!(')')
+        ),
+        |arguments: Vec<(String, String, usize, usize)>| {
+            let positions = arguments.iter().map(|&(_, _, key_position, value_position)| (key_position, value_position)).collect();
+            (arguments.into_iter().map(|(key, value, _, _)| (key, value)).collect(), positions)
+        }
+    )
+);
+
 named!(parse_field <&[u8],(String,String, bool)>,
   chain!(
     key: map_res!(alphanumeric, str::from_utf8) ~
@@ -78,21 +201,79 @@ named!(parse_object <(String, Vec<(String, String, bool)>)>,
     )
 );
 
-named! (pub parse_all_objects <&[u8], Vec <(String, Vec<(String, String, bool)>)> >,
+named!(parse_enum <(String, Vec<String>)>,
+    chain!(
+        tag!("enum")                         ~
+        space                                ~
+        name: map_res!(alphanumeric, str::from_utf8) ~
+        multispace?                          ~
+        values: delimited!(
+            char!('{'),
+            many0!(chain!(
+                multispace?                  ~
+                value: map_res!(alphanumeric, str::from_utf8) ~
+                multispace?,
+                ||{value.to_string()}
+            )),
+            char!('}')
+        ),
+        || {(name.to_string(), values)}
+    )
+);
+
+named!(parse_definition <SchemaDefinition>,
+    alt!(
+        chain!(
+            object: parse_object,
+            ||{SchemaDefinition::Type(object.0, object.1)}
+        ) |
+        chain!(
+            enumeration: parse_enum,
+            ||{SchemaDefinition::Enum(enumeration.0, enumeration.1)}
+        )
+    )
+);
+
+named! (pub parse_all_objects <&[u8], Vec <SchemaDefinition> >,
     many0!(chain!(
         multispace?                          ~
-        result: parse_object                 ~
+        result: parse_definition             ~
         multispace?,
         ||{result}
     ))
 );
 
+// alias: in front of a field renames it in the response
+named! (parse_alias <&[u8], &str>,
+    chain!(
+        alias: map_res!(alphanumeric, str::from_utf8) ~
+        space?                           ~
+        tag!(":")                        ~
+        multispace?,
+        ||{alias}
+    )
+);
+
+// avg(strength) in the selection of an aggregate, kept whole as the name of the field
+named! (parse_aggregate_function <&[u8], &[u8]>,
+    recognize!(chain!(
+        take_while1!(is_argument_name_char)  ~
+        char!('(')                           ~
+        take_while1!(is_argument_name_char)  ~
+        char!(')'),
+        ||{()}
+    ))
+);
+
 named! (parse_query_object <&[u8], QueryObject>,
     chain!(
         multispace?                      ~
+        alias: parse_alias?              ~
+        remaining: remaining_length      ~
         object: map_res!(
                     alt!(
-                        alphanumeric |
+                        parse_aggregate_function |
+                        take_while1!(is_argument_name_char) |
                         delimited!(
                             char!('\"'),
                             take_until_either!("\""),
@@ -102,33 +283,81 @@ named! (parse_query_object <&[u8], QueryObject>,
                     str::from_utf8
                 )                        ~
         space?                           ~
-        params: delimited!(
-            char!('('),
-            many0!(chain!(
-                multispace?              ~
-                param: parse_param       ~
-                multispace?,
-                ||{param}
-            )),
-            char!(')')
-        )?                               ~
+        arguments: parse_arguments?      ~
         space?                           ~
-        attributes: delimited!(
-            char!('{'),
-            many0!(chain!(
+        attributes: parse_selection_set? ~
+        multispace?,
+        ||{
+            let (params, param_positions) = match arguments {
+                Some((params, positions)) => (Some(params), positions),
+                None => (None, Vec::new())
+            };
+            QueryObject{name: object.to_string(), alias: alias.map(|alias| alias.to_string()), params: params, attrs: attributes, position: remaining, param_positions: param_positions}
+        }
+    )
+);
+
+named! (parse_selection_set <&[u8], Vec<QueryObject> >,
+    delimited!(
+        char!('{'),
+        many0!(chain!(
+            multispace?                  ~
+            attr: alt!(
+                parse_fragment_selection |
+                parse_query_object        //recursion
+            )                            ~
+            multispace?,
+            ||{attr}
+        )),
+        char!('}')
+    )
+);
+
+// ...Name spreads a fragment, ... on Type { } is an inline fragment
+named! (parse_fragment_selection <&[u8], QueryObject>,
+    chain!(
+        tag!("...")                      ~
+        multispace?                      ~
+        res: alt!(
+            chain!(
+                tag!("on")               ~
+                multispace               ~
+                remaining: remaining_length ~
+                on: map_res!(alphanumeric, str::from_utf8) ~
                 multispace?              ~
-                attr: parse_query_object ~ //recursion
-                multispace?,
-                ||{attr}
-            )),
-            char!('}')
-        )?                                ~
+                attrs: parse_selection_set,
+                ||{QueryObject{name: INLINE_FRAGMENT.to_string() + on, alias: None, params: None, attrs: Some(attrs), position: remaining, param_positions: Vec::new()}}
+            ) |
+            chain!(
+                remaining: remaining_length ~
+                name: map_res!(alphanumeric, str::from_utf8),
+                ||{QueryObject{name: FRAGMENT_SPREAD.to_string() + name, alias: None, params: None, attrs: None, position: remaining, param_positions: Vec::new()}}
+            )
+        ),
+        ||{res}
+    )
+);
+
+// fragment Name on Type { ... }, the position of its type condition is set by set_fragment_positions
+named! (parse_fragment_definition <&[u8], Fragment>,
+    chain!(
+        multispace?                      ~
+        tag!("fragment")                 ~
+        multispace                       ~
+        name: map_res!(alphanumeric, str::from_utf8) ~
+        multispace                       ~
+        tag!("on")                       ~
+        multispace                       ~
+        remaining: remaining_length      ~
+        on: map_res!(alphanumeric, str::from_utf8) ~
+        multispace?                      ~
+        attrs: parse_selection_set       ~
         multispace?,
-        ||{QueryObject{name: object.to_string(), params: params, attrs: attributes}}
+        ||{Fragment{name: name.to_string(), on: on.to_string(), attrs: attrs, position: remaining}}
     )
 );
 
-named! (pub parse_query <&[u8], QueryObject>,
+named! (parse_root_query <&[u8], QueryObject>,
     chain!(
         multispace?                              ~
         res: delimited!(
@@ -141,9 +370,173 @@ named! (pub parse_query <&[u8], QueryObject>,
     )
 );
 
+named! (pub parse_query <&[u8], QueryObject>,
+    chain!(
+        length: remaining_length         ~
+        query: parse_root_query,
+        ||{
+            let mut query = query;
+            set_query_positions(&mut query, length);
+            query
+        }
+    )
+);
+
+// $id: Int! = 3
+named! (parse_variable_definition <&[u8], VariableDefinition>,
+    chain!(
+        multispace?                      ~
+        char!('$')                       ~
+        name: map_res!(alphanumeric, str::from_utf8) ~
+        space?                           ~
+        tag!(":")                        ~
+        space?                           ~
+        var_type: map_res!(alphanumeric, str::from_utf8) ~
+        mandatory: tag!("!")?            ~
+        space?                           ~
+        default: chain!(
+            tag!("=")                    ~
+            space?                       ~
+            res: parse_value,
+            ||{res.to_string()}
+        )?                               ~
+        multispace?                      ~
+        char!(',')?                      ~
+        multispace?,
+        ||{VariableDefinition{name: name.to_string(), var_type: var_type.to_string(), is_mandatory: mandatory.is_some(), default: default}}
+    )
+);
+
+// query Name($id: Int!) { ... } or the anonymous { ... }
+named! (parse_operation_definition <&[u8], QueryOperation>,
+    alt!(
+        chain!(
+            multispace?                  ~
+            tag!("query")                ~
+            multispace?                  ~
+            name: map_res!(alphanumeric, str::from_utf8)? ~
+            multispace?                  ~
+            variables: delimited!(
+                char!('('),
+                many0!(parse_variable_definition),
+                char!(')')
+            )?                           ~
+            query: parse_root_query,
+            ||{QueryOperation{name: name.map(|name| name.to_string()), variables: variables.unwrap_or(Vec::new()), query: query, fragments: Vec::new()}}
+        ) |
+        chain!(
+            query: parse_root_query,
+            ||{QueryOperation{name: None, variables: Vec::new(), query: query, fragments: Vec::new()}}
+        )
+    )
+);
+
+// A document with one operation and the fragments it uses, before or after it
+named! (pub parse_operation <&[u8], QueryOperation>,
+    chain!(
+        length: remaining_length         ~
+        before: many0!(parse_fragment_definition) ~
+        operation: parse_operation_definition ~
+        after: many0!(parse_fragment_definition),
+        ||{
+            let mut operation = operation;
+            set_query_positions(&mut operation.query, length);
+            for mut fragment in before.into_iter().chain(after.into_iter()) {
+                set_fragment_positions(&mut fragment, length);
+                operation.fragments.push(fragment);
+            }
+            operation
+        }
+    )
+);
+
+// mutation Name { update { Weapon (name: Bow) { name: Sword } } }
+named! (parse_mutation_definition <&[u8], MutationOperation>,
+    chain!(
+        multispace?                      ~
+        tag!("mutation")                 ~
+        multispace?                      ~
+        name: map_res!(alphanumeric, str::from_utf8)? ~
+        multispace?                      ~
+        char!('{')                       ~
+        multispace?                      ~
+        kind: map_res!(alt!(tag!("add") | tag!("update") | tag!("delete")), str::from_utf8) ~
+        mutation: parse_root_mutation    ~
+        char!('}')                       ~
+        multispace?,
+        ||{MutationOperation{name: name.map(|name| name.to_string()), kind: kind.to_string(), mutation: mutation}}
+    )
+);
+
+// An operation or a fragment of a document
+enum ExecutableDefinition {
+    Operation(Operation),
+    Fragment(Fragment)
+}
+
+// A document with any number of operations and fragments, in any order
+named! (parse_document <&[u8], (usize, Vec<ExecutableDefinition>)>,
+    chain!(
+        length: remaining_length         ~
+        definitions: many1!(alt!(
+            map!(parse_fragment_definition, ExecutableDefinition::Fragment) |
+            map!(parse_mutation_definition, |mutation| ExecutableDefinition::Operation(Operation::Mutation(mutation))) |
+            map!(parse_operation_definition, |query| ExecutableDefinition::Operation(Operation::Query(query)))
+        )),
+        ||{(length, definitions)}
+    )
+);
+
+// Parses a document and selects the operation to execute, a query gets the fragments of the document.
+// The operation named operation_name is selected, without a name the document must have a single operation.
+pub fn parse_operation_named(input: &[u8], operation_name: Option<&str>) -> Result<Operation, GraphqlError> {
+    try!(check_depth(input));
+    let (length, definitions) = try!(into_result(input, parse_document(input)));
+    let mut operations: Vec<Operation> = Vec::new();
+    let mut fragments: Vec<Fragment> = Vec::new();
+    for definition in definitions {
+        match definition {
+            ExecutableDefinition::Operation(Operation::Query(mut query)) => {
+                set_query_positions(&mut query.query, length);
+                operations.push(Operation::Query(query));
+            },
+            ExecutableDefinition::Operation(Operation::Mutation(mut mutation)) => {
+                set_mutation_positions(&mut mutation.mutation, length);
+                operations.push(Operation::Mutation(mutation));
+            },
+            ExecutableDefinition::Fragment(mut fragment) => {
+                set_fragment_positions(&mut fragment, length);
+                fragments.push(fragment);
+            }
+        }
+    }
+    let mut operation = match operation_name {
+        Some(name) => try!(operations.into_iter().find(|operation| operation_name_of(operation) == Some(name))
+            .ok_or(GraphqlError::Validation("Unknown operation named \"".to_string() + name + "\""))),
+        None => {
+            if operations.len() != 1 {
+                return Err(GraphqlError::Validation("The document has ".to_string() + &operations.len().to_string() + " operations, an operation name must be provided"));
+            }
+            operations.remove(0)
+        }
+    };
+    if let Operation::Query(ref mut query) = operation {
+        query.fragments = fragments;
+    }
+    Ok(operation)
+}
+
+fn operation_name_of(operation: &Operation) -> Option<&str> {
+    match *operation {
+        Operation::Query(ref query) => query.name.as_ref().map(|name| name.as_str()),
+        Operation::Mutation(ref mutation) => mutation.name.as_ref().map(|name| name.as_str())
+    }
+}
+
 named! (parse_mutation_object <&[u8], MutationObject>,
     chain!(
         multispace?                      ~
+        remaining: remaining_length      ~
         name: map_res!(
             alt!(
                 alphanumeric |
@@ -159,6 +552,7 @@ named! (parse_mutation_object <&[u8], MutationObject>,
         value: chain! (
             tag!(":")                    ~
             space?                       ~
+            value_remaining: remaining_length ~
             res: map_res!(
                 alt!(
                     alphanumeric |
@@ -170,18 +564,9 @@ named! (parse_mutation_object <&[u8], MutationObject>,
                 ),
                 str::from_utf8
             ),
-            ||{res.to_string()}
-        )?                               ~
-        params: delimited!(
-            char!('('),
-            many0!(chain!(
-                multispace?              ~
-                res: parse_param         ~
-                multispace?,
-                ||{res}
-            )),
-            char!(')')
+            ||{(res.to_string(), value_remaining)}
         )?                               ~
+        arguments: parse_arguments?      ~
         space?                           ~
         attributes: delimited!(
             char!('{'),
@@ -194,11 +579,21 @@ named! (parse_mutation_object <&[u8], MutationObject>,
             char!('}')
         )?                               ~
         multispace?,
-        ||{MutationObject{name: name.to_string(), value: value, params: params, attrs: attributes}}
+        ||{
+            let (value, value_position) = match value {
+                Some((value, position)) => (Some(value), position),
+                None => (None, 0)
+            };
+            let (params, param_positions) = match arguments {
+                Some((params, positions)) => (Some(params), positions),
+                None => (None, Vec::new())
+            };
+            MutationObject{name: name.to_string(), value: value, params: params, attrs: attributes, position: remaining, value_position: value_position, param_positions: param_positions}
+        }
     )
 );
 
-named! (pub parse_mutation_query <&[u8], MutationObject>,
+named! (parse_root_mutation <&[u8], MutationObject>,
     chain!(
         multispace?                              ~
         res: delimited!(
@@ -211,6 +606,73 @@ named! (pub parse_mutation_query <&[u8], MutationObject>,
     )
 );
 
+named! (pub parse_mutation_query <&[u8], MutationObject>,
+    chain!(
+        length: remaining_length         ~
+        mutation: parse_root_mutation,
+        ||{
+            let mut mutation = mutation;
+            set_mutation_positions(&mut mutation, length);
+            mutation
+        }
+    )
+);
+
+// Parses a mutation of the add, update and delete requests
+pub fn parse_mutation(input: &[u8]) -> Result<MutationObject, GraphqlError> {
+    try!(check_depth(input));
+    into_result(input, parse_mutation_query(input))
+}
+
+// Selections, lists and arguments are parsed recursively, deeper nesting is rejected before it can overflow the stack
+pub const MAX_DEPTH: usize = 64;
+
+// Scans the brackets of a document outside of its strings, before the recursive descent of the parsers
+fn check_depth(input: &[u8]) -> Result<(), GraphqlError> {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (position, &byte) in input.iter().enumerate() {
+        match byte {
+            b'"' => in_string = !in_string,
+            b'{' | b'[' | b'(' if !in_string => {
+                if depth == MAX_DEPTH {
+                    return Err(GraphqlError::Parse{position: position, message: "too deeply nested".to_string()});
+                }
+                depth += 1;
+            },
+            b'}' | b']' | b')' if !in_string && depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn error_position(input: &[u8], err: &Err<&[u8]>) -> usize {
+    match *err {
+        Err::Position(_, remaining) | Err::NodePosition(_, remaining, _) => input.len() - remaining.len(),
+        Err::Node(_, ref next) => error_position(input, next),
+        Err::Code(_) => 0
+    }
+}
+
+fn syntax_error(input: &[u8], position: usize) -> GraphqlError {
+    let near: String = String::from_utf8_lossy(&input[position..]).chars().take(20).collect();
+    GraphqlError::Parse{
+        position: position,
+        message: if near.is_empty() {"unexpected end of input".to_string()} else {"unexpected input near \"".to_string() + near.trim() + "\""}
+    }
+}
+
+// Turns the result of one of the public parsers into a Result, requiring the whole input to be consumed
+pub fn into_result<T>(input: &[u8], result: IResult<&[u8], T>) -> Result<T, GraphqlError> {
+    match result {
+        IResult::Done(remaining, res) => {
+            if remaining.is_empty() {Ok(res)} else {Err(syntax_error(input, input.len() - remaining.len()))}
+        },
+        IResult::Error(err) => Err(syntax_error(input, error_position(input, &err))),
+        IResult::Incomplete(_) => Err(syntax_error(input, input.len()))
+    }
+}
 
 #[test]
 fn test_internal_parser_functions(){
@@ -276,6 +738,34 @@ fn test_internal_parser_functions(){
     );
 }
 
+#[test]
+fn test_enum_parser_function(){
+    assert_eq!(
+        parse_enum(&b"enum Race {
+                    ELF
+                    HUMAN ORC
+                }"[..]),
+        IResult::Done(&b""[..], ("Race".to_string(), vec!["ELF".to_string(), "HUMAN".to_string(), "ORC".to_string()]))
+    );
+
+    assert_eq!(
+        parse_all_objects(&b"enum Race { ELF HUMAN }
+
+                type Warrior {
+                    name: String!
+                    race: Race
+                }
+                "[..]),
+        IResult::Done(&b""[..], vec![
+            SchemaDefinition::Enum("Race".to_string(), vec!["ELF".to_string(), "HUMAN".to_string()]),
+            SchemaDefinition::Type("Warrior".to_string(), vec![
+                ("name".to_string(), "String".to_string(), true),
+                ("race".to_string(), "Race".to_string(), false)
+            ])
+        ])
+    );
+}
+
 #[test]
 fn test_get_parser_function(){
     let get_query =
@@ -288,19 +778,28 @@ fn test_get_parser_function(){
     let get_query_data = IResult::Done(&b""[..],
         {QueryObject {
             name:"user".to_string(),
+            alias: None,
             params: Some(vec![{("id".to_string(), "1".to_string())}]),
             attrs: Some(vec![
                 QueryObject {
                     name: "name".to_string(),
+                    alias: None,
                     params: None,
-                    attrs: None
+                    attrs: None,
+                    position: 0,
+                    param_positions: Vec::new()
                 },
                 QueryObject {
                     name: "phone".to_string(),
+                    alias: None,
                     params: None,
-                    attrs: None
+                    attrs: None,
+                    position: 0,
+                    param_positions: Vec::new()
                 }
-            ])
+            ]),
+            position: 0,
+            param_positions: Vec::new()
         }}
     );
 
@@ -320,30 +819,45 @@ fn test_get_parser_function(){
     let get_query_data = IResult::Done(&b""[..],
                                        {QueryObject {
                                            name:"user".to_string(),
+                                           alias: None,
                                            params: Some(vec![{("id".to_string(), "1".to_string())}]),
                                            attrs: Some(vec![
                                                QueryObject {
                                                     name: "name".to_string(),
+                                                    alias: None,
                                                     params: None,
-                                                    attrs: None
+                                                    attrs: None,
+                                                    position: 0,
+                                                    param_positions: Vec::new()
                                                },
                                                QueryObject {
                                                     name: "friends".to_string(),
+                                                    alias: None,
                                                     params: None,
                                                     attrs: Some(vec![
                                                         QueryObject {
                                                             name: "id".to_string(),
+                                                            alias: None,
                                                             params: None,
-                                                            attrs: None
+                                                            attrs: None,
+                                                            position: 0,
+                                                            param_positions: Vec::new()
                                                         },
                                                         QueryObject {
                                                             name: "name".to_string(),
+                                                            alias: None,
                                                             params: None,
-                                                            attrs: None
+                                                            attrs: None,
+                                                            position: 0,
+                                                            param_positions: Vec::new()
                                                         }
-                                                    ])
+                                                    ]),
+                                                    position: 0,
+                                                    param_positions: Vec::new()
                                                 }
-                                            ])
+                                            ]),
+                                           position: 0,
+                                           param_positions: Vec::new()
                                        }}
     );
     assert_eq!(parse_query(get_query), get_query_data);
@@ -368,21 +882,33 @@ fn test_insert_parser_function(){
                             name: "id".to_string(),
                             value: Some("1".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "name".to_string(),
                             value: Some("Luke".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "homePlanet".to_string(),
                             value: Some("Char".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         }
-                    ])
+                    ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(insert_query), insert_query_data);
     insert_query =
@@ -403,27 +929,42 @@ fn test_insert_parser_function(){
                             name: "id".to_string(),
                             value: Some("1".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "name".to_string(),
                             value: Some("R2D2".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "age".to_string(),
                             value: Some("3".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "primaryFunction".to_string(),
                             value: Some("Mechanic".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         }
-                    ])
+                    ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(insert_query), insert_query_data);
 
@@ -455,13 +996,19 @@ fn test_insert_parser_function(){
                             name: "id".to_string(),
                             value: Some("1".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "name".to_string(),
                             value: Some("Luke".to_string()),
                             params: None,
-                            attrs: None
+                            attrs: None,
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         },
                         MutationObject {
                             name: "friends".to_string(),
@@ -472,17 +1019,29 @@ fn test_insert_parser_function(){
                                     name: "Human".to_string(),
                                     value: None,
                                     params: Some(vec![("id".to_string(), "2".to_string()), ("name".to_string(), "Leia".to_string())]),
-                                    attrs: None
+                                    attrs: None,
+                                    position: 0,
+                                    value_position: 0,
+                                    param_positions: Vec::new()
                                 },
                                 MutationObject {
                                     name: "Human".to_string(),
                                     value: None,
                                     params: Some(vec![("id".to_string(), "3".to_string()), ("name".to_string(), "Han".to_string())]),
-                                    attrs: None
+                                    attrs: None,
+                                    position: 0,
+                                    value_position: 0,
+                                    param_positions: Vec::new()
                                 }
-                            ])
+                            ]),
+                            position: 0,
+                            value_position: 0,
+                            param_positions: Vec::new()
                         }
-                    ])
+                    ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(insert_query), insert_query_data);
 
@@ -505,9 +1064,15 @@ fn test_update_parser_function(){
                     name: "age".to_string(),
                     value: Some("4".to_string()),
                     params: None,
-                    attrs: None
+                    attrs: None,
+                    position: 0,
+                    value_position: 0,
+                    param_positions: Vec::new()
                 }
-            ])
+            ]),
+            position: 0,
+            value_position: 0,
+            param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(update_query), update_query_data);
 }
@@ -522,7 +1087,10 @@ fn test_delete_parser_function(){
         name: "user".to_string(),
         value: None,
         params: Some(vec![{("id".to_string(), ("1".to_string()))}]),
-        attrs: None
+        attrs: None,
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(delete_query), delete_query_data);
 
@@ -534,7 +1102,310 @@ fn test_delete_parser_function(){
         name: "user".to_string(),
         value: None,
         params: None,
-        attrs: None
+        attrs: None,
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
     }});
     assert_eq!(parse_mutation_query(delete_query), delete_query_data);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parser_errors(){
+    let query = &b"{ Warrior (id: 8) { name }"[..];
+    assert_eq!(
+        into_result(query, parse_query(query)),
+        Err(GraphqlError::Parse{position: 26, message: "unexpected end of input".to_string()})
+    );
+
+    let query = &b"{ Warrior { name } } }"[..];
+    assert_eq!(
+        into_result(query, parse_query(query)),
+        Err(GraphqlError::Parse{position: 21, message: "unexpected input near \"}\"".to_string()})
+    );
+}
+
+#[test]
+fn test_operation_parser(){
+    let query = &b"query Warriors($race: String = Elf, $strength: Int!) {
+        Warrior (race: $race strength: $strength) {
+            name
+        }
+    }"[..];
+    assert_eq!(
+        into_result(query, parse_operation(query)),
+        Ok(QueryOperation {
+            fragments: vec![],
+            name: Some("Warriors".to_string()),
+            variables: vec![
+                VariableDefinition{name: "race".to_string(), var_type: "String".to_string(), is_mandatory: false, default: Some("Elf".to_string())},
+                VariableDefinition{name: "strength".to_string(), var_type: "Int".to_string(), is_mandatory: true, default: None}
+            ],
+            query: QueryObject {
+                name: "Warrior".to_string(),
+                alias: None,
+                params: Some(vec![("race".to_string(), "$race".to_string()), ("strength".to_string(), "$strength".to_string())]),
+                attrs: Some(vec![field("name")]),
+                position: 0,
+                param_positions: Vec::new()
+            }
+        })
+    );
+
+    let query = &b"{ Weapon { name } }"[..];
+    assert_eq!(
+        into_result(query, parse_operation(query)),
+        Ok(QueryOperation {
+            fragments: vec![],
+            name: None,
+            variables: vec![],
+            query: QueryObject {
+                name: "Weapon".to_string(),
+                alias: None,
+                params: None,
+                attrs: Some(vec![field("name")]),
+                position: 0,
+                param_positions: Vec::new()
+            }
+        })
+    );
+
+    let query = &b"query { Weapon { name } }"[..];
+    assert_eq!(into_result(query, parse_operation(query)).map(|operation| operation.name), Ok(None));
+}
+
+#[test]
+fn test_positions(){
+    let query = parse_query(&b"{ Warrior (race: Elf) { elf: name } }"[..]);
+    match query {
+        IResult::Done(_, query) => {
+            assert_eq!((query.position, query.param_positions.clone()), (2, vec![(11, 17)]));
+            assert_eq!(query.attrs.unwrap()[0].position, 29);
+        },
+        _ => panic!("Unexpected result {:?}", query)
+    }
+
+    let mutation = parse_mutation_query(&b"{ Warrior (id: 3) { name: \"Gimli\" } }"[..]);
+    match mutation {
+        IResult::Done(_, mutation) => {
+            assert_eq!((mutation.position, mutation.param_positions.clone()), (2, vec![(11, 15)]));
+            let name = &mutation.attrs.unwrap()[0];
+            assert_eq!((name.position, name.value_position), (20, 26));
+        },
+        _ => panic!("Unexpected result {:?}", mutation)
+    }
+
+    // the positions are offsets in the whole document
+    match parse_operation_named(b"fragment Names on Warrior { name } query Q { Warrior { ...Names } }", Some("Q")) {
+        Ok(Operation::Query(operation)) => {
+            assert_eq!(operation.query.position, 45);
+            assert_eq!(operation.query.attrs.unwrap()[0].position, 58);
+            assert_eq!(operation.fragments[0].position, 18);
+            assert_eq!(operation.fragments[0].attrs[0].position, 28);
+        },
+        result => panic!("Unexpected result {:?}", result)
+    }
+}
+
+#[test]
+fn test_operation_selection(){
+    let query = &b"query Warriors { Warrior { ...Names } }
+                   fragment Names on Warrior { name }
+                   query Weapons { Weapon { name } }"[..];
+    let operation = match parse_operation_named(query, Some("Weapons")) {
+        Ok(Operation::Query(operation)) => operation,
+        result => panic!("Unexpected result {:?}", result)
+    };
+    assert_eq!(operation.name, Some("Weapons".to_string()));
+    assert_eq!(operation.query.name, "Weapon".to_string());
+    assert_eq!(operation.fragments.iter().map(|fragment| (fragment.name.as_str(), fragment.position)).collect::<Vec<_>>(), vec![("Names", 77)]);
+    match parse_operation_named(query, Some("Warriors")) {
+        Ok(Operation::Query(operation)) => assert_eq!(operation.query.name, "Warrior".to_string()),
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert_eq!(
+        parse_operation_named(query, Some("Leaders")),
+        Err(GraphqlError::Validation("Unknown operation named \"Leaders\"".to_string()))
+    );
+    assert_eq!(
+        parse_operation_named(query, None),
+        Err(GraphqlError::Validation("The document has 2 operations, an operation name must be provided".to_string()))
+    );
+    match parse_operation_named(&b"{ Weapon { name } }"[..], None) {
+        Ok(Operation::Query(operation)) => assert_eq!(operation.name, None),
+        result => panic!("Unexpected result {:?}", result)
+    }
+
+    let query = &b"query Weapons { Weapon { name } }
+                   mutation Sharpen { update { Weapon (name: Bow) { sharp: true } } }"[..];
+    assert_eq!(
+        parse_operation_named(query, Some("Sharpen")),
+        Ok(Operation::Mutation(MutationOperation{
+            name: Some("Sharpen".to_string()),
+            kind: "update".to_string(),
+            mutation: MutationObject{
+                name: "Weapon".to_string(),
+                value: None,
+                params: Some(vec![("name".to_string(), "Bow".to_string())]),
+                attrs: Some(vec![MutationObject{name: "sharp".to_string(), value: Some("true".to_string()), params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}]),
+                position: 0,
+                value_position: 0,
+                param_positions: Vec::new()
+            }
+        }))
+    );
+    match parse_operation_named(&b"mutation { add { Weapon { name: Bow } } }"[..], None) {
+        Ok(Operation::Mutation(mutation)) => assert_eq!((mutation.name, mutation.kind), (None, "add".to_string())),
+        result => panic!("Unexpected result {:?}", result)
+    }
+}
+
+#[test]
+fn test_fragment_parser(){
+    let query = &b"{
+        Leader (id: 1) {
+            ...LeaderFields
+            leads {
+                ... on Warrior {
+                    name
+                }
+            }
+        }
+    }
+    fragment LeaderFields on Leader {
+        name
+        wisdom
+    }"[..];
+    assert_eq!(
+        into_result(query, parse_operation(query)),
+        Ok(QueryOperation {
+            name: None,
+            variables: vec![],
+            query: QueryObject {
+                name: "Leader".to_string(),
+                alias: None,
+                params: Some(vec![("id".to_string(), "1".to_string())]),
+                attrs: Some(vec![
+                    field("...LeaderFields"),
+                    object("leads", vec![
+                        object("... on Warrior", vec![field("name")])
+                    ])
+                ]),
+                position: 0,
+                param_positions: Vec::new()
+            },
+            fragments: vec![
+                Fragment {
+                    name: "LeaderFields".to_string(),
+                    on: "Leader".to_string(),
+                    attrs: vec![
+                        field("name"),
+                        field("wisdom")
+                    ],
+                    position: 210
+                }
+            ]
+        })
+    );
+}
+
+#[test]
+fn test_alias_parser(){
+    let get_query =
+    &b"{
+        Leader (id: 1) {
+            title: name
+            elves: leads (race: Elf) {
+                name
+            }
+            orcs : leads (race: Orc) {
+                name
+            }
+        }
+    }"[..];
+    assert_eq!(
+        parse_query(get_query),
+        IResult::Done(&b""[..], QueryObject {
+            name: "Leader".to_string(),
+            alias: None,
+            params: Some(vec![("id".to_string(), "1".to_string())]),
+            attrs: Some(vec![
+                QueryObject{name: "name".to_string(), alias: Some("title".to_string()), params: None, attrs: None, position: 0, param_positions: Vec::new()},
+                QueryObject{
+                    name: "leads".to_string(),
+                    alias: Some("elves".to_string()),
+                    params: Some(vec![("race".to_string(), "Elf".to_string())]),
+                    attrs: Some(vec![field("name")]),
+                    position: 0,
+                    param_positions: Vec::new()
+                },
+                QueryObject{
+                    name: "leads".to_string(),
+                    alias: Some("orcs".to_string()),
+                    params: Some(vec![("race".to_string(), "Orc".to_string())]),
+                    attrs: Some(vec![field("name")]),
+                    position: 0,
+                    param_positions: Vec::new()
+                }
+            ]),
+            position: 0,
+            param_positions: Vec::new()
+        })
+    );
+
+    let get_aggregate_query = &b"{ Warrior_aggregate (groupBy: race) { race count strongest: max(strength) avg(strength) } }"[..];
+    assert_eq!(
+        parse_query(get_aggregate_query),
+        IResult::Done(&b""[..], QueryObject {
+            name: "Warrior_aggregate".to_string(),
+            alias: None,
+            params: Some(vec![("groupBy".to_string(), "race".to_string())]),
+            attrs: Some(vec![
+                field("race"),
+                field("count"),
+                QueryObject{name: "max(strength)".to_string(), alias: Some("strongest".to_string()), params: None, attrs: None, position: 0, param_positions: Vec::new()},
+                field("avg(strength)")
+            ]),
+            position: 0,
+            param_positions: Vec::new()
+        })
+    );
+}
+
+#[test]
+fn test_filter_arguments_parser(){
+    let get_query = &b"{ Warrior (strength_gt: 40 race_in: [Elf, Orc] OR: [{name: orc1} {name_startsWith: elf}]) { name } }"[..];
+    assert_eq!(
+        parse_query(get_query),
+        IResult::Done(&b""[..], QueryObject {
+            name: "Warrior".to_string(),
+            alias: None,
+            params: Some(vec![
+                ("strength_gt".to_string(), "40".to_string()),
+                ("race_in".to_string(), "[Elf, Orc]".to_string()),
+                ("OR".to_string(), "[{name: orc1} {name_startsWith: elf}]".to_string())
+            ]),
+            attrs: Some(vec![field("name")]),
+            position: 0,
+            param_positions: Vec::new()
+        })
+    );
+    assert_eq!(parse_list_items(&b"[Elf, Orc]"[..]), IResult::Done(&b""[..], vec!["Elf", "Orc"]));
+    assert_eq!(parse_list_items(&b"[{name: orc1} {name_startsWith: elf}]"[..]), IResult::Done(&b""[..], vec!["{name: orc1}", "{name_startsWith: elf}"]));
+    assert_eq!(
+        parse_object_fields(&b"{ race: Elf NOT: {strength_lt: 10} }"[..]),
+        IResult::Done(&b""[..], vec![("race".to_string(), "Elf".to_string()), ("NOT".to_string(), "{strength_lt: 10}".to_string())])
+    );
+}
+
+#[test]
+fn test_nesting_depth(){
+    let nested = "{ Warrior ".to_string() + &(1..MAX_DEPTH).map(|_| "{ friends ").collect::<String>() + &(0..MAX_DEPTH).map(|_| "}").collect::<String>();
+    assert!(parse_operation_named(nested.as_bytes(), None).is_ok());
+
+    let too_nested: String = (0..100000).map(|_| "{").collect();
+    assert_eq!(parse_operation_named(too_nested.as_bytes(), None), Err(GraphqlError::Parse{position: MAX_DEPTH, message: "too deeply nested".to_string()}));
+    let too_nested = "{ Warrior (id: 3) { name: ".to_string() + &(0..100000).map(|_| "[").collect::<String>();
+    assert_eq!(parse_mutation(too_nested.as_bytes()).unwrap_err(), GraphqlError::Parse{position: 26 + MAX_DEPTH - 2, message: "too deeply nested".to_string()});
+    assert!(parse_mutation(&b"{ Warrior (id: 3) { name: \"{{{{\" } }"[..]).is_ok());
+}
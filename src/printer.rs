@@ -0,0 +1,74 @@
+use std::vec::Vec;
+
+use def::*;
+use reader;
+
+// leads: [Warrior] or name: String!, with the standard GraphQL scalar of the field, Int for Number
+fn print_field(field: &(String, String, bool)) -> String {
+    let field_type = reader::mysql_to_graphql_type(&reader::graphql_to_mysql_type(&field.1));
+    "  ".to_string() + &field.0 + ": " + &field_type + if field.2 {"!"} else {""}
+}
+
+// The id field every table has comes first, whether the schema declares it or not
+fn print_type(name: &str, fields: &Vec<(String, String, bool)>) -> String {
+    let mut printed = "type ".to_string() + name + " {\n  id: ID!\n";
+    for field in fields {
+        if field.0 != "id" {
+            printed = printed + &print_field(field) + "\n";
+        }
+    }
+    printed + "}\n"
+}
+
+fn print_enum(name: &str, values: &Vec<String>) -> String {
+    let mut printed = "enum ".to_string() + name + " {\n";
+    for value in values {
+        printed = printed + "  " + value + "\n";
+    }
+    printed + "}\n"
+}
+
+// Prints the definitions as SDL in their order, separated by blank lines. Reading the printed schema gives the
+// same tables and enums, reader::into_definitions gives the definitions of a loaded schema.
+pub fn print_schema(definitions: &Vec<SchemaDefinition>) -> String {
+    let printed: Vec<String> = definitions.iter().map(|definition| match *definition {
+        SchemaDefinition::Type(ref name, ref fields) => print_type(name, fields),
+        SchemaDefinition::Enum(ref name, ref values) => print_enum(name, values)
+    }).collect();
+    printed.join("\n")
+}
+
+#[test]
+fn test_print_schema(){
+    let schema = "enum Race { Elf Orc }
+                  type Warrior {
+                    name:String!
+                    race: Race
+                    strength: Number
+                    wears:[Weapon]!
+                  }
+                  type Weapon { id: ID! name: String! sharp: Boolean power: Int }";
+    let definitions = reader::extract_definitions(schema).unwrap();
+    let printed = print_schema(&definitions);
+    assert_eq!(
+        printed,
+        "enum Race {\n  Elf\n  Orc\n}\n\
+         \n\
+         type Warrior {\n  id: ID!\n  name: String!\n  race: Race\n  strength: Int\n  wears: [Weapon]!\n}\n\
+         \n\
+         type Weapon {\n  id: ID!\n  name: String!\n  sharp: Boolean\n  power: Int\n}\n"
+    );
+    assert_eq!(reader::extract_database(&printed), reader::extract_database(schema));
+    assert_eq!(print_schema(&reader::extract_definitions(&printed).unwrap()), printed);
+
+    let (database, enums) = reader::extract_database(schema).unwrap();
+    assert_eq!(
+        print_schema(&reader::into_definitions(&database, &enums)),
+        "type Warrior {\n  id: ID!\n  name: String!\n  race: Race\n  strength: Int\n  wears: [Weapon]!\n}\n\
+         \n\
+         type Weapon {\n  id: ID!\n  name: String!\n  sharp: Boolean\n  power: Int\n}\n\
+         \n\
+         enum Race {\n  Elf\n  Orc\n}\n"
+    );
+    assert_eq!(print_schema(&vec![]), "");
+}
@@ -8,45 +8,127 @@ use std::vec::Vec;
 use std::str;
 use std::io::prelude::*;
 
-use nom::IResult;
+use error::GraphqlError;
+#[cfg(test)]
+use test_support::column;
 
-fn graphql_to_mysql_type (attr_type: String) -> String {
-    match attr_type.as_str(){
-        "Number" => "INT".to_string(),
+// Number is the former name of Int, schema files using it still load
+pub fn graphql_to_mysql_type (attr_type: &str) -> String {
+    match attr_type {
+        "Int" | "Number" => "INT".to_string(),
         "String" => "TEXT(2048)".to_string(),
         "Boolean" => "BOOLEAN".to_string(),
         _ => attr_type.to_string()
     }
 }
 
-pub fn extract_database_from_file (path_name: &str) -> Vec<DbTable> {
+// The type of a column in the schema file, TEXT(2048) gives String
+pub fn mysql_to_graphql_type (db_type: &str) -> String {
+    match db_type {
+        "INT" => "Int".to_string(),
+        "TEXT(2048)" => "String".to_string(),
+        "BOOLEAN" => "Boolean".to_string(),
+        _ => db_type.to_string()
+    }
+}
+
+// The schema file could not be opened or read is a validation error, a schema that does not parse a parse error
+pub fn extract_database_from_file (path_name: &str) -> Result<(Vec<DbTable>, Vec<DbEnum>), GraphqlError> {
     let path = Path::new(path_name);
     let mut file = match File::open(path){
-        Err(why) => panic!("couldn't open {}: {}", path_name,
-                why.description()),
+        Err(why) => return Err(GraphqlError::Validation("Couldn't open the schema file ".to_string() + path_name + ": " + why.description())),
         Ok(file) => file,
     };
 
     let mut db_data = String::new();
-    file.read_to_string(&mut db_data);
+    if let Err(why) = file.read_to_string(&mut db_data) {
+        return Err(GraphqlError::Validation("Couldn't read the schema file ".to_string() + path_name + ": " + why.description()));
+    }
+
+    extract_database(&db_data)
+}
 
-    let result = parser::parse_all_objects(db_data.as_bytes());
+// The types and enums of a schema, the inverse of printer::print_schema
+pub fn extract_database (db_data: &str) -> Result<(Vec<DbTable>, Vec<DbEnum>), GraphqlError> {
+    into_database(&try!(extract_definitions(db_data)))
+}
 
-    match result{
-        IResult::Done(_, tables) => {
-            let mut db: Vec<DbTable> = Vec::new();
-            for table in tables {
+// The definitions of a schema in the order of the file
+pub fn extract_definitions (db_data: &str) -> Result<Vec<SchemaDefinition>, GraphqlError> {
+    parser::into_result(db_data.as_bytes(), parser::parse_all_objects(db_data.as_bytes()))
+}
+
+// The tables and enums of the definitions. The id field is the primary key every table has, it may be declared
+// but only as an ID.
+pub fn into_database (definitions: &Vec<SchemaDefinition>) -> Result<(Vec<DbTable>, Vec<DbEnum>), GraphqlError> {
+    let mut db: Vec<DbTable> = Vec::new();
+    let mut enums: Vec<DbEnum> = Vec::new();
+    for definition in definitions {
+        match *definition {
+            SchemaDefinition::Type(ref name, ref fields) => {
                 let mut columns: Vec<DbColumn> = Vec::new();
-                for column in table.1 {
-                    columns.push(DbColumn { name: column.0.to_string(), db_type: graphql_to_mysql_type(column.1.to_string()), is_mandatory: column.2});
+                for column in fields {
+                    if column.0 == "id" {
+                        if column.1 != "ID" {
+                            return Err(GraphqlError::Validation("Field \"id\" of type \"".to_string() + name + "\" must be of type ID"));
+                        }
+                        continue;
+                    }
+                    columns.push(DbColumn { name: column.0.to_string(), db_type: graphql_to_mysql_type(&column.1), is_mandatory: column.2});
                 }
-                db.push(DbTable{ name: table.0.to_string(), columns:columns })
+                db.push(DbTable{ name: name.clone(), columns:columns })
+            },
+            SchemaDefinition::Enum(ref name, ref values) => {
+                enums.push(DbEnum{ name: name.clone(), values: values.clone() })
             }
-            db
-        },
-        IResult::Error (_) => unimplemented!(),
-        IResult::Incomplete (_) => unimplemented!()
+        }
     }
+    Ok((db, enums))
+}
 
+// The definitions of the tables then the enums, with the id field of each table
+pub fn into_definitions (database: &Vec<DbTable>, enums: &Vec<DbEnum>) -> Vec<SchemaDefinition> {
+    let mut definitions: Vec<SchemaDefinition> = Vec::new();
+    for table in database {
+        let mut fields = vec![("id".to_string(), "ID".to_string(), true)];
+        for column in &table.columns {
+            fields.push((column.name.clone(), mysql_to_graphql_type(&column.db_type), column.is_mandatory));
+        }
+        definitions.push(SchemaDefinition::Type(table.name.clone(), fields));
+    }
+    for enumeration in enums {
+        definitions.push(SchemaDefinition::Enum(enumeration.name.clone(), enumeration.values.clone()));
+    }
+    definitions
+}
 
-}
\ No newline at end of file
+#[test]
+fn test_extract_database_errors(){
+    match extract_database("type Warrior { name: String! ") {
+        Err(GraphqlError::Parse{..}) => {},
+        result => panic!("Unexpected result {:?}", result)
+    }
+    match extract_database_from_file("./no_such_schema.graphql") {
+        Err(GraphqlError::Validation(message)) => assert!(message.starts_with("Couldn't open the schema file ./no_such_schema.graphql")),
+        result => panic!("Unexpected result {:?}", result)
+    }
+    assert_eq!(extract_database(""), Ok((vec![], vec![])));
+    match extract_database("type Warrior { id: Int! name: String! }") {
+        Err(GraphqlError::Validation(message)) => assert_eq!(message, "Field \"id\" of type \"Warrior\" must be of type ID"),
+        result => panic!("Unexpected result {:?}", result)
+    }
+}
+
+#[test]
+fn test_extract_database(){
+    let (database, enums) = extract_database("type Warrior { id: ID! strength: Int power: Number } enum Race { Elf }").unwrap();
+    assert_eq!(database, vec![DbTable{ name: "Warrior".to_string(), columns: vec![column("strength", "INT", false), column("power", "INT", false)]}]);
+    assert_eq!(into_definitions(&database, &enums), vec![
+        SchemaDefinition::Type("Warrior".to_string(), vec![
+            ("id".to_string(), "ID".to_string(), true),
+            ("strength".to_string(), "Int".to_string(), false),
+            ("power".to_string(), "Int".to_string(), false)
+        ]),
+        SchemaDefinition::Enum("Race".to_string(), vec!["Elf".to_string()])
+    ]);
+}
@@ -0,0 +1,219 @@
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use filter;
+use json::JsonValue;
+#[cfg(test)]
+use test_support::{field, object};
+
+// WarriorConnection lists Warriors and leadsConnection the objects of the leads relation, as Relay connections
+pub const CONNECTION_SUFFIX: &'static str = "Connection";
+pub const CONNECTION_ARGUMENTS: [&'static str; 2] = ["first", "after"];
+pub const PAGE_INFO_FIELDS: [&'static str; 4] = ["hasNextPage", "hasPreviousPage", "startCursor", "endCursor"];
+
+const CURSOR_PREFIX: &'static str = "cursor:";
+const BASE64_CHARS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn is_connection(field: &QueryObject) -> bool {
+    field.name.len() > CONNECTION_SUFFIX.len() && field.name.ends_with(CONNECTION_SUFFIX)
+}
+
+// The type or relation a connection lists, WarriorConnection gives Warrior
+pub fn get_connection_target(name: &str) -> &str {
+    &name[..name.len() - CONNECTION_SUFFIX.len()]
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_CHARS[(group >> (18 - 6 * i) & 63) as usize] as char);
+            }
+            else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut group: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.trim_matches('=').bytes() {
+        let index = match BASE64_CHARS.iter().position(|&base64_char| base64_char == c) {
+            Some(index) => index as u32,
+            None => return None
+        };
+        group = group << 6 | index;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((group >> bits & 255) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+// Cursors are opaque to the clients, they hold the id of the object
+pub fn encode_cursor(id: i32) -> String {
+    encode_base64((CURSOR_PREFIX.to_string() + &id.to_string()).as_bytes())
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<i32, GraphqlError> {
+    let decoded = decode_base64(cursor).and_then(|bytes| String::from_utf8(bytes).ok());
+    match decoded {
+        Some(ref decoded) if decoded.starts_with(CURSOR_PREFIX) => {
+            decoded[CURSOR_PREFIX.len()..].parse::<i32>().map_err(|_| GraphqlError::Validation("Invalid cursor \"".to_string() + cursor + "\""))
+        },
+        _ => Err(GraphqlError::Validation("Invalid cursor \"".to_string() + cursor + "\""))
+    }
+}
+
+// The list query of the nodes of a connection, the page is found by the id in the cursor (keyset pagination)
+// and ordered by id, so orderBy and offset are rejected. One more node than asked for tells whether there is a next page.
+pub fn node_query(connection: &QueryObject) -> Result<QueryObject, GraphqlError> {
+    let mut params: Vec<(String, String)> = Vec::new();
+    for &(ref name, ref value) in connection.params.as_ref().unwrap_or(&Vec::new()) {
+        match name.as_str() {
+            "first" => {
+                let first = try!(filter::get_pagination(&vec![(name.clone(), value.clone())])).first.unwrap_or(0);
+                params.push((name.clone(), first.saturating_add(1).to_string()));
+            },
+            "after" => params.push(("id_gt".to_string(), try!(decode_cursor(value)).to_string())),
+            _ if filter::PAGINATION_ARGUMENTS.contains(&name.as_str()) => {
+                return Err(GraphqlError::Validation("Unknown argument \"".to_string() + name + "\" on \"" + &connection.name + "\", connections are ordered by id and paged with first and after"));
+            },
+            _ => params.push((name.clone(), value.clone()))
+        }
+    }
+    params.push(("orderBy".to_string(), "{id: ASC}".to_string()));
+
+    let mut attrs: Vec<QueryObject> = Vec::new();
+    for field in connection.attrs.as_ref().unwrap_or(&Vec::new()) {
+        if field.name == "edges" {
+            for edge_field in field.attrs.as_ref().unwrap_or(&Vec::new()) {
+                if edge_field.name == "node" {
+                    attrs.extend(edge_field.attrs.clone().unwrap_or(Vec::new()));
+                }
+            }
+        }
+    }
+    Ok(QueryObject{name: get_connection_target(&connection.name).to_string(), alias: None, params: Some(params), attrs: Some(attrs), position: connection.position, param_positions: Vec::new()})
+}
+
+// Wraps the nodes loaded by the node query into the selection of the connection, nodes are given with their ids.
+// type_name is the type of the nodes, WarriorConnection and WarriorEdge are named after it.
+pub fn connection_json(connection: &QueryObject, type_name: &str, nodes: Vec<(i32, JsonValue)>) -> Result<JsonValue, GraphqlError> {
+    let params = connection.params.clone().unwrap_or(Vec::new());
+    let first = try!(filter::get_pagination(&params)).first;
+    let mut nodes = nodes;
+    let has_next_page = first.map_or(false, |first| nodes.len() as u64 > first);
+    if let Some(first) = first {
+        nodes.truncate(first as usize);
+    }
+    let has_previous_page = params.iter().any(|&(ref name, _)| name == "after");
+    let cursor_at = |node: Option<&(i32, JsonValue)>| node.map_or(JsonValue::Null, |&(id, _)| JsonValue::String(encode_cursor(id)));
+
+    let mut fields: Vec<(String, JsonValue)> = Vec::new();
+    for field in connection.attrs.as_ref().unwrap_or(&Vec::new()) {
+        let subfields = field.attrs.clone().unwrap_or(Vec::new());
+        let value = match field.name.as_str() {
+            "edges" => JsonValue::Array(nodes.iter().map(|&(id, ref node)| {
+                JsonValue::Object(subfields.iter().map(|edge_field| {
+                    let value = match edge_field.name.as_str() {
+                        "cursor" => JsonValue::String(encode_cursor(id)),
+                        "node" => node.clone(),
+                        TYPENAME => JsonValue::String(type_name.to_string() + "Edge"),
+                        _ => JsonValue::Null
+                    };
+                    (edge_field.key().to_string(), value)
+                }).collect())
+            }).collect()),
+            "pageInfo" => JsonValue::Object(subfields.iter().map(|page_info_field| {
+                let value = match page_info_field.name.as_str() {
+                    "hasNextPage" => JsonValue::Boolean(has_next_page),
+                    "hasPreviousPage" => JsonValue::Boolean(has_previous_page),
+                    "startCursor" => cursor_at(nodes.first()),
+                    "endCursor" => cursor_at(nodes.last()),
+                    TYPENAME => JsonValue::String("PageInfo".to_string()),
+                    _ => JsonValue::Null
+                };
+                (page_info_field.key().to_string(), value)
+            }).collect()),
+            TYPENAME => JsonValue::String(type_name.to_string() + CONNECTION_SUFFIX),
+            _ => return Err(GraphqlError::Validation("Cannot query field \"".to_string() + &field.name + "\" on type \"" + type_name + CONNECTION_SUFFIX + "\""))
+        };
+        fields.push((field.key().to_string(), value));
+    }
+    Ok(JsonValue::Object(fields))
+}
+
+#[test]
+fn test_cursors(){
+    assert_eq!(encode_cursor(17), "Y3Vyc29yOjE3");
+    assert_eq!(encode_cursor(1), "Y3Vyc29yOjE=");
+    assert_eq!(decode_cursor("Y3Vyc29yOjE3"), Ok(17));
+    assert_eq!(decode_cursor(&encode_cursor(1234)), Ok(1234));
+    assert_eq!(decode_cursor("17"), Err(GraphqlError::Validation("Invalid cursor \"17\"".to_string())));
+}
+
+#[test]
+fn test_connection(){
+    let connection = QueryObject{
+        name: "WarriorConnection".to_string(),
+        alias: None,
+        params: Some(vec![("race".to_string(), "Elf".to_string()), ("first".to_string(), "2".to_string()), ("after".to_string(), encode_cursor(3))]),
+        attrs: Some(vec![
+            object("edges", vec![field("cursor"), object("node", vec![field("name")])]),
+            object("pageInfo", vec![field("hasNextPage"), field("endCursor")]),
+            field("__typename")
+        ]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        node_query(&connection),
+        Ok(QueryObject{
+            name: "Warrior".to_string(),
+            alias: None,
+            params: Some(vec![
+                ("race".to_string(), "Elf".to_string()),
+                ("first".to_string(), "3".to_string()),
+                ("id_gt".to_string(), "3".to_string()),
+                ("orderBy".to_string(), "{id: ASC}".to_string())
+            ]),
+            attrs: Some(vec![field("name")]),
+            position: 0,
+            param_positions: Vec::new()
+        })
+    );
+
+    let ordered = QueryObject{params: Some(vec![("orderBy".to_string(), "{name: DESC}".to_string())]), .. connection.clone()};
+    assert_eq!(
+        node_query(&ordered),
+        Err(GraphqlError::Validation("Unknown argument \"orderBy\" on \"WarriorConnection\", connections are ordered by id and paged with first and after".to_string()))
+    );
+    let unbounded = QueryObject{params: Some(vec![("first".to_string(), "18446744073709551615".to_string())]), .. connection.clone()};
+    assert_eq!(
+        node_query(&unbounded).unwrap().params,
+        Some(vec![("first".to_string(), "18446744073709551615".to_string()), ("orderBy".to_string(), "{id: ASC}".to_string())])
+    );
+
+    let node = |name: &str| JsonValue::Object(vec![("name".to_string(), JsonValue::String(name.to_string()))]);
+    assert_eq!(
+        connection_json(&connection, "Warrior", vec![(4, node("elf4")), (5, node("elf5")), (6, node("elf6"))]).unwrap().to_string(),
+        "{\"edges\":[{\"cursor\":\"Y3Vyc29yOjQ=\",\"node\":{\"name\":\"elf4\"}},{\"cursor\":\"Y3Vyc29yOjU=\",\"node\":{\"name\":\"elf5\"}}],\
+         \"pageInfo\":{\"hasNextPage\":true,\"endCursor\":\"Y3Vyc29yOjU=\"},\"__typename\":\"WarriorConnection\"}"
+    );
+    assert_eq!(
+        connection_json(&connection, "Warrior", vec![]).unwrap().to_string(),
+        "{\"edges\":[],\"pageInfo\":{\"hasNextPage\":false,\"endCursor\":null},\"__typename\":\"WarriorConnection\"}"
+    );
+}
@@ -1,16 +1,30 @@
-use mysql::PooledConn;
+use mysql;
 use std::vec::Vec;
 use def;
+use filter;
+use relay;
+use aggregate;
+use error::GraphqlError;
+#[cfg(test)]
+use test_support::{column, field, object};
+
+// MySQL has no OFFSET without a LIMIT, its documentation takes the largest BIGINT UNSIGNED to stand for all the rows
+const ALL_ROWS: u64 = 18446744073709551615;
 
 #[derive(Clone)]
 pub struct Serializer{
-    pub relations: Vec<def::Relation>
+    pub relations: Vec<def::Relation>,
+    pub enums: Vec<def::DbEnum>,
+    // the arguments filtering a table are split into columns and operators knowing its columns
+    pub tables: Vec<def::DbTable>
 }
 
 impl Serializer {
     pub fn new() -> Serializer{
         Serializer{
-            relations: Vec::new()
+            relations: Vec::new(),
+            enums: Vec::new(),
+            tables: Vec::new()
         }
     }
 
@@ -18,14 +32,47 @@ impl Serializer {
         self.relations.append(rels);
     }
 
-    fn get_target_by_relation (&self, origin: String, relation: String) -> String{
-        let mut target: String = "".to_string();
+    pub fn store_enums(&mut self, enums: &mut Vec<def::DbEnum>){
+        self.enums.append(enums);
+    }
+
+    pub fn store_tables(&mut self, tables: &mut Vec<def::DbTable>){
+        self.tables.append(tables);
+    }
+
+    // The columns of a table, none for a table the serializer does not know
+    fn get_columns(&self, type_name: &str) -> &[def::DbColumn]{
+        match self.tables.iter().find(|table| table.name == type_name) {
+            Some(table) => &table.columns,
+            None => &[]
+        }
+    }
+
+    fn get_enum_column_type(&self, type_name: &str) -> Option<String>{
+        for enumeration in &self.enums{
+            if enumeration.name == type_name{
+                let values: Vec<String> = enumeration.values.iter().map(|value| "'".to_string() + value + "'").collect();
+                return Some("ENUM(".to_string() + &values.join(",") + ")");
+            }
+        }
+        None
+    }
+
+    pub fn get_target_by_relation (&self, origin: String, relation: String) -> Result<String, GraphqlError>{
         for rel in &self.relations{
             if rel.owner == origin && rel.name == relation{
-                target = rel.target.clone();
+                return Ok(rel.target.clone());
             }
         }
-        target
+        Err(GraphqlError::Validation("Unknown relation ".to_string() + &relation + " on type " + &origin))
+    }
+
+    fn get_selection<'a>(&self, select_structure : &'a def::QueryObject) -> Result<&'a Vec<def::QueryObject>, GraphqlError>{
+        select_structure.attrs.as_ref().ok_or(GraphqlError::Validation("No fields selected on ".to_string() + &select_structure.name))
+    }
+
+    pub fn get_mutation_attrs<'a>(&self, mutation_structure : &'a def::MutationObject) -> Result<&'a Vec<def::MutationObject>, GraphqlError>{
+        mutation_structure.attrs.as_ref().ok_or(GraphqlError::Validation("No fields given for ".to_string() + &mutation_structure.name))
     }
 
     pub fn create_database(&self, db_name: String) -> String {
@@ -65,7 +112,7 @@ impl Serializer {
             }
             else {
                 load_table_query = load_table_query + ",
-                "+ &column.name + " "+ &column.db_type + if column.is_mandatory {" NOT NULL"} else {""};
+                "+ &self.get_column_definition(column);
             }
 
         };
@@ -74,6 +121,43 @@ impl Serializer {
         (load_table_query, rels)
     }
 
+    // name TEXT(2048) NOT NULL
+    pub fn get_column_definition(&self, column: &def::DbColumn) -> String{
+        column.name.clone() + " " + &self.get_sql_type(&column.db_type) + if column.is_mandatory {" NOT NULL"} else {""}
+    }
+
+    // The type of the column a scalar field is stored in, enums are stored as ENUM columns of their values
+    pub fn get_sql_type(&self, db_type: &str) -> String{
+        self.get_enum_column_type(db_type).unwrap_or(db_type.to_string())
+    }
+
+    pub fn add_column(&self, db_name: String, table_name: &str, column: &def::DbColumn) -> String{
+        "ALTER TABLE ".to_string() + &db_name + "." + table_name + " ADD COLUMN " + &self.get_column_definition(column) + ";"
+    }
+
+    pub fn modify_column(&self, db_name: String, table_name: &str, column: &def::DbColumn) -> String{
+        "ALTER TABLE ".to_string() + &db_name + "." + table_name + " MODIFY COLUMN " + &self.get_column_definition(column) + ";"
+    }
+
+    pub fn drop_column(&self, db_name: String, table_name: &str, column_name: &str) -> String{
+        "ALTER TABLE ".to_string() + &db_name + "." + table_name + " DROP COLUMN " + column_name + ";"
+    }
+
+    // The migrations, each one with the version of the schema it migrates to, its statements, how many of them
+    // were applied and its status: running, applied or failed with its error
+    pub fn create_history_table(&self, db_name: String) -> String{
+        "CREATE TABLE IF NOT EXISTS ".to_string() + &db_name + "." + def::HISTORY_TABLE + "(
+    id INT NOT NULL PRIMARY KEY AUTO_INCREMENT,
+    version VARCHAR(16) NOT NULL,
+    schema_definition TEXT NOT NULL,
+    statements TEXT NOT NULL,
+    applied_statements INT NOT NULL DEFAULT 0,
+    status VARCHAR(16) NOT NULL,
+    error TEXT,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);\n"
+    }
+
     pub fn create_relation_table (&self, db_name: String, relation: &def::Relation) -> String{
         let mut load_rel_query: String = "".to_string();
         load_rel_query = load_rel_query + "CREATE TABLE IF NOT EXISTS " + &db_name + "." + &relation.owner + "_" + &relation.name + "_" + &relation.target + "(\n   ";
@@ -83,192 +167,1121 @@ impl Serializer {
         load_rel_query
     }
 
-    pub fn perform_get_ids(&self, db_name: String, select_structure : &def::QueryObject) -> String{
-        let last_column = select_structure.attrs.as_ref().unwrap().last().unwrap();
-        let mut mysql_select: String = "SELECT id FROM ".to_string() + &(db_name) + "." + &select_structure.name + " ";
-        if let &Some(parameters) = &select_structure.params.as_ref() {
-            let last_param = select_structure.params.as_ref().unwrap().last().unwrap();
-            mysql_select = mysql_select + "WHERE ";
-            for parameter in parameters {
-                mysql_select = mysql_select + &parameter.0 + "=\"" + &parameter.1 + "\"";
-                if parameter != last_param {mysql_select = mysql_select + " AND";}
-                mysql_select = mysql_select + " ";
+    // The SQL condition of a filter, its values are bound as params. prefix is the alias of the filtered table.
+    fn perform_filter(&self, filter: &def::Filter, prefix: &str, params: &mut Vec<mysql::Value>) -> String{
+        match *filter {
+            def::Filter::Condition{ref column, ref operator, ref values} => {
+                let column = prefix.to_string() + column;
+                match operator.as_str() {
+                    "in" => {
+                        if values.is_empty() {
+                            return "FALSE".to_string();
+                        }
+                        let placeholders: Vec<&str> = values.iter().map(|_| "?").collect();
+                        for value in values {
+                            params.push(mysql::Value::from(value.as_str()));
+                        }
+                        column + " IN (" + &placeholders.join(", ") + ")"
+                    },
+                    "isNull" => column + if values[0] == "true" {" IS NULL"} else {" IS NOT NULL"},
+                    "contains" | "startsWith" => {
+                        // the wildcards of LIKE are matched literally in the value
+                        let value = values[0].replace("\\", "\\\\").replace("%", "\\%").replace("_", "\\_");
+                        params.push(mysql::Value::from(if operator == "contains" {"%".to_string() + &value + "%"} else {value + "%"}));
+                        column + " LIKE ?"
+                    },
+                    _ => {
+                        params.push(mysql::Value::from(values[0].as_str()));
+                        let comparison = match operator.as_str() {
+                            "gt" => ">",
+                            "gte" => ">=",
+                            "lt" => "<",
+                            "lte" => "<=",
+                            "ne" => "<>",
+                            _ => "="
+                        };
+                        column + comparison + "?"
+                    }
+                }
+            },
+            def::Filter::And(ref filters) | def::Filter::Or(ref filters) => {
+                if filters.is_empty() {
+                    return "TRUE".to_string();
+                }
+                let separator = if let def::Filter::And(_) = *filter {" AND "} else {" OR "};
+                let conditions: Vec<String> = filters.iter().map(|filter| self.perform_filter(filter, prefix, params)).collect();
+                if conditions.len() == 1 {conditions[0].clone()} else {"(".to_string() + &conditions.join(separator) + ")"}
+            },
+            def::Filter::Not(ref filter) => "NOT ".to_string() + &self.perform_filter(filter, prefix, params)
+        }
+    }
+
+    // The conditions of the arguments filtering the objects of a type, the ones given at the top level are not put in parentheses
+    fn get_conditions(&self, type_name: &str, arguments: &Vec<(String, String)>, prefix: &str, params: &mut Vec<mysql::Value>) -> Result<Vec<String>, GraphqlError>{
+        match try!(filter::get_filter(arguments, self.get_columns(type_name))) {
+            def::Filter::And(filters) => Ok(filters.iter().map(|filter| self.perform_filter(filter, prefix, params)).collect()),
+            other => Ok(vec![self.perform_filter(&other, prefix, params)])
+        }
+    }
+
+    fn perform_where(&self, type_name: &str, params: &Option<Vec<(String, String)>>, statement: &mut def::SqlStatement) -> Result<(), GraphqlError>{
+        if let &Some(ref parameters) = params {
+            let conditions = try!(self.get_conditions(type_name, parameters, "", &mut statement.params));
+            // the arguments may only paginate
+            if !conditions.is_empty() {
+                statement.query.push_str(&("WHERE ".to_string() + &conditions.join(" AND ") + " "));
+            }
+        }
+        Ok(())
+    }
+
+    // The ordering of the orderBy argument, a page without orderBy is taken in the order of the ids
+    fn get_order_by(&self, pagination: &def::Pagination, prefix: &str) -> Vec<String>{
+        let mut order_by: Vec<String> = pagination.order_by.iter().map(|&(ref field, ref direction)| prefix.to_string() + field + " " + direction).collect();
+        if order_by.is_empty() && (pagination.first.is_some() || pagination.offset.is_some()) {
+            order_by.push(prefix.to_string() + "id ASC");
+        }
+        order_by
+    }
+
+    // ORDER BY and LIMIT of the pagination arguments
+    fn perform_pagination(&self, params: &Option<Vec<(String, String)>>, prefix: &str) -> Result<String, GraphqlError>{
+        let pagination = try!(filter::get_pagination(params.as_ref().unwrap_or(&Vec::new())));
+        let order_by = self.get_order_by(&pagination, prefix);
+        let mut clauses = String::new();
+        if !order_by.is_empty() {
+            clauses = clauses + "ORDER BY " + &order_by.join(", ") + " ";
+        }
+        match (pagination.first, pagination.offset) {
+            (Some(first), Some(offset)) => clauses = clauses + "LIMIT " + &first.to_string() + " OFFSET " + &offset.to_string() + " ",
+            (Some(first), None) => clauses = clauses + "LIMIT " + &first.to_string() + " ",
+            (None, Some(offset)) => clauses = clauses + "LIMIT " + &ALL_ROWS.to_string() + " OFFSET " + &offset.to_string() + " ",
+            (None, None) => {}
+        }
+        Ok(clauses)
+    }
+
+    pub fn perform_get_ids(&self, db_name: String, select_structure : &def::QueryObject) -> Result<def::SqlStatement, GraphqlError>{
+        let mut mysql_select = def::SqlStatement{
+            query: "SELECT id FROM ".to_string() + &(db_name) + "." + &select_structure.name + " ",
+            params: Vec::new()
+        };
+        try!(self.perform_where(&select_structure.name, &select_structure.params, &mut mysql_select));
+        mysql_select.query = mysql_select.query + ";";
+        Ok(mysql_select)
+    }
+
+    // The name of a selected column, renamed to the alias of the field when it has one
+    fn get_column_selection(&self, table: &str, col: &def::QueryObject) -> String{
+        let column = table.to_string() + col.name.as_str();
+        match col.alias {
+            Some(ref alias) => column + " AS " + alias,
+            None => column
+        }
+    }
+
+    // The number of objects of a relation, leads_count, counted by a subquery on the origin object with the given id column.
+    // None when the field does not count a relation.
+    fn get_count_selection(&self, db_name: &str, owner: &str, origin_id: &str, field: &def::QueryObject, params: &mut Vec<mysql::Value>) -> Result<Option<String>, GraphqlError>{
+        let relation = match aggregate::get_count_relation(&field.name) {
+            Some(relation) => relation,
+            None => return Ok(None)
+        };
+        let target = match self.get_target_by_relation(owner.to_string(), relation.to_string()) {
+            Ok(target) => target,
+            Err(_) => return Ok(None)
+        };
+        let mut count = "(SELECT COUNT(*) FROM ".to_string()
+            + db_name + "." + owner + "_" + relation + "_" + &target + " AS counted_relation, "
+            + db_name + "." + &target + " AS counted "
+            + "WHERE counted.id = counted_relation.target_id AND counted_relation.origin_id = " + origin_id;
+        if let Some(parameters) = field.params.as_ref() {
+            for condition in try!(self.get_conditions(&target, parameters, "counted.", params)) {
+                count = count + " AND " + &condition;
+            }
+        }
+        count = count + ")";
+        Ok(Some(match field.alias {
+            Some(ref alias) => count + " AS " + alias,
+            None => count
+        }))
+    }
+
+    // The condition of an object of the earlier table being ordered before the one of the later table,
+    // ties of the orderBy argument are ordered by id and NULL comes first in ascending order like in MySQL
+    fn get_earlier_condition(&self, pagination: &def::Pagination, earlier: &str, later: &str) -> String{
+        let mut ordering = pagination.order_by.clone();
+        if !ordering.iter().any(|&(ref field, _)| field == "id") {
+            ordering.push(("id".to_string(), "ASC".to_string()));
+        }
+        let mut alternatives: Vec<String> = Vec::new();
+        let mut equal: Vec<String> = Vec::new();
+        for (field, direction) in ordering {
+            let earlier_column = earlier.to_string() + &field;
+            let later_column = later.to_string() + &field;
+            let comparison = if direction == "DESC" {" > "} else {" < "};
+            let before = if field == "id" {
+                earlier_column.clone() + comparison + &later_column
+            }
+            else {
+                let (first_null, last_null) = if direction == "DESC" {(&later_column, &earlier_column)} else {(&earlier_column, &later_column)};
+                "(".to_string() + &earlier_column + comparison + &later_column + " OR " + first_null + " IS NULL AND " + last_null + " IS NOT NULL)"
             };
+            let mut alternative = equal.clone();
+            alternative.push(before);
+            alternatives.push(alternative.join(" AND "));
+            equal.push(earlier_column + " <=> " + &later_column);
         }
-        mysql_select = mysql_select + ";";
-        mysql_select
+        "(".to_string() + &alternatives.join(" OR ") + ")"
     }
 
-    pub fn perform_get_rels (&self, db_name: String, select_structure : &def::QueryObject, origin_ids: Vec<i32>) -> String{
-        let mut mysql_select_rels: String = "".to_string();
-        let mut mysql_select_cols: String = "".to_string();
+    // The position of a related object in the list of its origin object, from 1.
+    // MySQL 5 has no window functions, the objects of the same origin ordered before it are counted by a subquery.
+    fn get_row_position(&self, db_name: &str, owner: &str, relation: &def::QueryObject, target: &str, pagination: &def::Pagination, params: &mut Vec<mysql::Value>) -> Result<String, GraphqlError>{
+        let mut count = "(SELECT COUNT(DISTINCT earlier.id) + 1 FROM ".to_string()
+            + db_name + "." + owner + "_" + &relation.name + "_" + target + " AS earlier_relation, "
+            + db_name + "." + target + " AS earlier "
+            + "WHERE earlier.id = earlier_relation.target_id AND earlier_relation.origin_id = relation.origin_id";
+        if let Some(parameters) = relation.params.as_ref() {
+            for condition in try!(self.get_conditions(target, parameters, "earlier.", params)) {
+                count = count + " AND " + &condition;
+            }
+        }
+        Ok(count + " AND " + &self.get_earlier_condition(pagination, "earlier.", "target.") + ")")
+    }
 
-        for col in select_structure.attrs.as_ref().unwrap(){
-            if let Some(attributes) = col.attrs.as_ref() {
-                for attribute in attributes {
-                    mysql_select_cols = mysql_select_cols + "target." + attribute.name.as_str() + ", ";
+    // Selects at once the objects all the given objects are related to through a relation field,
+    // each row starts with the id of the origin object and the id of the related object.
+    // A connection field selects the nodes of its relation.
+    pub fn perform_get_rel (&self, db_name: String, owner: &str, relation : &def::QueryObject, origin_ids: &Vec<i32>) -> Result<def::SqlStatement, GraphqlError>{
+        if relay::is_connection(relation) {
+            return self.perform_get_rel(db_name, owner, &try!(relay::node_query(relation)), origin_ids);
+        }
+        let target = try!(self.get_target_by_relation(owner.to_string(), relation.name.clone()));
+        let pagination = try!(filter::get_pagination(relation.params.as_ref().unwrap_or(&Vec::new())));
+        let paged = pagination.first.is_some() || pagination.offset.is_some();
+        let mut mysql_select_cols: Vec<String> = vec!["relation.origin_id".to_string(), "target.id".to_string()];
+        let mut params: Vec<mysql::Value> = Vec::new();
+        for attribute in try!(self.get_selection(relation)) {
+            if attribute.attrs.as_ref() == None && attribute.name != def::TYPENAME && !attribute.is_id() {
+                // the columns of a page are named by their position, an alias could repeat the name of another column
+                let unaliased;
+                let column = if paged {
+                    unaliased = def::QueryObject{alias: None, .. attribute.clone()};
+                    &unaliased
+                }
+                else {
+                    attribute
                 };
-                mysql_select_cols.pop();
-                mysql_select_cols.pop();
-                mysql_select_cols = mysql_select_cols + " ";
-                for id in origin_ids.clone() {
-                    mysql_select_rels = mysql_select_rels + "SELECT " + &mysql_select_cols;
-                    mysql_select_rels = mysql_select_rels + "FROM "
-                        + &(db_name) + "." + &select_structure.name + " AS origin, "
-                        + &(db_name) + "." + &select_structure.name + "_" + &col.name + "_" + self.get_target_by_relation(select_structure.name.clone(), col.name.clone()).as_str() +" AS relation, "
-                        + &(db_name) + "." + self.get_target_by_relation(select_structure.name.clone(), col.name.clone()).as_str() + " AS target ";
-                    mysql_select_rels = mysql_select_rels + "WHERE "
-                        + "origin.id = relation.origin_id AND "
-                        + "target.id = relation.target_id AND "
-                        + "origin.id = " + &id.to_string() + " ";
-                    mysql_select_rels = mysql_select_rels + "GROUP BY relation.origin_id, relation.target_id";
-                    mysql_select_rels = mysql_select_rels + ";";
+                let mut selected = match try!(self.get_count_selection(&db_name, &target, "target.id", column, &mut params)) {
+                    Some(count) => count,
+                    None => self.get_column_selection("target.", column)
+                };
+                if paged {
+                    selected = selected + " AS column_" + &mysql_select_cols.len().to_string();
                 }
-
+                mysql_select_cols.push(selected);
+            }
+        }
+        let mut select = "SELECT ".to_string() + &mysql_select_cols.join(", ");
+        // a page of related objects is taken for each origin object, numbering the objects of each one in their order
+        if paged {
+            select = select + ", " + &try!(self.get_row_position(&db_name, owner, relation, &target, &pagination, &mut params)) + " AS row_position";
+        }
+        let placeholders: Vec<&str> = origin_ids.iter().map(|_| "?").collect();
+        params.extend(origin_ids.iter().map(|id| mysql::Value::from(*id)));
+        select = select + " FROM "
+            + &(db_name) + "." + owner + "_" + &relation.name + "_" + &target + " AS relation, "
+            + &(db_name) + "." + &target + " AS target "
+            + "WHERE target.id = relation.target_id AND "
+            + "relation.origin_id IN (" + &placeholders.join(", ") + ") ";
+        if let Some(parameters) = relation.params.as_ref() {
+            for condition in try!(self.get_conditions(&target, parameters, "target.", &mut params)) {
+                select = select + "AND " + &condition + " ";
             }
         }
+        select = select + "GROUP BY relation.origin_id, relation.target_id";
 
+        let mysql_select_rel = if paged {
+            let mut page_cols: Vec<String> = vec!["page.origin_id".to_string(), "page.id".to_string()];
+            for position in 2..mysql_select_cols.len() {
+                page_cols.push("page.column_".to_string() + &position.to_string());
+            }
+            let offset = pagination.offset.unwrap_or(0);
+            let mut page = "SELECT ".to_string() + &page_cols.join(", ") + " FROM (" + &select + ") AS page "
+                + "WHERE page.row_position > " + &offset.to_string() + " ";
+            if let Some(first) = pagination.first {
+                page = page + "AND page.row_position <= " + &offset.saturating_add(first).to_string() + " ";
+            }
+            page + "ORDER BY page.origin_id, page.row_position;"
+        }
+        else {
+            let ordering = try!(self.perform_pagination(&relation.params, "target."));
+            if ordering.is_empty() {select + ";"} else {select + " " + ordering.trim() + ";"}
+        };
 
-        mysql_select_rels
+        Ok(def::SqlStatement{
+            query: mysql_select_rel,
+            params: params
+        })
     }
 
-    pub fn perform_get(&self, db_name: String, select_structure : &def::QueryObject) -> String{
-        let mut last_column_name = "".to_string();
-        for col in select_structure.attrs.as_ref().unwrap(){
-            if col.attrs.as_ref() == None {
-                last_column_name = col.name.clone();
+    fn perform_select(&self, db_name: String, select_structure : &def::QueryObject) -> Result<def::SqlStatement, GraphqlError>{
+        let selection = try!(self.get_selection(select_structure));
+        // the id comes first so the relations of each row can be attached to it
+        let mut columns: Vec<String> = vec!["id".to_string()];
+        let mut params: Vec<mysql::Value> = Vec::new();
+        let origin_id = select_structure.name.clone() + ".id";
+        for col in selection{
+            // __typename is not a column, the deserializer adds it from the type name
+            if col.attrs.as_ref() == None && col.name != def::TYPENAME && !col.is_id() {
+                match try!(self.get_count_selection(&db_name, &select_structure.name, &origin_id, col, &mut params)) {
+                    Some(count) => columns.push(count),
+                    None => columns.push(self.get_column_selection("", col))
+                }
             }
+        }
+
+        let mut mysql_select = def::SqlStatement{
+            query: "SELECT ".to_string(),
+            params: params
         };
+        for column in columns{
+            mysql_select.query = mysql_select.query + column.as_str() + ", ";
+        }
+        if mysql_select.query.ends_with(", ") {
+            mysql_select.query.pop();
+            mysql_select.query.pop();
+            mysql_select.query = mysql_select.query + " ";
+        }
+        mysql_select.query = mysql_select.query + "FROM " + &(db_name) + "." + &select_structure.name + " ";
 
+        Ok(mysql_select)
+    }
 
-        let mut mysql_select: String = "SELECT ".to_string();
-        for col in select_structure.attrs.as_ref().unwrap(){
-            if col.attrs.as_ref() == None {
-                mysql_select = mysql_select + col.name.as_str();
-                if col.name != last_column_name {mysql_select = mysql_select + ","};
-                mysql_select = mysql_select + " ";
+    // Selects the aggregates of the objects of a type, a row for each group of the groupBy columns
+    fn perform_get_aggregate(&self, db_name: String, aggregate_structure : &def::QueryObject) -> Result<def::SqlStatement, GraphqlError>{
+        let group_by = try!(aggregate::get_group_by(aggregate_structure.params.as_ref().unwrap_or(&Vec::new())));
+        let mut columns: Vec<String> = Vec::new();
+        for field in try!(self.get_selection(aggregate_structure)) {
+            if field.name == def::TYPENAME {
+                continue;
             }
-        }
-        mysql_select = mysql_select + "FROM " + &(db_name) + "." + &select_structure.name + " ";
-        if let &Some(parameters) = &select_structure.params.as_ref() {
-            let last_param = select_structure.params.as_ref().unwrap().last().unwrap();
-            mysql_select = mysql_select + "WHERE ";
-            for parameter in parameters {
-                mysql_select = mysql_select + &parameter.0 + "=\"" + &parameter.1 + "\"";
-                if parameter != last_param {mysql_select = mysql_select + " AND";}
-                mysql_select = mysql_select + " ";
+            let column = match aggregate::split_aggregate_field(&field.name) {
+                Some((_, None)) => "COUNT(*)".to_string(),
+                Some((function, Some(column))) => function.to_uppercase() + "(" + column + ")",
+                None => field.name.clone()
             };
+            columns.push(column);
+        }
+
+        let mut mysql_select = def::SqlStatement{
+            query: "SELECT ".to_string() + &columns.join(", ") + " FROM " + &(db_name) + "." + aggregate::get_aggregate_target(&aggregate_structure.name) + " ",
+            params: Vec::new()
+        };
+        try!(self.perform_where(aggregate::get_aggregate_target(&aggregate_structure.name), &aggregate::get_filter_arguments(&aggregate_structure.params), &mut mysql_select));
+        if !group_by.is_empty() {
+            mysql_select.query = mysql_select.query + "GROUP BY " + &group_by.join(", ") + " ORDER BY " + &group_by.join(", ") + " ";
         }
-        mysql_select = mysql_select + ";";
+        mysql_select.query = mysql_select.query + ";";
 
-        mysql_select
+        Ok(mysql_select)
     }
 
-    pub fn perform_add_rels(&self, db_name: String, origin: &def::MutationObject, rels: &def::MutationObject) -> String{
-        let mut mysql_insert_rels: String = "".to_string();
+    pub fn perform_get(&self, db_name: String, select_structure : &def::QueryObject) -> Result<def::SqlStatement, GraphqlError>{
+        if relay::is_connection(select_structure) {
+            return self.perform_get(db_name, &try!(relay::node_query(select_structure)));
+        }
+        if aggregate::is_aggregate(select_structure) {
+            return self.perform_get_aggregate(db_name, select_structure);
+        }
+        let mut mysql_select = try!(self.perform_select(db_name, select_structure));
+        try!(self.perform_where(&select_structure.name, &select_structure.params, &mut mysql_select));
+        mysql_select.query = mysql_select.query + &try!(self.perform_pagination(&select_structure.params, "")) + ";";
 
-        for target in rels.attrs.as_ref().unwrap(){
-            mysql_insert_rels = mysql_insert_rels + "INSERT INTO "  + &db_name + "." + &origin.name + "_" + rels.name.as_str() + "_" + target.name.as_str() + "(\n    ";
-            mysql_insert_rels = mysql_insert_rels + "    " + &origin.name + "_id" + &target.name + "_id" + "\n)\n" + "VALUES (\n    ";
-            if let Some(params) = target.params.as_ref(){
-                /*
-                    MutationObject {
-                        name: "Droid".to_string(),
-                        value: None,
-                        params: Some(vec![("id".to_string(), "1".to_string())]),
-                        attrs: None
-                    },
-                    MutationObject {
-                        name: "Droid".to_string(),
-                        value: None,
-                        params: Some(vec![("name".to_string(), "R2D2".to_string())]),
-                        attrs: None
-                    }
-                */
+        Ok(mysql_select)
+    }
+
+    // Selects the rows with the given ids, used to read back the objects affected by a mutation
+    pub fn perform_get_by_ids(&self, db_name: String, select_structure : &def::QueryObject, ids: &Vec<i32>) -> Result<def::SqlStatement, GraphqlError>{
+        let mut mysql_select = try!(self.perform_select(db_name, select_structure));
+        if ids.is_empty() {
+            mysql_select.query = mysql_select.query + "WHERE FALSE;";
+            return Ok(mysql_select);
+        }
+        let placeholders: Vec<&str> = ids.iter().map(|_| "?").collect();
+        mysql_select.query = mysql_select.query + "WHERE id IN (" + &placeholders.join(", ") + ");";
+        for id in ids {
+            mysql_select.params.push(mysql::Value::from(*id));
+        }
+
+        Ok(mysql_select)
+    }
+
+    // The fields given without a value in a mutation are the fields of the affected objects to return
+    pub fn get_mutation_selection(&self, mutation_structure : &def::MutationObject) -> Option<def::QueryObject>{
+        let mut selection: Vec<def::QueryObject> = Vec::new();
+        if let Some(attrs) = mutation_structure.attrs.as_ref() {
+            for attr in attrs {
+                if attr.value.is_none() && attr.attrs.is_none() {
+                    selection.push(def::QueryObject{name: attr.name.clone(), alias: None, params: None, attrs: None, position: attr.position, param_positions: Vec::new()});
+                }
+            }
+        }
+        if selection.is_empty() {
+            return None;
+        }
+        Some(def::QueryObject{
+            name: mutation_structure.name.clone(),
+            alias: None,
+            params: mutation_structure.params.clone(),
+            attrs: Some(selection),
+            position: mutation_structure.position,
+            param_positions: mutation_structure.param_positions.clone()
+        })
+    }
+
+    // Links an object to related objects through the Owner_relation_Target join table
+    pub fn perform_add_rels(&self, db_name: String, origin: &def::MutationObject, rels: &def::MutationObject, origin_id: i32, target_ids: &Vec<i32>) -> Result<def::SqlStatement, GraphqlError>{
+        let target = try!(self.get_target_by_relation(origin.name.clone(), rels.name.clone()));
+        let mut mysql_insert_rels = def::SqlStatement{
+            query: "INSERT INTO ".to_string() + &db_name + "." + &origin.name + "_" + &rels.name + "_" + &target + " (origin_id, target_id) VALUES ",
+            params: Vec::new()
+        };
+        let rows: Vec<&str> = target_ids.iter().map(|_| "(?, ?)").collect();
+        mysql_insert_rels.query = mysql_insert_rels.query + &rows.join(", ") + ";";
+        for target_id in target_ids {
+            mysql_insert_rels.params.push(mysql::Value::from(origin_id));
+            mysql_insert_rels.params.push(mysql::Value::from(*target_id));
+        }
+        Ok(mysql_insert_rels)
+    }
+
+    // Removes the links of the given objects through the Owner_relation_Target join table,
+    // all of their links when no target ids are given
+    pub fn perform_delete_rels(&self, db_name: String, origin: &def::MutationObject, rels: &def::MutationObject, origin_ids: &Vec<i32>, target_ids: Option<&Vec<i32>>) -> Result<def::SqlStatement, GraphqlError>{
+        let target = try!(self.get_target_by_relation(origin.name.clone(), rels.name.clone()));
+        let mut mysql_delete_rels = def::SqlStatement{
+            query: "DELETE FROM ".to_string() + &db_name + "." + &origin.name + "_" + &rels.name + "_" + &target + " ",
+            params: Vec::new()
+        };
+        let origin_placeholders: Vec<&str> = origin_ids.iter().map(|_| "?").collect();
+        mysql_delete_rels.query = mysql_delete_rels.query + "WHERE origin_id IN (" + &origin_placeholders.join(", ") + ")";
+        for origin_id in origin_ids {
+            mysql_delete_rels.params.push(mysql::Value::from(*origin_id));
+        }
+        if let Some(target_ids) = target_ids {
+            let target_placeholders: Vec<&str> = target_ids.iter().map(|_| "?").collect();
+            mysql_delete_rels.query = mysql_delete_rels.query + " AND target_id IN (" + &target_placeholders.join(", ") + ")";
+            for target_id in target_ids {
+                mysql_delete_rels.params.push(mysql::Value::from(*target_id));
+            }
+        }
+        mysql_delete_rels.query = mysql_delete_rels.query + ";";
+        Ok(mysql_delete_rels)
+    }
 
+    // Removes every link of the objects of a table that are deleted, from the join tables of the relations
+    // the table owns (origin_id) and of the relations targeting it (target_id)
+    pub fn perform_delete_object_rels(&self, db_name: String, table_name: &str, ids: &Vec<i32>) -> Vec<def::SqlStatement>{
+        let placeholders: Vec<&str> = ids.iter().map(|_| "?").collect();
+        let mut statements: Vec<def::SqlStatement> = Vec::new();
+        for rel in &self.relations {
+            let join_table = db_name.clone() + "." + &rel.owner + "_" + &rel.name + "_" + &rel.target;
+            for &(table, column) in &[(&rel.owner, "origin_id"), (&rel.target, "target_id")] {
+                if table == table_name {
+                    statements.push(def::SqlStatement{
+                        query: "DELETE FROM ".to_string() + &join_table + " WHERE " + column + " IN (" + &placeholders.join(", ") + ");",
+                        params: ids.iter().map(|id| mysql::Value::from(*id)).collect()
+                    });
+                }
             }
         }
-        mysql_insert_rels
+        statements
     }
 
-    pub fn perform_add_mutation(&self, conn: &mut PooledConn, db_name: String, insert_structure : &def::MutationObject){
+    pub fn perform_add_mutation(&self, db_name: String, insert_structure : &def::MutationObject) -> Result<def::SqlStatement, GraphqlError>{
+        let columns = try!(self.get_mutation_attrs(insert_structure));
         let mut last_column_name = "".to_string();
-        let mut last_value = &"".to_string();
-        for col in insert_structure.attrs.as_ref().unwrap(){
-            if let Some(val) = col.value.as_ref(){
+        for col in columns{
+            if col.value.is_some(){
                 last_column_name = col.name.clone();
-                last_value = val;
             }
         };
 
-        let mut mysql_insert_rels: String = "".to_string();
-        let mut mysql_insert: String = "INSERT INTO ".to_string() + &db_name + "." + &insert_structure.name + "(\n    ";
+        let mut mysql_insert = def::SqlStatement{
+            query: "INSERT INTO ".to_string() + &db_name + "." + &insert_structure.name + "(\n    ",
+            params: Vec::new()
+        };
         /*COLUMNS*/
-        for col in insert_structure.attrs.as_ref().unwrap(){
-            if let Some(val) = col.value.as_ref(){
-                mysql_insert = mysql_insert + col.name.as_str();
-                if col.name != last_column_name {mysql_insert = mysql_insert + ","};
-                mysql_insert = mysql_insert + " ";
+        for col in columns{
+            if col.value.is_some(){
+                mysql_insert.query = mysql_insert.query + col.name.as_str();
+                if col.name != last_column_name {mysql_insert.query = mysql_insert.query + ","};
+                mysql_insert.query = mysql_insert.query + " ";
             }
         }
 
-        mysql_insert = mysql_insert + "\n)\n" + "VALUES (\n    ";
-        for col in insert_structure.attrs.as_ref().unwrap(){
+        mysql_insert.query = mysql_insert.query + "\n)\n" + "VALUES (\n    ";
+        for col in columns{
             if let Some(val) = col.value.as_ref(){
-                mysql_insert = mysql_insert + "\"" + val.as_str() + "\"";;
-                if val != last_value {mysql_insert = mysql_insert + ","};
-                mysql_insert = mysql_insert + " ";
+                mysql_insert.query = mysql_insert.query + "?";
+                mysql_insert.params.push(mysql::Value::from(val.as_str()));
+                if col.name != last_column_name {mysql_insert.query = mysql_insert.query + ","};
+                mysql_insert.query = mysql_insert.query + " ";
             }
         }
-        mysql_insert = mysql_insert + "\n);\n";
-
-        mysql_insert = mysql_insert + mysql_insert_rels.as_str();
-        //got the id from origin
-        let last_insert_id = conn.query(&mysql_insert).unwrap().last_insert_id();
-
-        //get the id from targets
+        mysql_insert.query = mysql_insert.query + "\n);\n";
 
+        Ok(mysql_insert)
     }
 
-    pub fn perform_update_mutation(&self, db_name: String, update_structure : &def::MutationObject) -> String{
-        let last_column = &update_structure.attrs.as_ref().unwrap().last().unwrap();
-        let mut mysql_update: String = "UPDATE ".to_string() + &db_name + "." + &update_structure.name + " SET ";
+    pub fn perform_update_mutation(&self, db_name: String, update_structure : &def::MutationObject) -> Result<def::SqlStatement, GraphqlError>{
+        let mut columns: Vec<&def::MutationObject> = Vec::new();
+        for col in try!(self.get_mutation_attrs(update_structure)){
+            // relation fields are updated through the join tables, see perform_delete_rels
+            if col.value.is_some() {
+                columns.push(col);
+            }
+        }
+        let last_column = try!(columns.last().ok_or(GraphqlError::Validation("No fields given for ".to_string() + &update_structure.name)));
+        let mut mysql_update = def::SqlStatement{
+            query: "UPDATE ".to_string() + &db_name + "." + &update_structure.name + " SET ",
+            params: Vec::new()
+        };
         /*COLUMNS*/
-        for col in update_structure.attrs.as_ref().unwrap(){
-            mysql_update = mysql_update + col.name.as_str() + " = " + col.value.as_ref().unwrap().as_str();
-            if col.name != last_column.name {mysql_update = mysql_update + ","};
-            mysql_update = mysql_update + " ";
-        }
-
-        if let &Some(parameters) = &update_structure.params.as_ref() {
-            let last_param = update_structure.params.as_ref().unwrap().last().unwrap();
-            mysql_update = mysql_update + "WHERE ";
-            for parameter in parameters {
-                mysql_update = mysql_update + &parameter.0 + "=\"" + &parameter.1 + "\"";
-                if parameter != last_param {mysql_update = mysql_update + " AND";}
-                mysql_update = mysql_update + " ";
-            };
+        for col in &columns{
+            mysql_update.query = mysql_update.query + col.name.as_str() + " = ?";
+            mysql_update.params.push(mysql::Value::from(col.value.as_ref().unwrap().as_str()));
+            if col.name != last_column.name {mysql_update.query = mysql_update.query + ","};
+            mysql_update.query = mysql_update.query + " ";
         }
 
-        mysql_update = mysql_update + ";";
+        try!(self.perform_where(&update_structure.name, &update_structure.params, &mut mysql_update));
+        mysql_update.query = mysql_update.query + ";";
 
-        mysql_update
+        Ok(mysql_update)
     }
 
-    pub fn perform_delete_mutation(&self, db_name: String, delete_structure : &def::MutationObject) -> String{
-        let mut mysql_delete: String = "DELETE FROM ".to_string() + &db_name + "." + &delete_structure.name + " ";
-        if let &Some(parameters) = &delete_structure.params.as_ref() {
-            let last_param = delete_structure.params.as_ref().unwrap().last().unwrap();
-            mysql_delete = mysql_delete + "WHERE ";
-            for parameter in parameters {
-                mysql_delete = mysql_delete + &parameter.0 + "=\"" + &parameter.1 + "\"";
-                if parameter != last_param {mysql_delete = mysql_delete + " AND";}
-                mysql_delete = mysql_delete + " ";
-            };
-        }
-        mysql_delete = mysql_delete + ";";
+    pub fn perform_delete_mutation(&self, db_name: String, delete_structure : &def::MutationObject) -> Result<def::SqlStatement, GraphqlError>{
+        let mut mysql_delete = def::SqlStatement{
+            query: "DELETE FROM ".to_string() + &db_name + "." + &delete_structure.name + " ",
+            params: Vec::new()
+        };
+        try!(self.perform_where(&delete_structure.name, &delete_structure.params, &mut mysql_delete));
+        mysql_delete.query = mysql_delete.query + ";";
 
-        mysql_delete
+        Ok(mysql_delete)
     }
 
 }
+
+#[test]
+fn test_mutation_values_are_bound_as_params(){
+    let serializer = Serializer::new();
+
+    let update_structure = def::MutationObject {
+        name: "Warrior".to_string(),
+        value: None,
+        params: Some(vec![("name".to_string(), "elf\" OR \"1\"=\"1".to_string())]),
+        attrs: Some(vec![
+            def::MutationObject {
+                name: "race".to_string(),
+                value: Some("Elf\\".to_string()),
+                params: None,
+                attrs: None,
+                position: 0,
+                value_position: 0,
+                param_positions: Vec::new()
+            }
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_update_mutation("lotr_db".to_string(), &update_structure),
+        Ok(def::SqlStatement {
+            query: "UPDATE lotr_db.Warrior SET race = ? WHERE name=? ;".to_string(),
+            params: vec![mysql::Value::from("Elf\\"), mysql::Value::from("elf\" OR \"1\"=\"1")]
+        })
+    );
+
+    let select_structure = def::QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: Some(vec![("race".to_string(), "Elf".to_string()), ("strength".to_string(), "50".to_string())]),
+        attrs: Some(vec![
+            def::QueryObject {
+                name: "name".to_string(),
+                alias: None,
+                params: None,
+                attrs: None,
+                position: 0,
+                param_positions: Vec::new()
+            }
+        ]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE race=? AND strength=? ;".to_string(),
+            params: vec![mysql::Value::from("Elf"), mysql::Value::from("50")]
+        })
+    );
+}
+
+
+#[test]
+fn test_enum_columns(){
+    let mut serializer = Serializer::new();
+    serializer.store_enums(&mut vec![def::DbEnum{name: "Race".to_string(), values: vec!["ELF".to_string(), "ORC".to_string()]}]);
+
+    let table = def::DbTable {
+        name: "Warrior".to_string(),
+        columns: vec![
+            column("race", "Race", true)
+        ]
+    };
+    let (query, rels) = serializer.create_table("lotr_db".to_string(), &table);
+    assert!(query.contains("race ENUM('ELF','ORC') NOT NULL"));
+    assert!(rels.is_empty());
+}
+
+
+#[test]
+fn test_mutation_selection(){
+    let serializer = Serializer::new();
+
+    let insert_structure = def::MutationObject {
+        name: "Warrior".to_string(),
+        value: None,
+        params: None,
+        attrs: Some(vec![
+            def::MutationObject {name: "name".to_string(), value: Some("gimli".to_string()), params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()},
+            def::MutationObject {name: "id".to_string(), value: None, params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()},
+            def::MutationObject {name: "name".to_string(), value: None, params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_add_mutation("lotr_db".to_string(), &insert_structure),
+        Ok(def::SqlStatement {
+            query: "INSERT INTO lotr_db.Warrior(\n    name \n)\nVALUES (\n    ? \n);\n".to_string(),
+            params: vec![mysql::Value::from("gimli")]
+        })
+    );
+
+    let selection = serializer.get_mutation_selection(&insert_structure).unwrap();
+    assert_eq!(
+        serializer.perform_get_by_ids("lotr_db".to_string(), &selection, &vec![7, 8]),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE id IN (?, ?);".to_string(),
+            params: vec![mysql::Value::from(7), mysql::Value::from(8)]
+        })
+    );
+    assert_eq!(
+        serializer.perform_get_by_ids("lotr_db".to_string(), &selection, &vec![]),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE FALSE;".to_string(),
+            params: vec![]
+        })
+    );
+
+    let update_structure = def::MutationObject {
+        name: "Warrior".to_string(),
+        value: None,
+        params: Some(vec![("id".to_string(), "3".to_string())]),
+        attrs: Some(vec![
+            def::MutationObject {name: "strength".to_string(), value: Some("70".to_string()), params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()},
+            def::MutationObject {name: "strength".to_string(), value: None, params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_update_mutation("lotr_db".to_string(), &update_structure),
+        Ok(def::SqlStatement {
+            query: "UPDATE lotr_db.Warrior SET strength = ? WHERE id=? ;".to_string(),
+            params: vec![mysql::Value::from("70"), mysql::Value::from("3")]
+        })
+    );
+    assert_eq!(serializer.get_mutation_selection(&def::MutationObject {name: "Weapon".to_string(), value: None, params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}), None);
+}
+
+
+#[test]
+fn test_add_rels(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![def::Relation{name: "wears".to_string(), owner: "Warrior".to_string(), target: "Weapon".to_string()}]);
+
+    let wears = def::MutationObject {
+        name: "wears".to_string(),
+        value: None,
+        params: None,
+        attrs: Some(vec![
+            def::MutationObject {name: "Weapon".to_string(), value: None, params: Some(vec![("name".to_string(), "Bow".to_string())]), attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    let insert_structure = def::MutationObject {
+        name: "Warrior".to_string(),
+        value: None,
+        params: None,
+        attrs: Some(vec![
+            def::MutationObject {name: "name".to_string(), value: Some("legolas".to_string()), params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()},
+            wears.clone()
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_add_mutation("lotr_db".to_string(), &insert_structure),
+        Ok(def::SqlStatement {
+            query: "INSERT INTO lotr_db.Warrior(\n    name \n)\nVALUES (\n    ? \n);\n".to_string(),
+            params: vec![mysql::Value::from("legolas")]
+        })
+    );
+    assert_eq!(
+        serializer.perform_add_rels("lotr_db".to_string(), &insert_structure, &wears, 42, &vec![1, 4]),
+        Ok(def::SqlStatement {
+            query: "INSERT INTO lotr_db.Warrior_wears_Weapon (origin_id, target_id) VALUES (?, ?), (?, ?);".to_string(),
+            params: vec![mysql::Value::from(42), mysql::Value::from(1), mysql::Value::from(42), mysql::Value::from(4)]
+        })
+    );
+
+    let unknown = def::MutationObject {name: "rides".to_string(), value: None, params: None, attrs: Some(vec![]), position: 0, value_position: 0, param_positions: Vec::new()};
+    assert_eq!(
+        serializer.perform_add_rels("lotr_db".to_string(), &insert_structure, &unknown, 42, &vec![1]),
+        Err(GraphqlError::Validation("Unknown relation rides on type Warrior".to_string()))
+    );
+}
+
+
+#[test]
+fn test_update_rels(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()}]);
+
+    let leads = def::MutationObject {
+        name: "leads".to_string(),
+        value: None,
+        params: None,
+        attrs: Some(vec![
+            def::MutationObject {
+                name: "disconnect".to_string(),
+                value: None,
+                params: None,
+                attrs: Some(vec![
+                    def::MutationObject {name: "Warrior".to_string(), value: None, params: Some(vec![("name".to_string(), "orc1".to_string())]), attrs: None, position: 0, value_position: 0, param_positions: Vec::new()}
+                ]),
+                position: 0,
+                value_position: 0,
+                param_positions: Vec::new()
+            }
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    let update_structure = def::MutationObject {
+        name: "Leader".to_string(),
+        value: None,
+        params: Some(vec![("id".to_string(), "3".to_string())]),
+        attrs: Some(vec![
+            def::MutationObject {name: "wisdom".to_string(), value: Some("80".to_string()), params: None, attrs: None, position: 0, value_position: 0, param_positions: Vec::new()},
+            leads.clone()
+        ]),
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_update_mutation("lotr_db".to_string(), &update_structure),
+        Ok(def::SqlStatement {
+            query: "UPDATE lotr_db.Leader SET wisdom = ? WHERE id=? ;".to_string(),
+            params: vec![mysql::Value::from("80"), mysql::Value::from("3")]
+        })
+    );
+    assert_eq!(
+        serializer.perform_delete_rels("lotr_db".to_string(), &update_structure, &leads, &vec![3], Some(&vec![21, 22])),
+        Ok(def::SqlStatement {
+            query: "DELETE FROM lotr_db.Leader_leads_Warrior WHERE origin_id IN (?) AND target_id IN (?, ?);".to_string(),
+            params: vec![mysql::Value::from(3), mysql::Value::from(21), mysql::Value::from(22)]
+        })
+    );
+    assert_eq!(
+        serializer.perform_delete_rels("lotr_db".to_string(), &update_structure, &leads, &vec![3, 4], None),
+        Ok(def::SqlStatement {
+            query: "DELETE FROM lotr_db.Leader_leads_Warrior WHERE origin_id IN (?, ?);".to_string(),
+            params: vec![mysql::Value::from(3), mysql::Value::from(4)]
+        })
+    );
+}
+
+#[test]
+fn test_aliases(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()}]);
+
+    let select_structure = def::QueryObject {
+        name: "Leader".to_string(),
+        alias: None,
+        params: None,
+        attrs: Some(vec![
+            field("name"),
+            def::QueryObject{alias: Some("title".to_string()), .. field("name")},
+            def::QueryObject {
+                name: "leads".to_string(),
+                alias: Some("elves".to_string()),
+                params: Some(vec![("race".to_string(), "Elf".to_string())]),
+                attrs: Some(vec![def::QueryObject{alias: Some("elf".to_string()), .. field("name")}]),
+                position: 0,
+                param_positions: Vec::new()
+            },
+            def::QueryObject {
+                name: "leads".to_string(),
+                alias: Some("orcs".to_string()),
+                params: None,
+                attrs: Some(vec![field("strength")]),
+                position: 0,
+                param_positions: Vec::new()
+            }
+        ]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name, name AS title FROM lotr_db.Leader ;".to_string(),
+            params: vec![]
+        })
+    );
+    let from = "FROM lotr_db.Leader_leads_Warrior AS relation, lotr_db.Warrior AS target \
+                WHERE target.id = relation.target_id AND relation.origin_id IN (?) ";
+    let relations = select_structure.attrs.as_ref().unwrap();
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &relations[2], &vec![3]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.name AS elf ".to_string() + from + "AND target.race=? GROUP BY relation.origin_id, relation.target_id;",
+            params: vec![mysql::Value::from(3), mysql::Value::from("Elf")]
+        })
+    );
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &relations[3], &vec![3]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.strength ".to_string() + from + "GROUP BY relation.origin_id, relation.target_id;",
+            params: vec![mysql::Value::from(3)]
+        })
+    );
+}
+
+#[test]
+fn test_nested_rels(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![
+        def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()},
+        def::Relation{name: "wears".to_string(), owner: "Warrior".to_string(), target: "Weapon".to_string()}
+    ]);
+
+    let wears = object("wears", vec![field("name")]);
+    let leads = object("leads", vec![field("name"), wears.clone(), field("strength")]);
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &leads, &vec![1, 2]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.name, target.strength \
+                    FROM lotr_db.Leader_leads_Warrior AS relation, lotr_db.Warrior AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?, ?) \
+                    GROUP BY relation.origin_id, relation.target_id;".to_string(),
+            params: vec![mysql::Value::from(1), mysql::Value::from(2)]
+        })
+    );
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Warrior", &wears, &vec![7, 8, 9]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.name \
+                    FROM lotr_db.Warrior_wears_Weapon AS relation, lotr_db.Weapon AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?, ?, ?) \
+                    GROUP BY relation.origin_id, relation.target_id;".to_string(),
+            params: vec![mysql::Value::from(7), mysql::Value::from(8), mysql::Value::from(9)]
+        })
+    );
+}
+
+#[test]
+fn test_filters(){
+    let serializer = Serializer::new();
+    let name = field("name");
+    let select_structure = def::QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: Some(vec![
+            ("strength_gte".to_string(), "40".to_string()),
+            ("race_in".to_string(), "[Elf Orc]".to_string()),
+            ("OR".to_string(), "[{name_startsWith: elf} {strength: 10 NOT: {race_isNull: true}}]".to_string()),
+            ("name_contains".to_string(), "1_0%".to_string())
+        ]),
+        attrs: Some(vec![name.clone()]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior \
+                    WHERE strength>=? AND race IN (?, ?) AND (name LIKE ? OR (strength=? AND NOT race IS NULL)) AND name LIKE ? ;".to_string(),
+            params: vec![
+                mysql::Value::from("40"), mysql::Value::from("Elf"), mysql::Value::from("Orc"),
+                mysql::Value::from("elf%"), mysql::Value::from("10"), mysql::Value::from("%1\\_0\\%%")
+            ]
+        })
+    );
+
+    let delete_structure = def::MutationObject {
+        name: "Warrior".to_string(),
+        value: None,
+        params: Some(vec![("strength_lt".to_string(), "10".to_string()), ("name_ne".to_string(), "gimli".to_string())]),
+        attrs: None,
+        position: 0,
+        value_position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_delete_mutation("lotr_db".to_string(), &delete_structure),
+        Ok(def::SqlStatement {
+            query: "DELETE FROM lotr_db.Warrior WHERE strength<? AND name<>? ;".to_string(),
+            params: vec![mysql::Value::from("10"), mysql::Value::from("gimli")]
+        })
+    );
+    // a column whose name ends like an operator is filtered as a whole
+    let mut serializer = Serializer::new();
+    serializer.store_tables(&mut vec![def::DbTable{name: "Warrior".to_string(), columns: vec![column("is_in", "Boolean", false)]}]);
+    let select_structure = def::QueryObject{params: Some(vec![("is_in".to_string(), "true".to_string()), ("is_in_ne".to_string(), "false".to_string())]), .. select_structure};
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE is_in=? AND is_in<>? ;".to_string(),
+            params: vec![mysql::Value::from("true"), mysql::Value::from("false")]
+        })
+    );
+}
+
+#[test]
+fn test_pagination(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![
+        def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()}
+    ]);
+    let param = |name: &str, value: &str| (name.to_string(), value.to_string());
+    let name = field("name");
+    let select_structure = def::QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: Some(vec![param("race", "Elf"), param("orderBy", "{strength: DESC name: ASC}"), param("first", "10"), param("offset", "20")]),
+        attrs: Some(vec![name.clone()]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE race=? ORDER BY strength DESC, name ASC LIMIT 10 OFFSET 20 ;".to_string(),
+            params: vec![mysql::Value::from("Elf")]
+        })
+    );
+
+    let select_structure = def::QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: Some(vec![param("offset", "5")]),
+        attrs: Some(vec![name.clone()]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior ORDER BY id ASC LIMIT 18446744073709551615 OFFSET 5 ;".to_string(),
+            params: vec![]
+        })
+    );
+
+    let leads = def::QueryObject{name: "leads".to_string(), alias: None, params: Some(vec![param("race", "Orc"), param("first", "2")]), attrs: Some(vec![name.clone()]), position: 0, param_positions: Vec::new()};
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &leads, &vec![1, 2]),
+        Ok(def::SqlStatement {
+            query: "SELECT page.origin_id, page.id, page.column_2 FROM (\
+                    SELECT relation.origin_id, target.id, target.name AS column_2, \
+                    (SELECT COUNT(DISTINCT earlier.id) + 1 \
+                    FROM lotr_db.Leader_leads_Warrior AS earlier_relation, lotr_db.Warrior AS earlier \
+                    WHERE earlier.id = earlier_relation.target_id AND earlier_relation.origin_id = relation.origin_id \
+                    AND earlier.race=? AND (earlier.id < target.id)) AS row_position \
+                    FROM lotr_db.Leader_leads_Warrior AS relation, lotr_db.Warrior AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?, ?) AND target.race=? \
+                    GROUP BY relation.origin_id, relation.target_id) AS page \
+                    WHERE page.row_position > 0 AND page.row_position <= 2 ORDER BY page.origin_id, page.row_position;".to_string(),
+            params: vec![mysql::Value::from("Orc"), mysql::Value::from(1), mysql::Value::from(2), mysql::Value::from("Orc")]
+        })
+    );
+
+    // the columns of a page are named by their position, title is not ambiguous with another column
+    let title = def::QueryObject{name: "name".to_string(), alias: Some("title".to_string()), params: None, attrs: None, position: 0, param_positions: Vec::new()};
+    let leads = def::QueryObject{name: "leads".to_string(), alias: None, params: Some(vec![param("orderBy", "{name: DESC}"), param("offset", "3")]), attrs: Some(vec![title]), position: 0, param_positions: Vec::new()};
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &leads, &vec![1]),
+        Ok(def::SqlStatement {
+            query: "SELECT page.origin_id, page.id, page.column_2 FROM (\
+                    SELECT relation.origin_id, target.id, target.name AS column_2, \
+                    (SELECT COUNT(DISTINCT earlier.id) + 1 \
+                    FROM lotr_db.Leader_leads_Warrior AS earlier_relation, lotr_db.Warrior AS earlier \
+                    WHERE earlier.id = earlier_relation.target_id AND earlier_relation.origin_id = relation.origin_id \
+                    AND ((earlier.name > target.name OR target.name IS NULL AND earlier.name IS NOT NULL) \
+                    OR earlier.name <=> target.name AND earlier.id < target.id)) AS row_position \
+                    FROM lotr_db.Leader_leads_Warrior AS relation, lotr_db.Warrior AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?) \
+                    GROUP BY relation.origin_id, relation.target_id) AS page \
+                    WHERE page.row_position > 3 ORDER BY page.origin_id, page.row_position;".to_string(),
+            params: vec![mysql::Value::from(1)]
+        })
+    );
+
+    let leads = def::QueryObject{name: "leads".to_string(), alias: None, params: Some(vec![param("orderBy", "{name: DESC}")]), attrs: Some(vec![name.clone()]), position: 0, param_positions: Vec::new()};
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &leads, &vec![1, 2]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.name \
+                    FROM lotr_db.Leader_leads_Warrior AS relation, lotr_db.Warrior AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?, ?) \
+                    GROUP BY relation.origin_id, relation.target_id ORDER BY target.name DESC;".to_string(),
+            params: vec![mysql::Value::from(1), mysql::Value::from(2)]
+        })
+    );
+
+    // a connection takes the page after the id of its cursor
+    let edges = object("edges", vec![
+        object("node", vec![name.clone()])
+    ]);
+    let connection = def::QueryObject{
+        name: "WarriorConnection".to_string(),
+        alias: None,
+        params: Some(vec![param("first", "2"), param("after", "Y3Vyc29yOjM=")]),
+        attrs: Some(vec![edges]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &connection),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior WHERE id>? ORDER BY id ASC LIMIT 3 ;".to_string(),
+            params: vec![mysql::Value::from("3")]
+        })
+    );
+}
+
+#[test]
+fn test_aggregates(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![
+        def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()},
+        def::Relation{name: "allies".to_string(), owner: "Leader".to_string(), target: "Leader".to_string()}
+    ]);
+    let aggregate_structure = def::QueryObject {
+        name: "Warrior_aggregate".to_string(),
+        alias: None,
+        params: Some(vec![("strength_gt".to_string(), "10".to_string()), ("groupBy".to_string(), "[race]".to_string())]),
+        attrs: Some(vec![field("race"), field("count"), field("avg(strength)"), field("max(name)")]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &aggregate_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT race, COUNT(*), AVG(strength), MAX(name) FROM lotr_db.Warrior WHERE strength>? GROUP BY race ORDER BY race ;".to_string(),
+            params: vec![mysql::Value::from("10")]
+        })
+    );
+
+    let leads_count = def::QueryObject{name: "leads_count".to_string(), alias: None, params: Some(vec![("race".to_string(), "Orc".to_string())]), attrs: None, position: 0, param_positions: Vec::new()};
+    let select_structure = def::QueryObject {
+        name: "Leader".to_string(),
+        alias: None,
+        params: Some(vec![("wisdom".to_string(), "50".to_string())]),
+        attrs: Some(vec![field("name"), leads_count.clone()]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name, (SELECT COUNT(*) FROM lotr_db.Leader_leads_Warrior AS counted_relation, lotr_db.Warrior AS counted \
+                    WHERE counted.id = counted_relation.target_id AND counted_relation.origin_id = Leader.id AND counted.race=?) \
+                    FROM lotr_db.Leader WHERE wisdom=? ;".to_string(),
+            params: vec![mysql::Value::from("Orc"), mysql::Value::from("50")]
+        })
+    );
+
+    let allies = object("allies", vec![field("name"), leads_count]);
+    assert_eq!(
+        serializer.perform_get_rel("lotr_db".to_string(), "Leader", &allies, &vec![1]),
+        Ok(def::SqlStatement {
+            query: "SELECT relation.origin_id, target.id, target.name, (SELECT COUNT(*) FROM lotr_db.Leader_leads_Warrior AS counted_relation, lotr_db.Warrior AS counted \
+                    WHERE counted.id = counted_relation.target_id AND counted_relation.origin_id = target.id AND counted.race=?) \
+                    FROM lotr_db.Leader_allies_Leader AS relation, lotr_db.Leader AS target \
+                    WHERE target.id = relation.target_id AND relation.origin_id IN (?) \
+                    GROUP BY relation.origin_id, relation.target_id;".to_string(),
+            params: vec![mysql::Value::from("Orc"), mysql::Value::from(1)]
+        })
+    );
+}
+
+#[test]
+fn test_typename(){
+    let serializer = Serializer::new();
+    let select_structure = def::QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: None,
+        attrs: Some(vec![field("__typename"), field("name")]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+    assert_eq!(
+        serializer.perform_get("lotr_db".to_string(), &select_structure),
+        Ok(def::SqlStatement {
+            query: "SELECT id, name FROM lotr_db.Warrior ;".to_string(),
+            params: vec![]
+        })
+    );
+}
+
+#[test]
+fn test_delete_object_rels(){
+    let mut serializer = Serializer::new();
+    serializer.store_relations(&mut vec![
+        def::Relation{name: "leads".to_string(), owner: "Leader".to_string(), target: "Warrior".to_string()},
+        def::Relation{name: "wears".to_string(), owner: "Warrior".to_string(), target: "Weapon".to_string()}
+    ]);
+    assert_eq!(
+        serializer.perform_delete_object_rels("lotr_db".to_string(), "Warrior", &vec![3, 8]),
+        vec![
+            def::SqlStatement{
+                query: "DELETE FROM lotr_db.Leader_leads_Warrior WHERE target_id IN (?, ?);".to_string(),
+                params: vec![mysql::Value::from(3), mysql::Value::from(8)]
+            },
+            def::SqlStatement{
+                query: "DELETE FROM lotr_db.Warrior_wears_Weapon WHERE origin_id IN (?, ?);".to_string(),
+                params: vec![mysql::Value::from(3), mysql::Value::from(8)]
+            }
+        ]
+    );
+    assert_eq!(serializer.perform_delete_object_rels("lotr_db".to_string(), "Orc", &vec![3]), vec![]);
+}
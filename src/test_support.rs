@@ -0,0 +1,25 @@
+use std::vec::Vec;
+
+use def::*;
+
+// Fixtures of the schemas and queries the tests of the modules are written with
+
+// column("name", "TEXT(2048)", true) is the column of name: String! in the schemas of the tests
+pub fn column(name: &str, db_type: &str, is_mandatory: bool) -> DbColumn {
+    DbColumn{name: name.to_string(), db_type: db_type.to_string(), is_mandatory: is_mandatory}
+}
+
+// A column of the database as the migrations read it from information_schema
+pub fn existing_column(table_name: &str, name: &str, column_type: &str, is_mandatory: bool) -> ExistingColumn {
+    ExistingColumn{table_name: table_name.to_string(), name: name.to_string(), column_type: column_type.to_string(), is_mandatory: is_mandatory}
+}
+
+// A selected field without alias, arguments or subfields
+pub fn field(name: &str) -> QueryObject {
+    QueryObject{name: name.to_string(), alias: None, params: None, attrs: None, position: 0, param_positions: Vec::new()}
+}
+
+// A selected field with subfields
+pub fn object(name: &str, attrs: Vec<QueryObject>) -> QueryObject {
+    QueryObject{attrs: Some(attrs), .. field(name)}
+}
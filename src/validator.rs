@@ -0,0 +1,769 @@
+use std::vec::Vec;
+
+use def::*;
+use filter;
+use relay;
+use aggregate;
+use introspection;
+use parser;
+use error::{GraphqlError, ValidationError};
+#[cfg(test)]
+use test_support::column;
+
+fn is_relation_operation(name: &str) -> bool {
+    name == "connect" || name == "disconnect" || name == "set"
+}
+
+// [Warrior] gives Warrior
+fn get_relation_target(column: &DbColumn) -> Option<String> {
+    if column.db_type.starts_with("[") && column.db_type.ends_with("]") {
+        Some(column.db_type[1..column.db_type.len() - 1].to_string())
+    }
+    else {
+        None
+    }
+}
+
+// The id column is not part of the schema, every table has it
+fn get_column(table: &DbTable, column_name: &str) -> Option<DbColumn> {
+    if column_name == "id" {
+        return Some(DbColumn{name: "id".to_string(), db_type: "INT".to_string(), is_mandatory: true});
+    }
+    table.columns.iter().find(|column| column.name == column_name).cloned()
+}
+
+struct SchemaValidator<'a> {
+    database: &'a [DbTable],
+    enums: &'a [DbEnum],
+    // values of the variables of the operation, arguments using a variable are checked with its value
+    variables: &'a [(String, String)],
+    // fragments of the operation, a spread is checked against the type condition of its fragment
+    fragments: &'a [Fragment],
+    // number of fields being walked, it is limited like the nesting of a parsed query
+    depth: usize,
+    errors: Vec<ValidationError>
+}
+
+impl<'a> SchemaValidator<'a> {
+    fn error(&mut self, position: usize, message: String) {
+        self.errors.push(ValidationError{position: position, message: message});
+    }
+
+    fn new(database: &'a [DbTable], enums: &'a [DbEnum], variables: &'a [(String, String)]) -> SchemaValidator<'a> {
+        SchemaValidator{database: database, enums: enums, variables: variables, fragments: &[], depth: 0, errors: Vec::new()}
+    }
+
+    fn find_table(&self, type_name: &str) -> Option<&'a DbTable> {
+        let database = self.database;
+        database.iter().find(|table| table.name == type_name)
+    }
+
+    // Returns the expected type when the value does not fit in the column
+    fn check_value(&self, column: &DbColumn, value: &str) -> Option<String> {
+        if let Some(enumeration) = self.enums.iter().find(|enumeration| enumeration.name == column.db_type) {
+            if enumeration.values.iter().any(|enum_value| enum_value == value) {
+                return None;
+            }
+            return Some(enumeration.name.clone() + " (" + &enumeration.values.join(", ") + ")");
+        }
+        match column.db_type.as_str() {
+            "INT" => if value.parse::<i64>().is_ok() {None} else {Some("Int".to_string())},
+            "BOOLEAN" => if ["true", "false", "0", "1"].contains(&value) {None} else {Some("Boolean".to_string())},
+            _ => None
+        }
+    }
+
+    // Checks the conditions of a filter against the columns of the table, the errors are reported at the argument they come from
+    fn check_filter(&mut self, table: &DbTable, filter: &Filter, name_position: usize, value_position: usize) {
+        match *filter {
+            Filter::Condition{ref column, ref operator, ref values} => {
+                let name = if operator == "eq" {column.clone()} else {column.clone() + "_" + operator};
+                match get_column(table, column) {
+                    None => self.error(name_position, "Unknown argument \"".to_string() + &name + "\" on type \"" + &table.name + "\""),
+                    Some(ref column) if get_relation_target(column).is_some() => {
+                        self.error(name_position, "Argument \"".to_string() + &name + "\" on type \"" + &table.name + "\" is a relation and can not be used as a filter");
+                    },
+                    // contains and startsWith match text, isNull was checked to be a Boolean
+                    Some(_) if ["contains", "startsWith", "isNull"].contains(&operator.as_str()) => {},
+                    Some(ref column) => {
+                        for value in values {
+                            if let Some(expected) = self.check_value(column, value) {
+                                self.error(value_position, "Argument \"".to_string() + &name + "\" has invalid value " + value + ", expected " + &expected);
+                            }
+                        }
+                    }
+                }
+            },
+            Filter::And(ref filters) | Filter::Or(ref filters) => {
+                for filter in filters {
+                    self.check_filter(table, filter, name_position, value_position);
+                }
+            },
+            Filter::Not(ref filter) => self.check_filter(table, filter, name_position, value_position)
+        }
+    }
+
+    // orderBy and groupBy take the scalar fields of the table
+    fn check_pagination(&mut self, table: &DbTable, name: &str, value: &str, name_position: usize, value_position: usize) {
+        if name == "after" {
+            if let Err(GraphqlError::Validation(message)) = relay::decode_cursor(value) {
+                self.error(value_position, message);
+            }
+            return;
+        }
+        if name == "groupBy" {
+            match aggregate::get_group_by(&vec![(name.to_string(), value.to_string())]) {
+                Ok(group_by) => for field in group_by {
+                    if get_column(table, &field).as_ref().map_or(true, |column| get_relation_target(column).is_some()) {
+                        self.error(value_position, "Cannot group by field \"".to_string() + &field + "\" on type \"" + &table.name + "\"");
+                    }
+                },
+                Err(GraphqlError::Validation(message)) => self.error(name_position, message),
+                Err(err) => self.error(name_position, err.to_string())
+            }
+            return;
+        }
+        match filter::get_pagination(&vec![(name.to_string(), value.to_string())]) {
+            Ok(pagination) => {
+                for (field, _) in pagination.order_by {
+                    match get_column(table, &field) {
+                        None => self.error(value_position, "Cannot order by unknown field \"".to_string() + &field + "\" on type \"" + &table.name + "\""),
+                        Some(ref column) if get_relation_target(column).is_some() => {
+                            self.error(value_position, "Cannot order by relation field \"".to_string() + &field + "\" on type \"" + &table.name + "\"");
+                        },
+                        Some(_) => {}
+                    }
+                }
+            },
+            Err(GraphqlError::Validation(message)) => self.error(name_position, message),
+            Err(err) => self.error(name_position, err.to_string())
+        }
+    }
+
+    // Arguments filter the objects of a type by the value of its scalar fields, with the operators of the filter module.
+    // Lists of objects also take the pagination arguments, orderBy, first and offset, or first and after for connections.
+    fn check_arguments(&mut self, table: Option<&DbTable>, params: &Option<Vec<(String, String)>>, param_positions: &Vec<(usize, usize)>, pagination: &[&str]) {
+        let mut names: Vec<&str> = Vec::new();
+        for (i, &(ref name, ref value)) in params.as_ref().unwrap_or(&Vec::new()).iter().enumerate() {
+            let (name_position, value_position) = param_positions.get(i).cloned().unwrap_or((0, 0));
+            if names.contains(&name.as_str()) {
+                self.error(name_position, "There can be only one argument named \"".to_string() + name + "\"");
+            }
+            names.push(name);
+            let table = match table {
+                Some(table) => table,
+                None => continue
+            };
+            let value = if value.starts_with("$") {
+                match self.variables.iter().find(|&&(ref variable, _)| *variable == value[1..]) {
+                    Some(&(_, ref variable_value)) => variable_value,
+                    // not given, the argument is left out
+                    None => continue
+                }
+            }
+            else {
+                value
+            };
+            if filter::PAGINATION_ARGUMENTS.contains(&name.as_str()) || relay::CONNECTION_ARGUMENTS.contains(&name.as_str()) || aggregate::AGGREGATE_ARGUMENTS.contains(&name.as_str()) {
+                if pagination.contains(&name.as_str()) {
+                    self.check_pagination(table, name, value, name_position, value_position);
+                }
+                else {
+                    self.error(name_position, "Unknown argument \"".to_string() + name + "\" on type \"" + &table.name + "\"");
+                }
+                continue;
+            }
+            match filter::get_filter(&vec![(name.clone(), value.clone())], &table.columns) {
+                Ok(filter) => self.check_filter(table, &filter, name_position, value_position),
+                Err(GraphqlError::Validation(message)) => self.error(name_position, message),
+                Err(err) => self.error(name_position, err.to_string())
+            }
+        }
+    }
+
+    // A connection selects its edges, with the cursor and the node of each of them, and its pageInfo.
+    // table is the type of the nodes.
+    fn validate_connection(&mut self, table: Option<&DbTable>, position: usize, connection: &QueryObject) {
+        self.check_arguments(table, &connection.params, &connection.param_positions, &relay::CONNECTION_ARGUMENTS);
+        let type_name = table.map(|table| table.name.clone()).unwrap_or(String::new());
+        let fields = match connection.attrs {
+            Some(ref fields) => fields,
+            None => {
+                self.error(position, "Field \"".to_string() + &connection.name + "\" must have a selection of subfields");
+                return;
+            }
+        };
+        for field in fields {
+            let field_position = field.position;
+            self.check_arguments(None, &field.params, &field.param_positions, &[]);
+            if field.params.is_some() {
+                self.error(field_position, "Field \"".to_string() + &field.name + "\" on type \"" + &type_name + "Connection\" does not take arguments");
+            }
+            let subfields = field.attrs.clone().unwrap_or(Vec::new());
+            match field.name.as_str() {
+                "edges" => for edge_field in &subfields {
+                    let edge_field_position = edge_field.position;
+                    match edge_field.name.as_str() {
+                        "cursor" | TYPENAME => {},
+                        "node" => for node_field in edge_field.attrs.as_ref().unwrap_or(&Vec::new()) {
+                            self.validate_query_field(table, node_field);
+                        },
+                        _ => self.error(edge_field_position, "Cannot query field \"".to_string() + &edge_field.name + "\" on type \"" + &type_name + "Edge\"")
+                    }
+                },
+                "pageInfo" => for page_info_field in &subfields {
+                    let page_info_field_position = page_info_field.position;
+                    if !relay::PAGE_INFO_FIELDS.contains(&page_info_field.name.as_str()) && page_info_field.name != TYPENAME {
+                        self.error(page_info_field_position, "Cannot query field \"".to_string() + &page_info_field.name + "\" on type \"PageInfo\"");
+                    }
+                },
+                TYPENAME => {},
+                _ => self.error(field_position, "Cannot query field \"".to_string() + &field.name + "\" on type \"" + &type_name + "Connection\"")
+            }
+        }
+    }
+
+    // An aggregate selects count, the functions of its columns, avg(strength), and the columns it is grouped by
+    fn validate_aggregate(&mut self, table: Option<&DbTable>, aggregate_structure: &QueryObject) {
+        self.check_arguments(table, &aggregate_structure.params, &aggregate_structure.param_positions, &aggregate::AGGREGATE_ARGUMENTS);
+        let group_by = aggregate::get_group_by(aggregate_structure.params.as_ref().unwrap_or(&Vec::new())).unwrap_or(Vec::new());
+        for field in aggregate_structure.attrs.as_ref().unwrap_or(&Vec::new()) {
+            let position = field.position;
+            let table = match table {
+                Some(table) => table,
+                None => continue
+            };
+            if field.params.is_some() || field.attrs.is_some() {
+                self.error(position, "Field \"".to_string() + &field.name + "\" on type \"" + &aggregate_structure.name + "\" takes no arguments or subfields");
+            }
+            if field.name == TYPENAME {
+                continue;
+            }
+            match aggregate::split_aggregate_field(&field.name) {
+                Some((_, None)) => {},
+                Some((function, Some(column_name))) => match get_column(table, column_name) {
+                    Some(ref column) if get_relation_target(column).is_none() => {
+                        if (function == "sum" || function == "avg") && column.db_type != "INT" {
+                            self.error(position, "Cannot apply ".to_string() + function + " to field \"" + column_name + "\" of type \"" + &column.db_type + "\", expected an Int");
+                        }
+                    },
+                    _ => self.error(position, "Cannot aggregate field \"".to_string() + column_name + "\" on type \"" + &table.name + "\"")
+                },
+                None if group_by.contains(&field.name) => {},
+                None if get_column(table, &field.name).is_some() => {
+                    self.error(position, "Field \"".to_string() + &field.name + "\" on type \"" + &aggregate_structure.name + "\" must be in groupBy to be selected");
+                },
+                None => self.error(position, "Cannot query field \"".to_string() + &field.name + "\" on type \"" + &aggregate_structure.name + "\"")
+            }
+        }
+    }
+
+    fn validate_query_root(&mut self, select_structure: &QueryObject) {
+        let position = select_structure.position;
+        // introspection fields are checked while they are answered
+        if introspection::is_introspection(select_structure) {
+            return;
+        }
+        let type_name = if relay::is_connection(select_structure) {
+            relay::get_connection_target(&select_structure.name)
+        }
+        else if aggregate::is_aggregate(select_structure) {
+            aggregate::get_aggregate_target(&select_structure.name)
+        }
+        else {
+            &select_structure.name
+        };
+        let table = self.find_table(type_name);
+        if table.is_none() {
+            self.error(position, "Unknown type \"".to_string() + &select_structure.name + "\"");
+        }
+        if relay::is_connection(select_structure) {
+            self.validate_connection(table, position, select_structure);
+            return;
+        }
+        if aggregate::is_aggregate(select_structure) {
+            self.validate_aggregate(table, select_structure);
+            return;
+        }
+        self.check_arguments(table, &select_structure.params, &select_structure.param_positions, &filter::PAGINATION_ARGUMENTS);
+        match select_structure.attrs {
+            Some(ref fields) => {
+                for field in fields {
+                    self.validate_query_field(table, field);
+                }
+            },
+            None => if table.is_some() {
+                self.error(position, "Type \"".to_string() + &select_structure.name + "\" must have a selection of subfields");
+            }
+        }
+    }
+
+    // A fragment on another type than the one of the object it is spread in, an inline fragment has no name
+    fn check_spread(&mut self, position: usize, table: Option<&DbTable>, fragment_name: Option<&str>, on: &str) {
+        if let Some(table) = table {
+            if table.name != on && self.find_table(on).is_some() {
+                let fragment = match fragment_name {
+                    Some(name) => "Fragment \"".to_string() + name + "\" ",
+                    None => "Fragment ".to_string()
+                };
+                self.error(position, fragment + "cannot be spread here as objects of type \"" + &table.name + "\" can never be of type \"" + on + "\"");
+            }
+        }
+    }
+
+    // The table the fields of a fragment are checked against, None when its type does not exist
+    fn check_type_condition(&mut self, table: Option<&DbTable>, position: usize, on: &str) -> Option<&'a DbTable> {
+        let fragment_table = self.find_table(on);
+        if fragment_table.is_none() {
+            self.error(position, "Unknown type \"".to_string() + on + "\"");
+        }
+        self.check_spread(position, table, None, on);
+        fragment_table
+    }
+
+    fn validate_query_field(&mut self, table: Option<&DbTable>, field: &QueryObject) {
+        if self.depth == parser::MAX_DEPTH {
+            return self.error(field.position, "The selection is nested more than ".to_string() + &parser::MAX_DEPTH.to_string() + " levels deep");
+        }
+        self.depth += 1;
+        self.visit_query_field(table, field);
+        self.depth -= 1;
+    }
+
+    // Without a table the field belongs to something already reported, it is only walked through
+    fn visit_query_field(&mut self, table: Option<&DbTable>, field: &QueryObject) {
+        if let Some(fragment_name) = field.fragment_spread() {
+            // the fields of a named fragment are checked with its definition, its type condition where it is spread
+            let position = field.position;
+            let fragments = self.fragments;
+            if let Some(fragment) = fragments.iter().find(|fragment| fragment.name == fragment_name) {
+                if !fragment.on.starts_with("__") {
+                    self.check_spread(position, table, Some(fragment_name), &fragment.on);
+                }
+            }
+            return;
+        }
+        if let Some(on) = field.inline_fragment() {
+            let fragment_table = self.check_type_condition(table, field.position, on);
+            for subfield in field.attrs.as_ref().unwrap_or(&Vec::new()) {
+                self.validate_query_field(fragment_table, subfield);
+            }
+            return;
+        }
+
+        let position = field.position;
+        if field.name == TYPENAME {
+            if field.params.is_some() || field.attrs.is_some() {
+                self.error(position, "Field \"".to_string() + TYPENAME + "\" takes no arguments or subfields");
+            }
+            return;
+        }
+        if relay::is_connection(field) {
+            let connection_target = relay::get_connection_target(&field.name);
+            let connection_table = match table {
+                Some(table) => match get_column(table, connection_target).as_ref().and_then(get_relation_target) {
+                    Some(relation_target) => self.find_table(&relation_target),
+                    None => {
+                        self.error(position, "Cannot query field \"".to_string() + &field.name + "\" on type \"" + &table.name + "\", only relations have connections");
+                        None
+                    }
+                },
+                None => None
+            };
+            self.validate_connection(connection_table, position, field);
+            return;
+        }
+        let mut field_table: Option<&DbTable> = None;
+        // leads_count counts the objects of leads, its arguments filter them
+        let counted_target = table.and_then(|table| {
+            if get_column(table, &field.name).is_some() {
+                return None;
+            }
+            aggregate::get_count_relation(&field.name).and_then(|relation| get_column(table, relation)).and_then(|column| get_relation_target(&column))
+        });
+        if let Some(counted_target) = counted_target {
+            if field.attrs.is_some() {
+                self.error(position, "Field \"".to_string() + &field.name + "\" of type \"Int\" must not have a selection of subfields");
+            }
+            let counted_table = self.find_table(&counted_target);
+            self.check_arguments(counted_table, &field.params, &field.param_positions, &[]);
+            return;
+        }
+        if let Some(table) = table {
+            match get_column(table, &field.name) {
+                None => self.error(position, "Cannot query field \"".to_string() + &field.name + "\" on type \"" + &table.name + "\""),
+                Some(column) => match get_relation_target(&column) {
+                    Some(relation_target) => {
+                        field_table = self.find_table(&relation_target);
+                        if field.attrs.is_none() {
+                            self.error(position, "Field \"".to_string() + &field.name + "\" of type \"" + &column.db_type + "\" must have a selection of subfields");
+                        }
+                    },
+                    None => {
+                        if field.params.is_some() {
+                            self.error(position, "Field \"".to_string() + &field.name + "\" on type \"" + &table.name + "\" does not take arguments");
+                        }
+                        if field.attrs.is_some() {
+                            self.error(position, "Field \"".to_string() + &field.name + "\" of type \"" + &column.db_type + "\" must not have a selection of subfields");
+                        }
+                    }
+                }
+            }
+        }
+        self.check_arguments(field_table, &field.params, &field.param_positions, &filter::PAGINATION_ARGUMENTS);
+        for subfield in field.attrs.as_ref().unwrap_or(&Vec::new()) {
+            self.validate_query_field(field_table, subfield);
+        }
+    }
+
+    fn validate_fragment(&mut self, fragment: &Fragment) {
+        if fragment.on.starts_with("__") {
+            return;
+        }
+        let fragment_table = self.check_type_condition(None, fragment.position, &fragment.on);
+        for field in &fragment.attrs {
+            self.validate_query_field(fragment_table, field);
+        }
+    }
+
+    fn validate_mutation_root(&mut self, operation: &str, mutation_structure: &MutationObject) {
+        let position = mutation_structure.position;
+        let table = self.find_table(&mutation_structure.name);
+        if table.is_none() {
+            self.error(position, "Unknown type \"".to_string() + &mutation_structure.name + "\"");
+        }
+        if operation == "add" && mutation_structure.params.is_some() {
+            self.error(position, "Objects are added without arguments, only with fields".to_string());
+        }
+        self.check_arguments(table, &mutation_structure.params, &mutation_structure.param_positions, &[]);
+        self.validate_mutation_fields(operation, table, position, mutation_structure);
+    }
+
+    fn validate_mutation_fields(&mut self, operation: &str, table: Option<&DbTable>, position: usize, mutation_structure: &MutationObject) {
+        let mut assigned: Vec<&str> = Vec::new();
+        for field in mutation_structure.attrs.iter().flat_map(|fields| fields.iter()) {
+            if field.value.is_some() {
+                assigned.push(&field.name);
+            }
+            self.validate_mutation_field(operation, table, field);
+        }
+        if let Some(table) = table {
+            if operation == "add" {
+                for column in &table.columns {
+                    if column.is_mandatory && get_relation_target(column).is_none() && !assigned.contains(&column.name.as_str()) {
+                        self.error(position, "Field \"".to_string() + &table.name + "." + &column.name + "\" of required type \"" + &column.db_type + "!\" was not provided");
+                    }
+                }
+            }
+        }
+    }
+
+    fn validate_mutation_field(&mut self, operation: &str, table: Option<&DbTable>, field: &MutationObject) {
+        if self.depth == parser::MAX_DEPTH {
+            return self.error(field.position, "The object is nested more than ".to_string() + &parser::MAX_DEPTH.to_string() + " levels deep");
+        }
+        self.depth += 1;
+        self.visit_mutation_field(operation, table, field);
+        self.depth -= 1;
+    }
+
+    fn visit_mutation_field(&mut self, operation: &str, table: Option<&DbTable>, field: &MutationObject) {
+        let position = field.position;
+        let value_position = field.value_position;
+        let table = match table {
+            Some(table) => table,
+            None => return self.skip_mutation_contents(field)
+        };
+        let column = match get_column(table, &field.name) {
+            Some(column) => column,
+            None => {
+                self.error(position, "Unknown field \"".to_string() + &field.name + "\" on type \"" + &table.name + "\"");
+                return self.skip_mutation_contents(field);
+            }
+        };
+        if field.params.is_some() {
+            self.error(position, "Field \"".to_string() + &field.name + "\" on type \"" + &table.name + "\" does not take arguments");
+        }
+        self.check_arguments(None, &field.params, &field.param_positions, &[]);
+
+        let relation_target = match get_relation_target(&column) {
+            Some(relation_target) => relation_target,
+            None => {
+                if let Some(ref value) = field.value {
+                    if operation == "delete" {
+                        self.error(position, "Fields can not be assigned in a delete".to_string());
+                    }
+                    else if let Some(expected) = self.check_value(&column, value) {
+                        self.error(value_position, "Field \"".to_string() + &field.name + "\" has invalid value " + value + ", expected " + &expected);
+                    }
+                }
+                if field.attrs.is_some() {
+                    self.error(position, "Field \"".to_string() + &field.name + "\" of type \"" + &column.db_type + "\" must not have a selection of subfields");
+                    self.skip_mutation_contents(field);
+                }
+                return;
+            }
+        };
+
+        if field.value.is_some() {
+            self.error(position, "Relation \"".to_string() + &field.name + "\" can not be assigned a value, related objects are given in braces");
+        }
+        let related_objects = match field.attrs {
+            Some(ref related_objects) => related_objects,
+            None => {
+                if field.value.is_none() {
+                    self.error(position, "Field \"".to_string() + &field.name + "\" of type \"" + &column.db_type + "\" must have a selection of subfields");
+                }
+                return;
+            }
+        };
+        match operation {
+            "add" => {
+                for related_object in related_objects {
+                    self.validate_related_object(table, &field.name, &relation_target, related_object, true);
+                }
+            },
+            "update" => {
+                // leads { connect { Warrior (name: gimli) } disconnect { Warrior (id: 21) } }
+                for relation_operation in related_objects {
+                    let operation_position = relation_operation.position;
+                    if !is_relation_operation(&relation_operation.name) {
+                        self.error(operation_position, "Unknown relation operation \"".to_string() + &relation_operation.name + "\" on " + &table.name + "." + &field.name + ", expected connect, disconnect or set");
+                    }
+                    match relation_operation.attrs {
+                        Some(ref operation_objects) => {
+                            for related_object in operation_objects {
+                                self.validate_related_object(table, &field.name, &relation_target, related_object, relation_operation.name != "disconnect");
+                            }
+                        },
+                        None => self.error(operation_position, "No related objects given to ".to_string() + &relation_operation.name + " on " + &table.name + "." + &field.name)
+                    }
+                }
+            },
+            _ => {
+                self.error(position, "Relation \"".to_string() + &field.name + "\" can not be changed in a delete");
+                self.skip_mutation_contents(field);
+            }
+        }
+    }
+
+    // Only the arguments of something already reported are checked, they must not repeat
+    fn skip_mutation_contents(&mut self, mutation_structure: &MutationObject) {
+        self.check_arguments(None, &mutation_structure.params, &mutation_structure.param_positions, &[]);
+        for field in mutation_structure.attrs.as_ref().unwrap_or(&Vec::new()) {
+            self.skip_mutation_contents(field);
+        }
+    }
+
+    // A related object is either created (Weapon { name: Bow }) or found by a filter (Weapon (name: Bow))
+    fn validate_related_object(&mut self, owner: &DbTable, relation_name: &str, relation_target: &str, related_object: &MutationObject, can_create: bool) {
+        let position = related_object.position;
+        let table = if related_object.name == relation_target {
+            self.find_table(relation_target)
+        }
+        else {
+            self.error(position, related_object.name.clone() + " can not be related through " + &owner.name + "." + relation_name + ", expected " + relation_target);
+            None
+        };
+        if related_object.attrs.is_some() && !can_create {
+            self.error(position, "The related ".to_string() + &related_object.name + " of " + &owner.name + "." + relation_name + " can only be given by a filter");
+        }
+        else if related_object.attrs.is_none() && related_object.params.is_none() {
+            self.error(position, "No ".to_string() + if can_create {"fields or "} else {""} + "filter given for the related " + &related_object.name + " of " + &owner.name + "." + relation_name);
+        }
+        else if related_object.attrs.is_some() && related_object.params.is_some() {
+            self.error(position, "The related ".to_string() + &related_object.name + " of " + &owner.name + "." + relation_name + " is either created with fields or found by a filter, not both");
+        }
+        self.check_arguments(table, &related_object.params, &related_object.param_positions, &[]);
+        if related_object.attrs.is_some() {
+            self.validate_mutation_fields("add", table, position, related_object);
+        }
+    }
+
+    fn into_result(self) -> Result<(), GraphqlError> {
+        if self.errors.is_empty() {Ok(())} else {Err(GraphqlError::Invalid(self.errors))}
+    }
+}
+
+// Checks a query against the schema before any SQL is generated, the errors are at the positions the parser recorded
+pub fn validate_query(target: &TargetPool, operation: &QueryOperation, variables: &Vec<(String, String)>) -> Result<(), GraphqlError> {
+    let mut validator = SchemaValidator::new(&target.database, &target.enums, variables);
+    validator.fragments = &operation.fragments;
+    validator.validate_query_root(&operation.query);
+    for fragment in &operation.fragments {
+        validator.validate_fragment(fragment);
+    }
+    validator.into_result()
+}
+
+// Checks an add, update or delete mutation against the schema before any SQL is generated
+pub fn validate_mutation(target: &TargetPool, operation: &str, mutation_structure: &MutationObject) -> Result<(), GraphqlError> {
+    let mut validator = SchemaValidator::new(&target.database, &target.enums, &[]);
+    validator.validate_mutation_root(operation, mutation_structure);
+    validator.into_result()
+}
+
+#[cfg(test)]
+fn test_schema() -> (Vec<DbTable>, Vec<DbEnum>) {
+    (
+        vec![
+            DbTable{name: "Leader".to_string(), columns: vec![column("name", "TEXT(2048)", true), column("wisdom", "INT", false), column("leads", "[Warrior]", false)]},
+            DbTable{name: "Warrior".to_string(), columns: vec![column("name", "TEXT(2048)", true), column("race", "Race", false), column("strength", "INT", false)]}
+        ],
+        vec![DbEnum{name: "Race".to_string(), values: vec!["Elf".to_string(), "Orc".to_string()]}]
+    )
+}
+
+#[test]
+fn test_query_validation(){
+    use parser;
+
+    let (database, enums) = test_schema();
+    let variables = vec![("strength".to_string(), "strong".to_string())];
+    let validate = |source: &str| {
+        let select_structure = parser::into_result(source.as_bytes(), parser::parse_operation(source.as_bytes())).unwrap().query;
+        let mut validator = SchemaValidator::new(&database, &enums, &variables);
+        validator.validate_query_root(&select_structure);
+        validator.errors
+    };
+
+    assert_eq!(validate("{ Leader (id: 3) { name leads { name race } } }"), vec![]);
+    assert_eq!(
+        validate("{ Leader (wisdom: much) { name height leads { name (id: 1) } } }"),
+        vec![
+            ValidationError{position: 18, message: "Argument \"wisdom\" has invalid value much, expected Int".to_string()},
+            ValidationError{position: 31, message: "Cannot query field \"height\" on type \"Leader\"".to_string()},
+            ValidationError{position: 46, message: "Field \"name\" on type \"Warrior\" does not take arguments".to_string()}
+        ]
+    );
+    assert_eq!(
+        validate("{ Warrior (race: Dwarf name: elf1) { leads { name } } }").into_iter().map(|error| error.position).collect::<Vec<usize>>(),
+        vec![17, 37]
+    );
+    assert_eq!(validate("{ WarriorConnection (race: Elf first: 2 after: \"Y3Vyc29yOjE3\") { edges { cursor node { name } } pageInfo { hasNextPage endCursor } } }"), vec![]);
+    assert_eq!(
+        validate("{ Leader { leadsConnection (offset: 1 after: \"17\") { edges { node { height } } pageInfo { total } count } wisdomConnection { edges { cursor } } } }"),
+        vec![
+            ValidationError{position: 28, message: "Unknown argument \"offset\" on type \"Warrior\"".to_string()},
+            ValidationError{position: 45, message: "Invalid cursor \"17\"".to_string()},
+            ValidationError{position: 68, message: "Cannot query field \"height\" on type \"Warrior\"".to_string()},
+            ValidationError{position: 90, message: "Cannot query field \"total\" on type \"PageInfo\"".to_string()},
+            ValidationError{position: 98, message: "Cannot query field \"count\" on type \"WarriorConnection\"".to_string()},
+            ValidationError{position: 106, message: "Cannot query field \"wisdomConnection\" on type \"Leader\", only relations have connections".to_string()}
+        ]
+    );
+    assert_eq!(validate("{ Warrior_aggregate (race: Elf groupBy: [race]) { race count strongest: max(strength) avg(strength) } }"), vec![]);
+    assert_eq!(validate("{ Leader { name leads_count (race: Orc) } }"), vec![]);
+    assert_eq!(
+        validate("{ Warrior_aggregate (groupBy: [height]) { name sum(name) median(strength) } }"),
+        vec![
+            ValidationError{position: 30, message: "Cannot group by field \"height\" on type \"Warrior\"".to_string()},
+            ValidationError{position: 42, message: "Field \"name\" on type \"Warrior_aggregate\" must be in groupBy to be selected".to_string()},
+            ValidationError{position: 47, message: "Cannot apply sum to field \"name\" of type \"TEXT(2048)\", expected an Int".to_string()},
+            ValidationError{position: 57, message: "Cannot query field \"median(strength)\" on type \"Warrior_aggregate\"".to_string()}
+        ]
+    );
+    assert_eq!(
+        validate("{ Orc { name } }"),
+        vec![ValidationError{position: 2, message: "Unknown type \"Orc\"".to_string()}]
+    );
+    assert_eq!(
+        validate("query Warrior($strength: Int, $race: Race) { Warrior (strength: $strength race: $race) { name } }"),
+        vec![ValidationError{position: 64, message: "Argument \"strength\" has invalid value strong, expected Int".to_string()}]
+    );
+    assert_eq!(validate("{ Warrior (strength_gte: 10 OR: [{race: Elf} {name_startsWith: orc}]) { name } }"), vec![]);
+    assert_eq!(
+        validate("{ Warrior (strength_gt: strong height_lt: 3 name_in: elf1 NOT: {race_ne: Dwarf}) { name } }"),
+        vec![
+            ValidationError{position: 24, message: "Argument \"strength_gt\" has invalid value strong, expected Int".to_string()},
+            ValidationError{position: 31, message: "Unknown argument \"height_lt\" on type \"Warrior\"".to_string()},
+            ValidationError{position: 44, message: "Argument \"name_in\" has invalid value elf1, expected a list".to_string()},
+            ValidationError{position: 63, message: "Argument \"race_ne\" has invalid value Dwarf, expected Race (Elf, Orc)".to_string()}
+        ]
+    );
+    assert_eq!(validate("{ Leader (orderBy: {wisdom: DESC} first: 2 offset: 1) { leads (orderBy: {name: ASC} first: 5) { name } } }"), vec![]);
+    assert_eq!(
+        validate("{ Leader (orderBy: {leads: ASC height: DESC} first: many) { name } }"),
+        vec![
+            ValidationError{position: 19, message: "Cannot order by relation field \"leads\" on type \"Leader\"".to_string()},
+            ValidationError{position: 19, message: "Cannot order by unknown field \"height\" on type \"Leader\"".to_string()},
+            ValidationError{position: 45, message: "Argument \"first\" has invalid value many, expected a non-negative Int".to_string()}
+        ]
+    );
+    let nested = "{ Leader ".to_string() + &(0..100).map(|_| "{ leads ").collect::<String>() + &(0..101).map(|_| "}").collect::<String>();
+    assert!(validate(&nested).iter().any(|error| error.message == "The selection is nested more than 64 levels deep"));
+}
+
+#[test]
+fn test_fragment_validation(){
+    use parser;
+
+    let (database, enums) = test_schema();
+    let validate = |source: &str| {
+        let operation = parser::into_result(source.as_bytes(), parser::parse_operation(source.as_bytes())).unwrap();
+        let mut validator = SchemaValidator::new(&database, &enums, &[]);
+        validator.fragments = &operation.fragments;
+        validator.validate_query_root(&operation.query);
+        for fragment in &operation.fragments {
+            validator.validate_fragment(fragment);
+        }
+        validator.errors
+    };
+
+    assert_eq!(validate("{ Leader (id: 3) { ...LeaderFields leads { ... on Warrior { race } } } } fragment LeaderFields on Leader { name }"), vec![]);
+    assert_eq!(
+        validate("{ Leader { ... on Warrior { name } ...LeaderFields } } fragment LeaderFields on Leader { height }"),
+        vec![
+            ValidationError{position: 18, message: "Fragment cannot be spread here as objects of type \"Leader\" can never be of type \"Warrior\"".to_string()},
+            ValidationError{position: 89, message: "Cannot query field \"height\" on type \"Leader\"".to_string()}
+        ]
+    );
+    assert_eq!(
+        validate("{ Leader { ...Fields } } fragment Fields on Dwarf { name }"),
+        vec![ValidationError{position: 44, message: "Unknown type \"Dwarf\"".to_string()}]
+    );
+    assert_eq!(
+        validate("{ Leader { leads { ...LeaderFields } } } fragment LeaderFields on Leader { name }"),
+        vec![ValidationError{position: 22, message: "Fragment \"LeaderFields\" cannot be spread here as objects of type \"Warrior\" can never be of type \"Leader\"".to_string()}]
+    );
+}
+
+#[test]
+fn test_mutation_validation(){
+    use parser;
+
+    let (database, enums) = test_schema();
+    let validate = |operation: &str, source: &str| {
+        let mutation_structure = parser::into_result(source.as_bytes(), parser::parse_mutation_query(source.as_bytes())).unwrap();
+        let mut validator = SchemaValidator::new(&database, &enums, &[]);
+        validator.validate_mutation_root(operation, &mutation_structure);
+        validator.errors.into_iter().map(|error| error.message).collect::<Vec<String>>()
+    };
+
+    assert_eq!(validate("add", "{ Leader { name: Elrond leads { Warrior (name: gimli) Warrior { name: legolas race: Elf } } id } }"), Vec::<String>::new());
+    assert_eq!(
+        validate("add", "{ Leader { wisdom: 9000 leads { Weapon (name: Bow) Warrior { race: Dwarf } } } }"),
+        vec![
+            "Weapon can not be related through Leader.leads, expected Warrior".to_string(),
+            "Field \"race\" has invalid value Dwarf, expected Race (Elf, Orc)".to_string(),
+            "Field \"Warrior.name\" of required type \"TEXT(2048)!\" was not provided".to_string(),
+            "Field \"Leader.name\" of required type \"TEXT(2048)!\" was not provided".to_string()
+        ]
+    );
+    assert_eq!(validate("update", "{ Leader (name: Elrond) { leads { set { Warrior (name: gimli) } } } }"), Vec::<String>::new());
+    assert_eq!(
+        validate("delete", "{ Warrior (race: Orc first: 1) }"),
+        vec!["Unknown argument \"first\" on type \"Warrior\"".to_string()]
+    );
+    assert_eq!(
+        validate("update", "{ Leader (name: Elrond) { leads { add { Warrior (name: gimli) } disconnect { Warrior { name: boromir } } } } }"),
+        vec![
+            "Unknown relation operation \"add\" on Leader.leads, expected connect, disconnect or set".to_string(),
+            "The related Warrior of Leader.leads can only be given by a filter".to_string()
+        ]
+    );
+    assert_eq!(
+        validate("delete", "{ Warrior (strength: 50) { name: x } }"),
+        vec!["Fields can not be assigned in a delete".to_string()]
+    );
+}
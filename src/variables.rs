@@ -0,0 +1,156 @@
+use std::vec::Vec;
+
+use def::*;
+use error::GraphqlError;
+use json::JsonValue;
+use parser;
+#[cfg(test)]
+use test_support::field;
+
+fn invalid_value(definition: &VariableDefinition, value: &JsonValue) -> GraphqlError {
+    GraphqlError::Validation(
+        "Variable \"$".to_string() + &definition.name + "\" got invalid value " + &value.to_string() + ", expected type " + &definition.var_type
+    )
+}
+
+// Turns the JSON value of a variable into the string arguments are bound with, None for null
+fn value_to_argument(definition: &VariableDefinition, value: &JsonValue) -> Result<Option<String>, GraphqlError> {
+    match (definition.var_type.as_str(), value) {
+        (_, &JsonValue::Null) => Ok(None),
+        ("Int", &JsonValue::Int(number)) | ("Float", &JsonValue::Int(number)) | ("ID", &JsonValue::Int(number)) => Ok(Some(number.to_string())),
+        ("Float", &JsonValue::Float(number)) => Ok(Some(number.to_string())),
+        ("Boolean", &JsonValue::Boolean(boolean)) => Ok(Some(if boolean {"1"} else {"0"}.to_string())),
+        ("Int", _) | ("Float", _) | ("Boolean", _) => Err(invalid_value(definition, value)),
+        // String, ID and enums
+        (_, &JsonValue::String(ref string)) => Ok(Some(string.clone())),
+        _ => Err(invalid_value(definition, value))
+    }
+}
+
+// Gives the value of every variable of an operation, from the request or from its default.
+// Optional variables without a value are left out, so are the arguments that use them.
+pub fn resolve_variables(definitions: &Vec<VariableDefinition>, values: Option<&JsonValue>) -> Result<Vec<(String, String)>, GraphqlError> {
+    match values {
+        None | Some(&JsonValue::Object(_)) => {},
+        Some(_) => return Err(GraphqlError::Validation("Variables must be given as an object".to_string()))
+    }
+
+    let mut resolved: Vec<(String, String)> = Vec::new();
+    for (i, definition) in definitions.iter().enumerate() {
+        if definitions[..i].iter().any(|previous| previous.name == definition.name) {
+            return Err(GraphqlError::Validation("There can be only one variable named \"$".to_string() + &definition.name + "\""));
+        }
+        let value = match values.and_then(|values| values.get(&definition.name)) {
+            Some(value) => try!(value_to_argument(definition, value)),
+            None => None
+        };
+        match value.or(definition.default.clone()) {
+            Some(value) => resolved.push((definition.name.clone(), value)),
+            None => if definition.is_mandatory {
+                return Err(GraphqlError::Validation(
+                    "Variable \"$".to_string() + &definition.name + "\" of required type \"" + &definition.var_type + "!\" was not provided"
+                ));
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+// depth counts the selections being bound, it is limited like the nesting of a parsed query
+fn bind_params(definitions: &Vec<VariableDefinition>, query: QueryObject, values: &Vec<(String, String)>, used: &mut Vec<String>, depth: usize) -> Result<QueryObject, GraphqlError> {
+    if depth > parser::MAX_DEPTH {
+        return Err(GraphqlError::Validation("The selection is nested more than ".to_string() + &parser::MAX_DEPTH.to_string() + " levels deep"));
+    }
+    // the arguments left out lose their positions
+    let mut param_positions: Vec<(usize, usize)> = Vec::new();
+    let params = match query.params {
+        Some(params) => {
+            let mut bound: Vec<(String, String)> = Vec::new();
+            for (i, (name, value)) in params.into_iter().enumerate() {
+                let position = query.param_positions.get(i).cloned();
+                if !value.starts_with("$") {
+                    bound.push((name, value));
+                    param_positions.extend(position);
+                    continue;
+                }
+                let variable = value[1..].to_string();
+                if !definitions.iter().any(|definition| definition.name == variable) {
+                    return Err(GraphqlError::Validation("Variable \"".to_string() + &value + "\" is not defined"));
+                }
+                if let Some(&(_, ref variable_value)) = values.iter().find(|&&(ref variable_name, _)| *variable_name == variable) {
+                    bound.push((name, variable_value.clone()));
+                    param_positions.extend(position);
+                }
+                used.push(variable);
+            }
+            if bound.is_empty() {None} else {Some(bound)}
+        },
+        None => None
+    };
+    let attrs = match query.attrs {
+        Some(attrs) => {
+            let mut bound: Vec<QueryObject> = Vec::new();
+            for attr in attrs {
+                bound.push(try!(bind_params(definitions, attr, values, used, depth + 1)));
+            }
+            Some(bound)
+        },
+        None => None
+    };
+    Ok(QueryObject{name: query.name, alias: query.alias, params: params, attrs: attrs, position: query.position, param_positions: param_positions})
+}
+
+// Replaces the variable references in the arguments of an operation by their values
+pub fn bind_variables(definitions: &Vec<VariableDefinition>, query: QueryObject, values: &Vec<(String, String)>) -> Result<QueryObject, GraphqlError> {
+    let mut used: Vec<String> = Vec::new();
+    let query = try!(bind_params(definitions, query, values, &mut used, 0));
+    for definition in definitions {
+        if !used.contains(&definition.name) {
+            return Err(GraphqlError::Validation("Variable \"$".to_string() + &definition.name + "\" is never used"));
+        }
+    }
+    Ok(query)
+}
+
+#[test]
+fn test_bind_variables(){
+    let definitions = vec![
+        VariableDefinition{name: "race".to_string(), var_type: "String".to_string(), is_mandatory: false, default: Some("Elf".to_string())},
+        VariableDefinition{name: "strength".to_string(), var_type: "Int".to_string(), is_mandatory: false, default: None},
+        VariableDefinition{name: "id".to_string(), var_type: "Int".to_string(), is_mandatory: true, default: None}
+    ];
+    let query = QueryObject {
+        name: "Warrior".to_string(),
+        alias: None,
+        params: Some(vec![("race".to_string(), "$race".to_string()), ("strength".to_string(), "$strength".to_string()), ("id".to_string(), "$id".to_string())]),
+        attrs: Some(vec![field("name")]),
+        position: 0,
+        param_positions: Vec::new()
+    };
+
+    let values = resolve_variables(&definitions, Some(&JsonValue::Object(vec![("id".to_string(), JsonValue::Int(8))]))).unwrap();
+    assert_eq!(values, vec![("race".to_string(), "Elf".to_string()), ("id".to_string(), "8".to_string())]);
+    assert_eq!(
+        bind_variables(&definitions, query, &values),
+        Ok(QueryObject {
+            name: "Warrior".to_string(),
+            alias: None,
+            params: Some(vec![("race".to_string(), "Elf".to_string()), ("id".to_string(), "8".to_string())]),
+            attrs: Some(vec![field("name")]),
+            position: 0,
+            param_positions: Vec::new()
+        })
+    );
+
+    assert_eq!(
+        resolve_variables(&definitions, None),
+        Err(GraphqlError::Validation("Variable \"$id\" of required type \"Int!\" was not provided".to_string()))
+    );
+    assert_eq!(
+        resolve_variables(&definitions, Some(&JsonValue::Object(vec![("id".to_string(), JsonValue::String("8".to_string()))]))),
+        Err(GraphqlError::Validation("Variable \"$id\" got invalid value \"8\", expected type Int".to_string()))
+    );
+
+    let query = QueryObject {name: "Warrior".to_string(), alias: None, params: Some(vec![("id".to_string(), "$id".to_string())]), attrs: None, position: 0, param_positions: Vec::new()};
+    assert_eq!(bind_variables(&vec![], query, &vec![]), Err(GraphqlError::Validation("Variable \"$id\" is not defined".to_string())));
+}
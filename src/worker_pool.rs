@@ -0,0 +1,76 @@
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+use std::vec::Vec;
+
+use connection::*;
+use def::TargetPool;
+use error::GraphqlError;
+use serialize;
+
+// Runs requests on a fixed number of threads, each one owning a Connection with its own MySQL connection.
+// Requests are taken from a single FIFO queue by whichever worker is free first.
+pub struct WorkerPool {
+    sender: mpsc::Sender<GraphqlMsg>,
+    workers: Vec<thread::JoinHandle<()>>
+}
+
+impl WorkerPool {
+    pub fn new(number_of_workers: usize, target_pool: TargetPool, serializer: serialize::Serializer) -> WorkerPool {
+        let (sender, receiver) = mpsc::channel::<GraphqlMsg>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers: Vec<thread::JoinHandle<()>> = Vec::new();
+        for i in 0..number_of_workers {
+            let receiver = receiver.clone();
+            let target_pool = target_pool.clone();
+            let serializer = serializer.clone();
+            let mut connection = Connection::new(target_pool.clone(), serializer.clone());
+            let worker = thread::Builder::new().name("worker_".to_string() + &i.to_string()).spawn(move || {
+                loop {
+                    // the lock is released as soon as a message is taken, so the other workers can wait for the next one.
+                    // The queue stays usable if a worker panicked while holding it.
+                    let msg = receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+                    match msg {
+                        Ok(msg) => {
+                            // a request that panics fails its future (it is dropped) and the worker goes on with a new connection
+                            let processed = panic::catch_unwind(AssertUnwindSafe(|| {
+                                connection.push_request(msg);
+                                connection.process();
+                            }));
+                            if processed.is_err() {
+                                error!("A request panicked in worker {}", i);
+                                connection = Connection::new(target_pool.clone(), serializer.clone());
+                            }
+                        },
+                        // the pool was dropped
+                        Err(_) => break
+                    }
+                }
+            }).ok().expect("Failed to start a worker");
+            workers.push(worker);
+        }
+
+        WorkerPool {
+            sender: sender,
+            workers: workers
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn dispatch(&self, msg: GraphqlMsg) {
+        if let Err(mpsc::SendError(msg)) = self.sender.send(msg) {
+            match msg {
+                GraphqlMsg::Request{tx, ..} => tx.fail(GraphqlError::Connection("All workers have stopped".to_string())),
+                GraphqlMsg::Transaction{tx, ..} => tx.fail(GraphqlError::Connection("All workers have stopped".to_string())),
+                GraphqlMsg::Query{tx, ..} => tx.fail(GraphqlError::Connection("All workers have stopped".to_string())),
+                _ => {}
+            }
+        }
+    }
+}
@@ -16,6 +16,9 @@ extern crate env_logger;
 
 
 use rust_sql::graphql_pool::*;
+use rust_sql::{reader, printer};
+use rust_sql::error::GraphqlError;
+use rust_sql::json::JsonValue;
 use eventual::*;
 use mysql as my;
 use std::str;
@@ -23,6 +26,8 @@ use std::str::FromStr;
 use std::vec::Vec;
 use std::option::Option;
 use std::thread;
+use std::io::{Read, Write};
+use std::net::TcpStream;
 use nom::{IResult,digit};
 use nom::IResult::*;
 
@@ -211,31 +216,39 @@ fn test_eventual () {
     assert_eq!(61, res);
 }
 
+#[test]
+fn test_schema_round_trip () {
+    let (db, enums) = reader::extract_database_from_file(&(FILE_LOCATION.to_string()+"/"+FILE_NAME)).unwrap();
+    let printed = printer::print_schema(&reader::into_definitions(&db, &enums));
+    assert_eq!(reader::extract_database(&printed), Ok((db, enums)));
+    assert_eq!(printer::print_schema(&reader::extract_definitions(&printed).unwrap()), printed);
+}
+
 fn create_weapons(graph_ql_pool: &mut GraphQLPool){
-    graph_ql_pool.add(" { Weapon { name: Bow } }");
-    graph_ql_pool.add(" { Weapon { name: Spear } }");
-    graph_ql_pool.add(" { Weapon { name: Sword } }");
-    graph_ql_pool.add(" { Weapon { name: Axe } }");
+    graph_ql_pool.add(" { Weapon { name: Bow } }").await().unwrap();
+    graph_ql_pool.add(" { Weapon { name: Spear } }").await().unwrap();
+    graph_ql_pool.add(" { Weapon { name: Sword } }").await().unwrap();
+    graph_ql_pool.add(" { Weapon { name: Axe } }").await().unwrap();
 }
 fn create_warriors(graph_ql_pool: &mut GraphQLPool){
     for i in 1..11{
-        graph_ql_pool.add((" { Warrior { name: elf".to_string()+&i.to_string()+" race: Elf strength: 50 } }").as_str());
+        graph_ql_pool.add((" { Warrior { name: elf".to_string()+&i.to_string()+" race: Elf strength: 50 } }").as_str()).await().unwrap();
     }
     for i in 1..11{
-        graph_ql_pool.add((" { Warrior { name: human".to_string()+&i.to_string()+" race: Human strength: 50 } }").as_str());
+        graph_ql_pool.add((" { Warrior { name: human".to_string()+&i.to_string()+" race: Human strength: 50 } }").as_str()).await().unwrap();
     }
     for i in 1..11{
-        graph_ql_pool.add((" { Warrior { name: orc".to_string()+&i.to_string()+" race: Orc strength: 50 } }").as_str());
+        graph_ql_pool.add((" { Warrior { name: orc".to_string()+&i.to_string()+" race: Orc strength: 50 } }").as_str()).await().unwrap();
     }
     for i in 1..11{
-        graph_ql_pool.add((" { Warrior { name: uruk".to_string()+&i.to_string()+" race: Uruk strength: 50 } }").as_str());
+        graph_ql_pool.add((" { Warrior { name: uruk".to_string()+&i.to_string()+" race: Uruk strength: 50 } }").as_str()).await().unwrap();
     }
 }
 fn create_leaders(graph_ql_pool: &mut GraphQLPool){
-    graph_ql_pool.add(" { Leader { name: Galadriel wisdom: 50 } }");
-    graph_ql_pool.add(" { Leader { name: Aragorn wisdom: 50 } }");
-    graph_ql_pool.add(" { Leader { name: Sauron wisdom: 50 } }");
-    graph_ql_pool.add(" { Leader { name: Saruman wisdom: 50 } }");
+    graph_ql_pool.add(" { Leader { name: Galadriel wisdom: 50 } }").await().unwrap();
+    graph_ql_pool.add(" { Leader { name: Aragorn wisdom: 50 } }").await().unwrap();
+    graph_ql_pool.add(" { Leader { name: Sauron wisdom: 50 } }").await().unwrap();
+    graph_ql_pool.add(" { Leader { name: Saruman wisdom: 50 } }").await().unwrap();
 }
 fn create_relations(graph_ql_pool: &mut GraphQLPool){
     //alliances
@@ -294,19 +307,18 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_warrior_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
-        assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'elf8\',\n\t\t\t\"race\": \'Elf\',\n\t\t\t\"wears\": [  \n\t\t\t\t{\n\t\t\t\t\t\"name\": \'Bow\'\n\t\t\t\t}\n\t\t\t]\n\t\t}\n\t}\n}\n"
-        );
-    });
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Warrior\":[{\"name\":\"elf8\",\"race\":\"Elf\",\"wears\":[{\"name\":\"Bow\"}]}]}}"
+    );
 
     let get_warriors_query =
     "{
@@ -316,19 +328,18 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_warriors_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
-        assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human1\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human2\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human3\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human4\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human5\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human6\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human7\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human8\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human9\'\n\t\t},\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human10\'\n\t\t}\n\t}\n}\n"
-        );
-    });
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Warrior\":[{\"name\":\"human1\"},{\"name\":\"human2\"},{\"name\":\"human3\"},{\"name\":\"human4\"},{\"name\":\"human5\"},{\"name\":\"human6\"},{\"name\":\"human7\"},{\"name\":\"human8\"},{\"name\":\"human9\"},{\"name\":\"human10\"}]}}"
+    );
 
     let get_leader_and_his_warriors_query =
     "{
@@ -342,27 +353,406 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_leader_and_his_warriors_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Leader\":[{\"name\":\"Galadriel\",\"wisdom\":50,\"leads\":[{\"name\":\"elf1\"},{\"name\":\"elf2\"},{\"name\":\"elf3\"},{\"name\":\"elf4\"},{\"name\":\"elf5\"},{\"name\":\"elf6\"},{\"name\":\"elf7\"},{\"name\":\"elf8\"},{\"name\":\"elf9\"},{\"name\":\"elf10\"}]}]}}"
+    );
+
+    let get_leader_with_aliases_query =
+    "{
+        Leader (id: 2){
+            title: name
+            first: leads (name: human1) {
+                warrior: name
+            }
+            second: leads (name: human2) {
+                name
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_leader_with_aliases_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"title\":\"Aragorn\",\"first\":[{\"warrior\":\"human1\"}],\"second\":[{\"name\":\"human2\"}]}]}}"
+    );
+
+    let get_nested_relations_query =
+    "{
+        Leader (id: 1){
+            name
+            allies {
+                name
+                leads (name: human1) {
+                    name
+                    wears {
+                        name
+                    }
+                }
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_nested_relations_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Galadriel\",\"allies\":[{\"name\":\"Aragorn\",\"leads\":[{\"name\":\"human1\",\"wears\":[{\"name\":\"Spear\"}]}]}]}]}}"
+    );
+
+    let get_leaders_and_their_allies_query =
+    "{
+        Leader {
+            name
+            allies {
+                name
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_leaders_and_their_allies_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Galadriel\",\"allies\":[{\"name\":\"Aragorn\"}]},{\"name\":\"Aragorn\",\"allies\":[]},{\"name\":\"Sauron\",\"allies\":[{\"name\":\"Saruman\"}]},{\"name\":\"Saruman\",\"allies\":[]}]}}"
+    );
+
+    // only the leader of orc2 lists it, the leaders before it do not take its warriors
+    let get_leaders_of_orc2_query =
+    "{
+        Leader {
+            name
+            leads (name: orc2) {
+                name
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_leaders_of_orc2_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Galadriel\",\"leads\":[]},{\"name\":\"Aragorn\",\"leads\":[]},{\"name\":\"Sauron\",\"leads\":[{\"name\":\"orc2\"}]},{\"name\":\"Saruman\",\"leads\":[]}]}}"
+    );
+
+    let get_warriors_by_filter_query =
+    "{
+        Warrior (race: Orc name_in: [orc1, orc3, elf1] OR: [{name_startsWith: orc1} {NOT: {name_ne: orc3}}]) {
+            name
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_warriors_by_filter_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"name\":\"orc1\"},{\"name\":\"orc3\"}]}}"
+    );
+
+    let get_page_of_elves_query =
+    "{
+        Warrior (race: Elf orderBy: {name: DESC} first: 2 offset: 1) {
+            name
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_page_of_elves_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"name\":\"elf8\"},{\"name\":\"elf7\"}]}}"
+    );
+
+    // each leader lists its own last two warriors
+    let get_page_of_leaders_query =
+    "{
+        Leader (first: 2 offset: 1) {
+            name
+            leads (orderBy: {id: DESC} first: 2) {
+                name
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_page_of_leaders_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Aragorn\",\"leads\":[{\"name\":\"human10\"},{\"name\":\"human9\"}]},{\"name\":\"Sauron\",\"leads\":[{\"name\":\"orc10\"},{\"name\":\"orc9\"}]}]}}"
+    );
+
+    let get_elves_connection_query =
+    "{
+        WarriorConnection (race: Elf first: 2 after: \"Y3Vyc29yOjM=\") {
+            edges {
+                cursor
+                node {
+                    name
+                }
+            }
+            pageInfo {
+                hasNextPage
+                endCursor
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_elves_connection_query).await().unwrap(),
+        "{\"data\":{\"WarriorConnection\":{\"edges\":[{\"cursor\":\"Y3Vyc29yOjQ=\",\"node\":{\"name\":\"elf4\"}},{\"cursor\":\"Y3Vyc29yOjU=\",\"node\":{\"name\":\"elf5\"}}],\"pageInfo\":{\"hasNextPage\":true,\"endCursor\":\"Y3Vyc29yOjU=\"}}}}"
+    );
+
+    let get_leads_connection_query =
+    "{
+        Leader (id: 3) {
+            name
+            leadsConnection (first: 2) {
+                edges {
+                    node {
+                        name
+                    }
+                }
+                pageInfo {
+                    hasNextPage
+                    hasPreviousPage
+                }
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_leads_connection_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Sauron\",\"leadsConnection\":{\"edges\":[{\"node\":{\"name\":\"orc1\"}},{\"node\":{\"name\":\"orc2\"}}],\"pageInfo\":{\"hasNextPage\":true,\"hasPreviousPage\":false}}}]}}"
+    );
+
+    let get_warriors_by_race_query =
+    "{
+        Warrior_aggregate (strength_gte: 50 groupBy: [race]) {
+            race
+            count
+            average: avg(strength)
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_warriors_by_race_query).await().unwrap(),
+        "{\"data\":{\"Warrior_aggregate\":[{\"race\":\"Elf\",\"count\":10,\"average\":50.0},{\"race\":\"Human\",\"count\":10,\"average\":50.0},\
+         {\"race\":\"Orc\",\"count\":10,\"average\":50.0},{\"race\":\"Uruk\",\"count\":10,\"average\":50.0}]}}"
+    );
+
+    let get_number_of_led_warriors_query =
+    "{
+        Leader {
+            name
+            leads_count
+            humans: leads_count (race: Human)
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_number_of_led_warriors_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Galadriel\",\"leads_count\":10,\"humans\":0},{\"name\":\"Aragorn\",\"leads_count\":10,\"humans\":10},\
+         {\"name\":\"Sauron\",\"leads_count\":10,\"humans\":0},{\"name\":\"Saruman\",\"leads_count\":10,\"humans\":0}]}}"
+    );
+
+    let get_weapon_type_query =
+    "{
+        __type (name: \"Weapon\") {
+            name
+            kind
+            fields {
+                name
+                type { kind name ofType { name } }
+            }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_weapon_type_query).await().unwrap(),
+        "{\"data\":{\"__type\":{\"name\":\"Weapon\",\"kind\":\"OBJECT\",\"fields\":[\
+         {\"name\":\"id\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"ID\"}}},\
+         {\"name\":\"name\",\"type\":{\"kind\":\"NON_NULL\",\"name\":null,\"ofType\":{\"name\":\"String\"}}}]}}}"
+    );
+
+    let get_typename_query =
+    "{
+        Warrior (id: 1) {
+            __typename
+            name
+            wears { __typename name }
+        }
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_typename_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"__typename\":\"Warrior\",\"name\":\"elf1\",\"wears\":[{\"__typename\":\"Weapon\",\"name\":\"Bow\"}]}]}}"
+    );
+
+    let report = graph_ql_pool.mysql_query(
+        &("SELECT Leader.name, COUNT(*) AS warriors FROM ".to_string() + DB_NAME + ".Leader, " + DB_NAME + ".Leader_leads_Warrior AS leads \
+           WHERE leads.origin_id = Leader.id GROUP BY Leader.id ORDER BY Leader.id LIMIT 2;")
+    ).await().unwrap();
+    assert_eq!(report.columns, vec!["name".to_string(), "warriors".to_string()]);
+    assert_eq!(report.rows, vec![
+        vec![JsonValue::String("Galadriel".to_string()), JsonValue::Int(10)],
+        vec![JsonValue::String("Aragorn".to_string()), JsonValue::Int(10)]
+    ]);
+
+    let broken_query =
+    "{
+        Warrior (id: 8){
+            name
+    }";
+
+    future = graph_ql_pool.get(broken_query);
+    let data = future.await();
+    match data {
+        Err(AsyncError::Failed(GraphqlError::Parse{..})) => {},
+        other => panic!("Expected a parse error, got {:?}", other)
+    }
+
+    let unknown_relation_query =
+    "{
+        Warrior (id: 8){
+            name
+            rides {
+                name
+            }
+        }
+    }";
+
+    future = graph_ql_pool.get(unknown_relation_query);
+    let data = future.await();
+    match data {
+        Err(AsyncError::Failed(GraphqlError::Invalid(errors))) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Cannot query field \"rides\" on type \"Warrior\"");
+            assert_eq!(&unknown_relation_query[errors[0].position..errors[0].position + 5], "rides");
+        },
+        other => panic!("Expected a validation error, got {:?}", other)
+    }
+
+    let get_warrior_by_id_query = "query WarriorById($id: Int!) { Warrior (id: $id) { name } }";
+    let elf_futures: Vec<Future<String, GraphqlError>> = (1..11).map(|id| {
+        graph_ql_pool.get_with_variables(get_warrior_by_id_query, &[("id", JsonValue::Int(id))])
+    }).collect();
+    for (i, elf_future) in elf_futures.into_iter().enumerate() {
         assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Leader\": {\n\t\t\t\"name\": \'Galadriel\',\n\t\t\t\"wisdom\": 50,\n\t\t\t\"leads\": [  \n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf1\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf2\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf3\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf4\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf5\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf6\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf7\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf8\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf9\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'elf10\'\n\t\t\t\t}\n\t\t\t]\n\t\t}\n\t}\n}\n"
+            elf_future.await().unwrap(),
+            "{\"data\":{\"Warrior\":[{\"name\":\"elf".to_string() + &(i + 1).to_string() + "\"}]}}"
         );
-    });
+    }
+
+    let get_warrior_with_fragments_query = "{
+        Warrior (id: 8) {
+            ...WarriorFields
+            ... on Warrior {
+                strength
+                name
+            }
+        }
+    }
+    fragment WarriorFields on Warrior {
+        name
+        race
+    }";
+    assert_eq!(
+        graph_ql_pool.get(get_warrior_with_fragments_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"name\":\"elf8\",\"race\":\"Elf\",\"strength\":50}]}}"
+    );
+
+    future = graph_ql_pool.get("{ Warrior (id: 8) { ...WarriorFields } } fragment WarriorFields on Warrior { ...WarriorFields }");
+    match future.await() {
+        Err(AsyncError::Failed(GraphqlError::Validation(message))) => assert_eq!(message, "Cannot spread fragment \"WarriorFields\" within itself via WarriorFields"),
+        other => panic!("Expected a fragment cycle error, got {:?}", other)
+    }
+
+    future = graph_ql_pool.add(" { Warrior { name: gimli race: Dwarf strength: 60 } }");
+    let data = future.await();
+    match data {
+        Err(AsyncError::Failed(GraphqlError::Invalid(_))) => {},
+        other => panic!("Expected a validation error, got {:?}", other)
+    }
+
+    let add_warrior_query = "{
+        Warrior {
+            name: gimli
+            race: Human
+            strength: 60
+            id
+            name
+        }
+    }";
+
+    assert_eq!(
+        graph_ql_pool.add(add_warrior_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"id\":41,\"name\":\"gimli\"}]}}"
+    );
+
+    let add_leader_with_warriors_query = "{
+        Leader {
+            name: Elrond
+            wisdom: 90
+            leads {
+                Warrior (name: gimli)
+                Warrior {
+                    name: legolas
+                    race: Elf
+                    strength: 70
+                }
+            }
+        }
+    }";
+
+    graph_ql_pool.add(add_leader_with_warriors_query).await().unwrap();
+
+    let get_Elrond_query = "{
+        Leader (name: Elrond) {
+            name
+            leads {
+                name
+            }
+        }
+    }";
+
+    assert_eq!(
+        graph_ql_pool.get(get_Elrond_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Elrond\",\"leads\":[{\"name\":\"gimli\"},{\"name\":\"legolas\"}]}]}}"
+    );
+
+    let update_Elrond_warriors_query = "{
+        Leader (name: Elrond) {
+            wisdom: 95
+            leads {
+                disconnect {
+                    Warrior (name: gimli)
+                }
+                connect {
+                    Warrior (name: elf1)
+                    Warrior (name: legolas)
+                }
+            }
+        }
+    }";
+
+    graph_ql_pool.update(update_Elrond_warriors_query).await().unwrap();
+    assert_eq!(
+        graph_ql_pool.get(get_Elrond_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Elrond\",\"leads\":[{\"name\":\"elf1\"},{\"name\":\"legolas\"}]}]}}"
+    );
+
+    graph_ql_pool.update("{ Leader (name: Elrond) { leads { set { Warrior (name: gimli) } } } }").await().unwrap();
+    assert_eq!(
+        graph_ql_pool.get(get_Elrond_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Elrond\",\"leads\":[{\"name\":\"gimli\"}]}]}}"
+    );
+
+    future = graph_ql_pool.update("{ Leader (name: Elrond) { leads { disconnect { Warrior { name: boromir } } } } }");
+    match future.await() {
+        Err(AsyncError::Failed(GraphqlError::Invalid(_))) => {},
+        other => panic!("Expected a validation error, got {:?}", other)
+    }
+
+    future = graph_ql_pool.add("{ Leader { name: Elendil leads { Weapon (name: Bow) } } }");
+    match future.await() {
+        Err(AsyncError::Failed(GraphqlError::Invalid(_))) => {},
+        other => panic!("Expected a validation error, got {:?}", other)
+    }
 
     let update_leader_query = "{
         Leader (id:3) {
             wisdom: 75
+            name
+            wisdom
         }
     }";
 
-    graph_ql_pool.update(update_leader_query);
+    assert_eq!(
+        graph_ql_pool.update(update_leader_query).await().unwrap(),
+        "{\"data\":{\"Leader\":[{\"name\":\"Sauron\",\"wisdom\":75}]}}"
+    );
 
     let get_Sauron_query =
     "{
@@ -373,25 +763,34 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_Sauron_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
-        assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Leader\": {\n\t\t\t\"name\": \'Sauron\',\n\t\t\t\"wisdom\": 75\n\t\t}\n\t}\n}\n"
-        );
-    });
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Leader\":[{\"name\":\"Sauron\",\"wisdom\":75}]}}"
+    );
 
     let delete_warrior_query = "{
-        Warrior (id:22)
+        Warrior (id:22) {
+            name
+        }
     }";
 
-    graph_ql_pool.delete(delete_warrior_query);
+    assert_eq!(
+        graph_ql_pool.delete(delete_warrior_query).await().unwrap(),
+        "{\"data\":{\"Warrior\":[{\"name\":\"orc2\"}]}}"
+    );
+    let links = graph_ql_pool.mysql_query(
+        &("SELECT (SELECT COUNT(*) FROM ".to_string() + DB_NAME + ".Leader_leads_Warrior WHERE target_id = 22) + \
+           (SELECT COUNT(*) FROM " + DB_NAME + ".Warrior_wears_Weapon WHERE origin_id = 22) AS links;")
+    ).await().unwrap();
+    assert_eq!(links.rows, vec![vec![JsonValue::Int(0)]]);
 
     let get_leader_and_his_warriors_query =
     "{
@@ -405,25 +804,53 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_leader_and_his_warriors_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
-        assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Leader\": {\n\t\t\t\"name\": \'Sauron\',\n\t\t\t\"wisdom\": 75,\n\t\t\t\"leads\": [  \n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc1\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc3\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc4\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc5\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc6\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc7\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc8\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc9\'\n\t\t\t\t},\n\t\t\t\t{\n\t\t\t\t\t\"name\": \'orc10\'\n\t\t\t\t}\n\t\t\t]\n\t\t}\n\t}\n}\n"
-        );
-    });
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Leader\":[{\"name\":\"Sauron\",\"wisdom\":75,\"leads\":[{\"name\":\"orc1\"},{\"name\":\"orc3\"},{\"name\":\"orc4\"},{\"name\":\"orc5\"},{\"name\":\"orc6\"},{\"name\":\"orc7\"},{\"name\":\"orc8\"},{\"name\":\"orc9\"},{\"name\":\"orc10\"}]}]}}"
+    );
+
+    let responses = graph_ql_pool.transaction(&[
+        ("add", "{ Weapon { name: Dagger id } }"),
+        ("update", "{ Weapon (name: Dagger) { name: Knife name } }")
+    ]).await().unwrap();
+    assert_eq!(
+        responses,
+        vec![
+            "{\"data\":{\"Weapon\":[{\"id\":5}]}}".to_string(),
+            "{\"data\":{\"Weapon\":[{\"name\":\"Knife\"}]}}".to_string()
+        ]
+    );
+
+    // the second operation fails, so the Mace is not added either
+    let rolled_back = graph_ql_pool.transaction(&[
+        ("add", "{ Weapon { name: Mace } }"),
+        ("update", "{ Weapon (name: Mace) { sharpness: 10 } }")
+    ]).await();
+    assert!(rolled_back.is_err());
+    // raw SQL could commit the transaction, it is rejected before anything is executed
+    let raw_sql = graph_ql_pool.transaction(&[
+        ("add", "{ Weapon { name: Mace } }"),
+        ("query", "COMMIT")
+    ]).await();
+    assert!(raw_sql.is_err());
+    assert_eq!(
+        graph_ql_pool.get("{ Weapon (name: Mace) { name } }").await().unwrap(),
+        "{\"data\":{\"Weapon\":[]}}"
+    );
 
     let delete_weapons_query = "{
         Weapon
     }";
 
-    graph_ql_pool.delete(delete_weapons_query);
+    graph_ql_pool.delete(delete_weapons_query).await().unwrap();
 
     let get_warriors_query =
     "{
@@ -437,19 +864,77 @@ fn test_queries (graph_ql_pool: &mut GraphQLPool){
     }";
 
     future = graph_ql_pool.get(get_warriors_query);
-    future.receive(move |data| {
-        let result = match data {
-            Ok(res) => res,
-            Err(err) => {
-                panic!("Error: {:?}",err);
-                return;
-            },
-        };
-        assert_eq!(
-            result,
-            "{\n\t\"data\": {\n\t\t\"Warrior\": {\n\t\t\t\"name\": \'human1\',\n\t\t\t\"strength\": 50,\n\t\t\t\"wears\": [ \n\t\t\t]\n\t\t}\n\t}\n}\n"
-        );
-    });
+    let data = future.await();
+    let result = match data {
+        Ok(res) => res,
+        Err(err) => {
+            panic!("Error: {:?}",err);
+            return;
+        },
+    };
+    assert_eq!(
+        result,
+        "{\"data\":{\"Warrior\":[{\"name\":\"human1\",\"strength\":50,\"wears\":[]}]}}"
+    );
+
+    graph_ql_pool.mysql_query("INSERT INTO Weapon (name) VALUES ('Bow of \\\"Lorien\\\"\\n');").await().unwrap();
+    assert_eq!(
+        graph_ql_pool.get("{ Weapon { name } }").await().unwrap(),
+        "{\"data\":{\"Weapon\":[{\"name\":\"Bow of \\\"Lorien\\\"\\n\"}]}}"
+    );
+}
+
+fn http_request (request: &str) -> String {
+    let mut stream = TcpStream::connect("127.0.0.1:10000").unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn test_http_queries (){
+    let body = "{\"query\": \"{ Leader (id: 3) { name } }\", \"variables\": null}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"Leader\":[{\"name\":\"Sauron\"}]}}"));
+
+    let response = http_request("GET /graphql?query=%7B+Leader+%28id%3A+3%29+%7B+name+%7D+%7D HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"Leader\":[{\"name\":\"Sauron\"}]}}"));
+
+    let body = "{\"query\": \"query Leader($id: Int!) { Leader (id: $id) { name } }\", \"variables\": {\"id\": 3}}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"Leader\":[{\"name\":\"Sauron\"}]}}"));
+
+    let body = "{\"query\": \"query Leaders { Leader (id: 3) { name } } query Weapons { Weapon { name } }\", \"operationName\": \"Leaders\"}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"Leader\":[{\"name\":\"Sauron\"}]}}"));
+
+    let body = "{\"query\": \"query Leaders { Leader (id: 3) { name } } query Weapons { Weapon { name } }\"}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains("an operation name must be provided"));
+
+    let body = "{\"query\": \"mutation { add { Weapon { name: Axe } } }\"}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"status\":\"add response completed\"}}"));
+
+    let body = "{\"query\": \"mutation RemoveAxe { delete { Weapon (name: Axe) { name } } }\"}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"data\":{\"Weapon\":[{\"name\":\"Axe\"}]}}"));
+
+    let response = http_request("GET /graphql?query=mutation+%7B+add+%7B+Weapon+%7B+name%3A+Axe+%7D+%7D+%7D HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains("mutations are sent with POST"));
+
+    let body = "{\"query\": \"{ Leader (id: 3) { name }\"}";
+    let response = http_request(&("POST /graphql HTTP/1.1\r\nContent-Length: ".to_string() + &body.len().to_string() + "\r\n\r\n" + body));
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains("\"errors\":[{\"message\":\"Parse error at 25"));
 }
 
 #[test]
@@ -459,7 +944,7 @@ fn test_db_creation_and_crud () {
         mysql_connection.as_str(),
         DB_NAME,
         &(FILE_LOCATION.to_string()+"/"+FILE_NAME)
-    );
+    ).unwrap();
 
     create_weapons(&mut graph_ql_pool);
     create_warriors(&mut graph_ql_pool);
@@ -469,4 +954,32 @@ fn test_db_creation_and_crud () {
 
     test_queries(&mut graph_ql_pool);
     thread::sleep_ms(10000);
+
+    test_http_queries();
+
+    test_migrations(&mut graph_ql_pool, mysql_connection.as_str());
+}
+
+fn test_migrations (graph_ql_pool: &mut GraphQLPool, mysql_connection: &str){
+    let schema_file = FILE_LOCATION.to_string()+"/"+FILE_NAME;
+    let plan = GraphQLPool::plan_migration(mysql_connection, DB_NAME, &schema_file).unwrap();
+    assert_eq!(plan.statements, Vec::<String>::new());
+    assert_eq!(plan.destructive_statements, Vec::<String>::new());
+
+    graph_ql_pool.mysql_query(&("ALTER TABLE ".to_string() + DB_NAME + ".Leader DROP COLUMN wisdom")).await().unwrap();
+    graph_ql_pool.mysql_query(&("ALTER TABLE ".to_string() + DB_NAME + ".Leader ADD COLUMN height INT")).await().unwrap();
+    let plan = GraphQLPool::plan_migration(mysql_connection, DB_NAME, &schema_file).unwrap();
+    assert_eq!(plan.statements, vec!["ALTER TABLE lotr_db.Leader ADD COLUMN wisdom INT;".to_string()]);
+    assert_eq!(plan.destructive_statements, vec!["ALTER TABLE lotr_db.Leader DROP COLUMN height;".to_string()]);
+
+    let migration = GraphQLPool::apply_migration(mysql_connection, DB_NAME, &schema_file).unwrap();
+    assert_eq!(migration.destructive_statements, plan.destructive_statements);
+    let plan = GraphQLPool::plan_migration(mysql_connection, DB_NAME, &schema_file).unwrap();
+    assert_eq!(plan.statements, Vec::<String>::new());
+    assert_eq!(plan.destructive_statements, Vec::<String>::new());
+    let history = graph_ql_pool.mysql_query(&("SELECT version, applied_statements, status FROM ".to_string() + DB_NAME + ".schema_migrations ORDER BY id")).await().unwrap();
+    assert_eq!(history.rows, vec![
+        vec![JsonValue::String(plan.version.clone()), JsonValue::Int(6), JsonValue::String("applied".to_string())],
+        vec![JsonValue::String(plan.version.clone()), JsonValue::Int(2), JsonValue::String("applied".to_string())]
+    ]);
 }